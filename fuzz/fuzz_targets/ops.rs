@@ -0,0 +1,32 @@
+//! Reads the input as pairs of bytes, an operation and a value,
+//! applies them to an `ArenaTree` and a `BTreeSet` alike and checks they agree.
+//!
+//! Run with `cargo fuzz run ops`.
+
+#![no_main]
+
+use bst::arena::ArenaTree;
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeSet;
+
+fuzz_target!(|data: &[u8]| {
+    let mut t = ArenaTree::default();
+    let mut oracle = BTreeSet::new();
+    for op in data.chunks_exact(2) {
+        let val = op[1];
+        match op[0] % 3 {
+            0 => {
+                let id = t.insert(val);
+                oracle.insert(val);
+                assert_eq!(t.get(id), Some(&val));
+            }
+            1 => assert_eq!(t.delete(&val), oracle.remove(&val)),
+            _ => assert_eq!(t.contains(&val), oracle.contains(&val)),
+        }
+        if let Err(err) = t.validate() {
+            panic!("{} after {:?}", err, op);
+        }
+        assert_eq!(t.size(), oracle.len());
+    }
+    assert!(t.iter().eq(oracle.iter()));
+});
@@ -0,0 +1,82 @@
+//! proptest support, enabled by the `proptest` feature.
+//!
+//! `ArenaTree<T>` implements `Arbitrary`, and the strategies behind it are exposed
+//! so code embedding a tree can generate trees and operation sequences of its own.
+
+use crate::arena::ArenaTree;
+use proptest::arbitrary::{any_with, Arbitrary};
+use proptest::collection::{vec, SizeRange};
+use proptest::prop_oneof;
+use proptest::strategy::{BoxedStrategy, Strategy};
+
+/// Operation on a tree, see `ops`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<T> {
+    Insert(T),
+    Delete(T),
+    Contains(T),
+}
+
+/// Trees made by inserting values of `element` in the order they are drawn,
+/// so every shape a sequence of inserts can build is covered.
+pub fn arena_tree<S>(
+    element: S,
+    size: impl Into<SizeRange>,
+) -> impl Strategy<Value = ArenaTree<S::Value>>
+where
+    S: Strategy,
+    S::Value: Ord,
+{
+    vec(element, size).prop_map(ArenaTree::from_vec)
+}
+
+/// Sequences of operations on values of `element`.
+pub fn ops<S>(element: S, size: impl Into<SizeRange>) -> impl Strategy<Value = Vec<Op<S::Value>>>
+where
+    S: Strategy + Clone,
+{
+    let op = prop_oneof![
+        element.clone().prop_map(Op::Insert),
+        element.clone().prop_map(Op::Delete),
+        element.prop_map(Op::Contains),
+    ];
+    vec(op, size)
+}
+
+impl<T> Arbitrary for ArenaTree<T>
+where
+    T: Arbitrary + Ord + 'static,
+{
+    type Parameters = T::Parameters;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+        arena_tree(any_with::<T>(args), 0..100).boxed()
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn arbitrary_ops_match_btreeset(
+        t in proptest::arbitrary::any::<ArenaTree<u8>>(),
+        ops in ops(proptest::arbitrary::any::<u8>(), 0..200),
+    ) {
+        use proptest::prop_assert_eq;
+        let mut t = t;
+        let mut oracle: std::collections::BTreeSet<_> = t.iter().copied().collect();
+        for op in ops {
+            match op {
+                Op::Insert(val) => {
+                    t.insert(val);
+                    oracle.insert(val);
+                }
+                Op::Delete(val) => prop_assert_eq!(t.delete(&val), oracle.remove(&val)),
+                Op::Contains(val) => prop_assert_eq!(t.contains(&val), oracle.contains(&val)),
+            }
+            prop_assert_eq!(t.validate(), Ok(()));
+            prop_assert_eq!(t.size(), oracle.len());
+        }
+        proptest::prop_assert!(t.iter().eq(oracle.iter()));
+    }
+}
@@ -1,37 +1,333 @@
+use crate::compare::{Comparator, KeyFn, Natural, Reversed};
+use crate::slots::{slot, Slots};
+use std::borrow::Borrow;
 use std::cmp::Ordering;
+use std::collections::{BTreeSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+#[cfg(feature = "u32-index")]
+use std::num::NonZeroU32 as NonZeroId;
+#[cfg(not(feature = "u32-index"))]
+use std::num::NonZeroUsize as NonZeroId;
+use std::ops::{Bound, ControlFlow, Deref, RangeBounds};
+use std::sync::Arc;
 
-#[derive(Debug)]
-pub struct Node<T> {
-    idx: usize,
+/// Index of a node inside the arena.
+#[cfg(not(feature = "u32-index"))]
+pub type NodeId = usize;
+/// Index of a node inside the arena.
+/// Narrowed by the `u32-index` feature to halve the size of the links.
+#[cfg(feature = "u32-index")]
+pub type NodeId = u32;
+
+/// Whether link integrity is checked and traversals guard against cycles,
+/// always on in debug builds and kept in release builds by the `checked` feature.
+const CHECKED: bool = cfg!(any(debug_assertions, feature = "checked"));
+
+/// Snapshot of the arena usage and tree shape, see `ArenaTree::stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaStats {
+    /// Number of nodes in the tree.
+    pub live: usize,
+    /// Number of vacant slots left by deletions, waiting to be reused.
+    pub tombstones: usize,
+    /// Number of nodes the arena can hold without reallocating.
+    pub capacity: usize,
+    /// Number of nodes on the longest root-to-leaf path.
+    pub height: usize,
+    /// Mean number of edges from the root to a node.
+    pub average_depth: f64,
+}
+
+/// Why `ArenaTree::try_insert` and the other fallible inserts didn't insert a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InsertError {
+    /// An equal value is already present, at this node.
+    AlreadyPresent(NodeId),
+    /// The tree already holds as many values as its `max_len`.
+    Full,
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertError::AlreadyPresent(id) => write!(f, "value already present at node #{}", id),
+            InsertError::Full => write!(f, "tree is full"),
+        }
+    }
+}
+
+impl Error for InsertError {}
+
+/// Node id paired with the generation of its arena slot.
+/// Unlike a bare `NodeId`, it stops resolving once the node is deleted,
+/// even if the slot is later reused by another node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle {
+    id: NodeId,
+    generation: u32,
+}
+
+impl Handle {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+}
+
+/// Optional link to another node, stored as `id + 1` so that
+/// `Option` fits in the niche and takes no discriminant bytes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Link(Option<NonZeroId>);
+
+impl Link {
+    pub(crate) const NONE: Self = Link(None);
+
+    pub(crate) fn new(id: Option<NodeId>) -> Self {
+        Link(id.map(|id| {
+            id.checked_add(1)
+                .and_then(NonZeroId::new)
+                .expect("node id overflow")
+        }))
+    }
+
+    pub(crate) fn get(self) -> Option<NodeId> {
+        self.0.map(|id| id.get() - 1)
+    }
+}
+
+impl fmt::Debug for Link {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node<T, M = ()> {
+    idx: NodeId,
     val: T,
-    parent: Option<usize>,
-    left: Option<usize>,
-    right: Option<usize>,
+    meta: M,
+    parent: Link,
+    left: Link,
+    right: Link,
+}
+
+/// Binary search tree keeping its nodes in an arena, linked by `NodeId`,
+/// with per-node metadata `M` for the balanced variants built on it.
+///
+/// # Balanced variants
+///
+/// `AvlArenaTree`, `RbArenaTree`, `SplayArenaTree`, `Treap`, `ScapegoatArenaTree`,
+/// `WbtArenaTree` and `AugmentedTree` each wrap an `ArenaTree`. Its read-only API is
+/// available on them through `Deref`, while mutations go through their own methods,
+/// as there's no `DerefMut` to break the balance with. Rebalancing relinks nodes
+/// without moving them, so node ids stay valid across those mutations.
+#[derive(Debug)]
+pub struct ArenaTree<T, C = Natural, M = ()> {
+    root_id: NodeId,
+    arena: Slots<Node<T, M>>,
+    cmp: C,
+    compaction_threshold: Option<f64>,
+    max_len: Option<usize>,
+}
+
+impl<T> Default for ArenaTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> From<BTreeSet<T>> for ArenaTree<T>
+where
+    T: Ord,
+{
+    /// Balanced in O(n) as the set is sorted already, see `from_sorted_iter`.
+    fn from(set: BTreeSet<T>) -> Self {
+        Self::from_sorted_iter(set)
+    }
+}
+
+impl<T, C, M> From<ArenaTree<T, C, M>> for BTreeSet<T>
+where
+    T: Ord,
+{
+    fn from(tree: ArenaTree<T, C, M>) -> Self {
+        tree.into_sorted_vec().into_iter().collect()
+    }
+}
+
+/// Clones into a dense arena, squeezing out the gaps left by deletions.
+/// Node ids of the clone are remapped accordingly.
+impl<T, C, M> Clone for ArenaTree<T, C, M>
+where
+    T: Clone,
+    C: Clone,
+    M: Clone,
+{
+    fn clone(&self) -> Self {
+        let remap = self.arena.dense_ids();
+        let remap = |id: NodeId| remap[slot(id)].expect("link into vacant slot");
+        let mut arena = Slots::with_capacity(self.arena.len());
+        for (id, node) in self.arena.iter() {
+            arena.push(Node {
+                idx: remap(id),
+                val: node.val.clone(),
+                meta: node.meta.clone(),
+                parent: Link::new(node.parent().map(remap)),
+                left: Link::new(node.left().map(remap)),
+                right: Link::new(node.right().map(remap)),
+            });
+        }
+        Self {
+            root_id: if arena.is_empty() {
+                0
+            } else {
+                remap(self.root_id)
+            },
+            arena,
+            cmp: self.cmp.clone(),
+            compaction_threshold: self.compaction_threshold,
+            max_len: self.max_len,
+        }
+    }
+}
+
+/// Frozen view of an `ArenaTree`, taken by `ArenaTree::snapshot`. Cloning it is O(1).
+/// The read-only API of `ArenaTree` is available through `Deref`.
+#[derive(Debug)]
+pub struct Snapshot<T, C = Natural, M = ()> {
+    tree: Arc<ArenaTree<T, C, M>>,
+}
+
+impl<T, C, M> Clone for Snapshot<T, C, M> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: Arc::clone(&self.tree),
+        }
+    }
+}
+
+impl<T, C, M> Deref for Snapshot<T, C, M> {
+    type Target = ArenaTree<T, C, M>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, C, M> ArenaTree<T, C, M>
+where
+    T: Clone,
+    C: Clone,
+    M: Clone,
+{
+    /// Frozen view of the tree as it is now, unaffected by later changes to the tree.
+    ///
+    /// The snapshot shares the arena chunks with the tree instead of copying the nodes,
+    /// only the generations of the slots are copied. Afterwards, the tree copies a chunk
+    /// the first time it writes to it, as long as a snapshot holds onto it.
+    pub fn snapshot(&mut self) -> Snapshot<T, C, M> {
+        Snapshot {
+            tree: Arc::new(Self {
+                root_id: self.root_id,
+                arena: self.arena.share(),
+                cmp: self.cmp.clone(),
+                compaction_threshold: self.compaction_threshold,
+                max_len: self.max_len,
+            }),
+        }
+    }
+}
+
+/// Trees are equal when they hold equal values in order,
+/// regardless of their shapes and arena layouts.
+impl<T, C, M> PartialEq for ArenaTree<T, C, M>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.size() == other.size() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T, C, M> Eq for ArenaTree<T, C, M> where T: Eq {}
+
+/// Compares the in-order values lexicographically, like `BTreeSet`.
+impl<T, C, M> PartialOrd for ArenaTree<T, C, M>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
 }
 
-#[derive(Debug, Default)]
-pub struct ArenaTree<T> {
-    root_id: usize,
-    arena: Vec<Node<T>>,
+impl<T, C, M> Ord for ArenaTree<T, C, M>
+where
+    T: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+/// Hashes the values in order, consistent with `PartialEq`.
+impl<T, C, M> Hash for ArenaTree<T, C, M>
+where
+    T: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.size());
+        for val in self.iter() {
+            val.hash(state);
+        }
+    }
 }
 
-impl<T> Node<T> {
-    fn new(idx: usize, val: T) -> Self {
+impl<T, M> Node<T, M> {
+    fn new(idx: NodeId, val: T, meta: M) -> Self {
         Self {
             idx,
             val,
-            parent: None,
-            left: None,
-            right: None,
+            meta,
+            parent: Link::NONE,
+            left: Link::NONE,
+            right: Link::NONE,
         }
     }
 
+    fn parent(&self) -> Option<NodeId> {
+        self.parent.get()
+    }
+
+    fn left(&self) -> Option<NodeId> {
+        self.left.get()
+    }
+
+    fn right(&self) -> Option<NodeId> {
+        self.right.get()
+    }
+
+    fn set_parent(&mut self, id: Option<NodeId>) {
+        self.parent = Link::new(id);
+    }
+
+    fn set_left(&mut self, id: Option<NodeId>) {
+        self.left = Link::new(id);
+    }
+
+    fn set_right(&mut self, id: Option<NodeId>) {
+        self.right = Link::new(id);
+    }
+
     pub fn is_root(&self) -> bool {
-        self.parent.is_none()
+        self.parent().is_none()
     }
 
     pub fn is_leaf(&self) -> bool {
-        self.left.is_none() && self.right.is_none()
+        self.left().is_none() && self.right().is_none()
     }
 }
 
@@ -48,440 +344,3473 @@ pub enum Traversal {
     BFS,
 }
 
-impl<T> ArenaTree<T>
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    N,
+    L,
+    R,
+}
+
+impl Traversal {
+    /// What to do at every node, in order, `None` for BFS.
+    fn steps(&self) -> Option<[Step; 3]> {
+        use Step::*;
+        match self {
+            Traversal::NLR => Some([N, L, R]),
+            Traversal::LNR => Some([L, N, R]),
+            Traversal::LRN => Some([L, R, N]),
+            Traversal::NRL => Some([N, R, L]),
+            Traversal::RNL => Some([R, N, L]),
+            Traversal::RLN => Some([R, L, N]),
+            Traversal::BFS => None,
+        }
+    }
+}
+
+/// Unpruned walk, see `TraversalIds::prune`.
+type Everything<T> = fn(&T) -> Ordering;
+
+/// Lazy iterator over the node ids of a subtree in any `Traversal` order,
+/// walking with an explicit stack, or a queue for BFS.
+#[derive(Debug)]
+pub(crate) struct TraversalIds<'a, T, C = Natural, M = (), F = Everything<T>> {
+    tree: &'a ArenaTree<T, C, M>,
+    steps: Option<[Step; 3]>,
+    /// Nodes to walk down into, or whose turn it is to be visited if flagged.
+    stack: Vec<(NodeId, bool)>,
+    queue: VecDeque<NodeId>,
+    /// Where the wanted values are relative to the one given, `Less` if they are
+    /// all smaller, so whole subtrees without any are skipped.
+    prune: F,
+}
+
+impl<'a, T, C, M> TraversalIds<'a, T, C, M> {
+    fn new(tree: &'a ArenaTree<T, C, M>, typ: &Traversal, start: Option<NodeId>) -> Self {
+        Self::pruned(tree, typ, start, |_| Ordering::Equal)
+    }
+}
+
+impl<'a, T, C, M, F> TraversalIds<'a, T, C, M, F>
 where
-    T: Ord + Copy,
+    F: FnMut(&T) -> Ordering,
 {
-    fn node(&mut self, val: T) -> usize {
-        let idx = self.arena.len();
-        self.arena.push(Node::new(idx, val));
-        idx
+    fn pruned(
+        tree: &'a ArenaTree<T, C, M>,
+        typ: &Traversal,
+        start: Option<NodeId>,
+        prune: F,
+    ) -> Self {
+        let steps = typ.steps();
+        let (stack, queue) = match steps {
+            Some(_) => (
+                start.map(|id| (id, false)).into_iter().collect(),
+                VecDeque::new(),
+            ),
+            None => (vec![], start.into_iter().collect()),
+        };
+        Self {
+            tree,
+            steps,
+            stack,
+            queue,
+            prune,
+        }
     }
+}
 
-    pub fn from_vec(v: Vec<T>) -> Self {
-        let mut t = Self {
-            arena: vec![],
-            root_id: 0,
-        };
-        for &val in v.iter() {
-            t.insert(val);
+impl<'a, T, C, M, F> Iterator for TraversalIds<'a, T, C, M, F>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let id = match self.steps {
+                Some(_) => match self.stack.pop()? {
+                    (id, true) => break Some(id),
+                    (id, false) => id,
+                },
+                None => self.queue.pop_front()?,
+            };
+            let node = &self.tree.arena[id];
+            let ord = (self.prune)(&node.val);
+            let left = node.left().filter(|_| ord != Ordering::Greater);
+            let right = node.right().filter(|_| ord != Ordering::Less);
+            let steps = match self.steps {
+                Some(steps) => steps,
+                None => {
+                    self.queue.extend(left);
+                    self.queue.extend(right);
+                    if ord == Ordering::Equal {
+                        break Some(id);
+                    }
+                    continue;
+                }
+            };
+            for step in steps.iter().rev() {
+                match step {
+                    Step::N if ord != Ordering::Equal => {}
+                    Step::N => self.stack.push((id, true)),
+                    Step::L => self.stack.extend(left.map(|id| (id, false))),
+                    Step::R => self.stack.extend(right.map(|id| (id, false))),
+                }
+            }
         }
-        t
     }
+}
 
-    pub fn size(&self) -> usize {
-        self.arena.len()
+/// Lazy iterator over the values in any `Traversal` order, see `traversal_iter`.
+#[derive(Debug)]
+pub struct TraversalIter<'a, T, C = Natural, M = (), F = Everything<T>> {
+    ids: TraversalIds<'a, T, C, M, F>,
+}
+
+impl<'a, T, C, M, F> Iterator for TraversalIter<'a, T, C, M, F>
+where
+    F: FnMut(&T) -> Ordering,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.ids.next()?;
+        Some(&self.ids.tree.arena[id].val)
     }
+}
 
-    pub fn search_parent(&mut self, val: T) -> Option<(usize, bool)> {
-        if self.size() == 0 {
-            None
+/// Step of an Euler tour, entering a node before its subtree or exiting it after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EulerStep {
+    Enter(NodeId),
+    Exit(NodeId),
+}
+
+/// In-order iterator over values, walking parent links from both ends.
+pub struct Iter<'a, T, C = Natural, M = ()> {
+    tree: &'a ArenaTree<T, C, M>,
+    front: Option<NodeId>,
+    back: Option<NodeId>,
+}
+
+impl<'a, T, C, M> Iterator for Iter<'a, T, C, M> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.front?;
+        if self.front == self.back {
+            self.front = None;
+            self.back = None;
         } else {
-            let mut cur = &self.arena[self.root_id];
-            loop {
-                cur = match val.cmp(&cur.val) {
-                    Ordering::Less => match cur.left {
-                        None => break Some((cur.idx, true)),
-                        Some(i) => &self.arena[i],
-                    },
-                    Ordering::Equal => {
-                        break match cur.parent {
-                            None => None,
-                            Some(parent_id) => {
-                                Some((parent_id, self.arena[parent_id].left == Some(cur.idx)))
-                            }
-                        }
-                    }
-                    Ordering::Greater => match cur.right {
-                        None => break Some((cur.idx, false)),
-                        Some(i) => &self.arena[i],
-                    },
-                }
+            self.front = self.tree.successor_id(id);
+        }
+        Some(&self.tree.arena[id].val)
+    }
+}
+
+impl<'a, T, C, M> DoubleEndedIterator for Iter<'a, T, C, M> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let id = self.back?;
+        if self.front == self.back {
+            self.front = None;
+            self.back = None;
+        } else {
+            self.back = self.tree.predecessor_id(id);
+        }
+        Some(&self.tree.arena[id].val)
+    }
+}
+
+impl<'a, T, C, M> IntoIterator for &'a ArenaTree<T, C, M> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the ids of leaf nodes, from left to right.
+pub struct LeafIds<'a, T, C = Natural, M = ()> {
+    tree: &'a ArenaTree<T, C, M>,
+    stack: Vec<NodeId>,
+}
+
+impl<'a, T, C, M> Iterator for LeafIds<'a, T, C, M> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = &self.tree.arena[self.stack.pop()?];
+            if node.is_leaf() {
+                break Some(node.idx);
+            }
+            if let Some(right_id) = node.right() {
+                self.stack.push(right_id);
+            }
+            if let Some(left_id) = node.left() {
+                self.stack.push(left_id);
             }
         }
     }
+}
 
-    pub fn search(&mut self, val: T) -> Option<usize> {
-        match self.search_parent(val) {
-            None => {
-                if !self.arena.is_empty() && self.arena[self.root_id].val == val {
-                    Some(self.root_id)
-                } else {
-                    None
-                }
+/// Iterator over the values of every depth, from the root down, see `by_level`.
+pub struct Levels<'a, T, C = Natural, M = ()> {
+    tree: &'a ArenaTree<T, C, M>,
+    frontier: Vec<NodeId>,
+}
+
+impl<'a, T, C, M> Iterator for Levels<'a, T, C, M> {
+    type Item = Vec<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frontier.is_empty() {
+            return None;
+        }
+        let mut next = Vec::with_capacity(self.frontier.len() * 2);
+        let mut level = Vec::with_capacity(self.frontier.len());
+        for &id in self.frontier.iter() {
+            let node = &self.tree.arena[id];
+            level.push(&node.val);
+            next.extend(node.left());
+            next.extend(node.right());
+        }
+        self.frontier = next;
+        Some(level)
+    }
+}
+
+/// Iterator over the ids of the ancestors of a node, from its parent up to the root.
+pub struct Ancestors<'a, T, C = Natural, M = ()> {
+    tree: &'a ArenaTree<T, C, M>,
+    next: Option<NodeId>,
+}
+
+impl<'a, T, C, M> Iterator for Ancestors<'a, T, C, M> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.tree.arena[id].parent();
+        Some(id)
+    }
+}
+
+/// Iterator over the values of root-to-leaf paths, from the leftmost leaf to the rightmost one.
+pub struct Paths<'a, T, C = Natural, M = ()> {
+    tree: &'a ArenaTree<T, C, M>,
+    /// Nodes left to walk down into, with their depth.
+    stack: Vec<(NodeId, usize)>,
+    path: Vec<&'a T>,
+}
+
+impl<'a, T, C, M> Iterator for Paths<'a, T, C, M> {
+    type Item = Vec<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (id, depth) = self.stack.pop()?;
+            let node = &self.tree.arena[id];
+            self.path.truncate(depth);
+            self.path.push(&node.val);
+            if node.is_leaf() {
+                break Some(self.path.clone());
             }
-            Some((parent_id, dir)) => {
-                let parent = &self.arena[parent_id];
-                if dir {
-                    parent.left
-                } else {
-                    parent.right
-                }
+            self.stack.extend(node.right().map(|id| (id, depth + 1)));
+            self.stack.extend(node.left().map(|id| (id, depth + 1)));
+        }
+    }
+}
+
+impl<T, C> ArenaTree<T, C> {
+    /// Creates an empty tree ordered by `cmp`,
+    /// e.g. a closure `|a: &T, b: &T| -> Ordering`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_comparator_and_meta(cmp)
+    }
+}
+
+impl<T, C, M> ArenaTree<T, C, M> {
+    /// Creates an empty tree ordered by `cmp` whose nodes carry metadata of type `M`,
+    /// starting out as `M::default()` on insert.
+    pub fn with_comparator_and_meta(cmp: C) -> Self {
+        Self {
+            root_id: 0,
+            arena: Slots::default(),
+            cmp,
+            compaction_threshold: None,
+            max_len: None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Removes every node, leaving the comparator and settings. Handles go stale.
+    pub fn clear(&mut self) {
+        self.arena.clear();
+    }
+
+    pub fn meta(&self, id: NodeId) -> Option<&M> {
+        Some(&self.arena.get(id)?.meta)
+    }
+
+    pub fn meta_mut(&mut self, id: NodeId) -> Option<&mut M> {
+        Some(&mut self.arena.get_mut(id)?.meta)
+    }
+
+    /// Replaces the metadata of the node, returning the previous one,
+    /// or `None` if the id does not refer to a node.
+    pub fn set_meta(&mut self, id: NodeId, meta: M) -> Option<M> {
+        Some(std::mem::replace(self.meta_mut(id)?, meta))
+    }
+
+    pub(crate) fn most_left(&self, id: NodeId) -> NodeId {
+        let mut cur = &self.arena[id];
+        loop {
+            cur = match cur.left() {
+                Some(id) => &self.arena[id],
+                None => break cur.idx,
+            };
+        }
+    }
+
+    pub(crate) fn most_right(&self, id: NodeId) -> NodeId {
+        let mut cur = &self.arena[id];
+        loop {
+            cur = match cur.right() {
+                Some(id) => &self.arena[id],
+                None => break cur.idx,
+            };
+        }
+    }
+
+    fn successor_id(&self, id: NodeId) -> Option<NodeId> {
+        if let Some(right_id) = self.arena[id].right() {
+            return Some(self.most_left(right_id));
+        }
+        let mut cur = id;
+        loop {
+            let parent_id = self.arena[cur].parent()?;
+            if self.arena[parent_id].left() == Some(cur) {
+                break Some(parent_id);
             }
+            cur = parent_id;
         }
     }
 
-    pub fn insert(&mut self, val: T) -> usize {
-        match self.search_parent(val) {
-            None => {
-                if !self.arena.is_empty() && self.arena[self.root_id].val == val {
-                    0
-                } else {
-                    self.node(val)
-                }
-            }
-            Some((parent_id, dir)) => {
-                {
-                    let parent = &self.arena[parent_id];
-                    if dir {
-                        if parent.left.is_some() {
-                            return parent.left.unwrap();
-                        }
-                    } else if parent.right.is_some() {
-                        return parent.right.unwrap();
-                    }
-                }
-                let id = self.node(val);
-                {
-                    let node = &mut self.arena[id];
-                    node.parent.replace(parent_id);
-                }
-                {
-                    let parent = &mut self.arena[parent_id];
-                    if dir {
-                        parent.left.replace(id);
-                    } else {
-                        parent.right.replace(id);
-                    }
-                }
-                id
-            }
-        }
+    fn predecessor_id(&self, id: NodeId) -> Option<NodeId> {
+        if let Some(left_id) = self.arena[id].left() {
+            return Some(self.most_right(left_id));
+        }
+        let mut cur = id;
+        loop {
+            let parent_id = self.arena[cur].parent()?;
+            if self.arena[parent_id].right() == Some(cur) {
+                break Some(parent_id);
+            }
+            cur = parent_id;
+        }
+    }
+
+    /// Lazy in-order iterator, also walkable from the maximum via `rev()`.
+    pub fn iter(&self) -> Iter<'_, T, C, M> {
+        if self.arena.is_empty() {
+            return Iter {
+                tree: self,
+                front: None,
+                back: None,
+            };
+        }
+        Iter {
+            tree: self,
+            front: Some(self.most_left(self.root_id)),
+            back: Some(self.most_right(self.root_id)),
+        }
+    }
+
+    pub fn leaf_ids(&self) -> LeafIds<'_, T, C, M> {
+        let mut stack = vec![];
+        if !self.arena.is_empty() {
+            stack.push(self.root_id);
+        }
+        LeafIds { tree: self, stack }
+    }
+
+    pub fn leaves(&self) -> impl Iterator<Item = &T> {
+        self.leaf_ids().map(move |id| &self.arena[id].val)
+    }
+
+    /// Anticlockwise boundary from the root: the left edge down, the leaves
+    /// from left to right, then the right edge back up, each value once.
+    pub fn boundary(&self) -> Vec<&T> {
+        let root = match self.root() {
+            Some(root) => root,
+            None => return vec![],
+        };
+        let node = &self.arena[root];
+        let mut path = vec![&node.val];
+        if node.is_leaf() {
+            return path;
+        }
+        // The edges stop above the leaves, those are visited with the others.
+        let edge = |mut cur: Option<NodeId>, left_first: bool| {
+            let mut ids = vec![];
+            while let Some(id) = cur {
+                let node = &self.arena[id];
+                if node.is_leaf() {
+                    break;
+                }
+                ids.push(id);
+                cur = if left_first {
+                    node.left().or_else(|| node.right())
+                } else {
+                    node.right().or_else(|| node.left())
+                };
+            }
+            ids
+        };
+        let left = edge(node.left(), true);
+        let right = edge(node.right(), false);
+        let ids = left
+            .into_iter()
+            .chain(self.leaf_ids())
+            .chain(right.into_iter().rev());
+        path.extend(ids.map(|id| &self.arena[id].val));
+        path
+    }
+
+    /// Depth-first walk recording when every node is entered and exited,
+    /// left subtree first, so the steps between the entry and exit of a node
+    /// are its subtree.
+    pub fn euler_tour(&self) -> Vec<EulerStep> {
+        let mut tour = Vec::with_capacity(self.size() * 2);
+        let mut stack: Vec<_> = self.root().map(EulerStep::Enter).into_iter().collect();
+        while let Some(step) = stack.pop() {
+            tour.push(step);
+            if let EulerStep::Enter(id) = step {
+                let node = &self.arena[id];
+                stack.push(EulerStep::Exit(id));
+                stack.extend(node.right().map(EulerStep::Enter));
+                stack.extend(node.left().map(EulerStep::Enter));
+            }
+        }
+        tour
+    }
+
+    /// Iterates over the values of every root-to-leaf path.
+    pub fn paths(&self) -> Paths<'_, T, C, M> {
+        Paths {
+            tree: self,
+            stack: self.root().map(|id| (id, 0)).into_iter().collect(),
+            path: vec![],
+        }
+    }
+
+    /// Consumes the tree, moving its values out in order; nothing is cloned.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut ids = Vec::with_capacity(self.size());
+        let mut cur = self.root().map(|id| self.most_left(id));
+        while let Some(id) = cur {
+            ids.push(id);
+            cur = self.successor_id(id);
+        }
+        ids.into_iter()
+            .map(|id| self.arena.remove(id).expect("vacant slot").val)
+            .collect()
+    }
+
+    /// Number of nodes the tree can hold without growing its arena.
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    /// Reserves room for at least `additional` more nodes,
+    /// counting slots freed by deletions as available room.
+    pub fn reserve(&mut self, additional: usize) {
+        self.arena.reserve(additional)
+    }
+
+    /// Releases unused arena memory without moving nodes,
+    /// so only gaps at the end of the arena are reclaimed; see `compact`.
+    pub fn shrink_to_fit(&mut self) {
+        self.arena.shrink_to_fit()
+    }
+
+    /// Heap bytes held by the arena: the allocated slots, their generations and the free list.
+    /// Memory owned by the values themselves is not counted.
+    pub fn memory_usage(&self) -> usize {
+        self.arena.memory_usage()
+    }
+
+    /// Depth of every node, root being 0, in level order.
+    fn depths(&self) -> Vec<usize> {
+        let mut depths = Vec::with_capacity(self.size());
+        let mut frontier: Vec<NodeId> = self.root().into_iter().collect();
+        let mut depth = 0;
+        while !frontier.is_empty() {
+            let mut next = Vec::with_capacity(frontier.len() * 2);
+            for &id in frontier.iter() {
+                depths.push(depth);
+                let node = &self.arena[id];
+                next.extend(node.left());
+                next.extend(node.right());
+            }
+            frontier = next;
+            depth += 1;
+        }
+        depths
+    }
+
+    /// Number of nodes on the longest root-to-leaf path, 0 for an empty tree.
+    pub fn height(&self) -> usize {
+        self.depths().last().map_or(0, |depth| depth + 1)
+    }
+
+    /// Number of nodes at every depth, the root's first.
+    pub fn level_widths(&self) -> Vec<usize> {
+        let mut widths = vec![];
+        for depth in self.depths() {
+            match widths.get_mut(depth) {
+                Some(width) => *width += 1,
+                None => widths.push(1),
+            }
+        }
+        widths
+    }
+
+    /// Largest number of nodes at a single depth, 0 for an empty tree.
+    pub fn width(&self) -> usize {
+        self.level_widths().into_iter().max().unwrap_or(0)
+    }
+
+    /// Height of the subtree of every node under `id`, indexed by slot, 0 elsewhere.
+    /// Computed without recursion, so degenerate trees don't overflow the stack.
+    fn subtree_heights(&self, id: NodeId) -> Vec<usize> {
+        let mut heights = vec![0; self.arena.slots_len()];
+        // Pre-order, so walking it backwards visits children before their parent.
+        let mut ids = vec![];
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            ids.push(id);
+            let node = &self.arena[id];
+            stack.extend(node.left());
+            stack.extend(node.right());
+        }
+        for id in ids.into_iter().rev() {
+            let node = &self.arena[id];
+            let left = node.left().map_or(0, |id| heights[slot(id)]);
+            let right = node.right().map_or(0, |id| heights[slot(id)]);
+            heights[slot(id)] = 1 + left.max(right);
+        }
+        heights
+    }
+
+    /// Height of the left subtree of `id` minus the height of its right subtree.
+    pub fn balance_factor(&self, id: NodeId) -> Option<i64> {
+        let node = self.arena.get(id)?;
+        let heights = self.subtree_heights(id);
+        let height_of = |id: Option<NodeId>| id.map_or(0, |id| heights[slot(id)] as i64);
+        Some(height_of(node.left()) - height_of(node.right()))
+    }
+
+    /// Number of edges on the longest path between two nodes, 0 for an empty tree.
+    pub fn diameter(&self) -> usize {
+        let heights = match self.root() {
+            Some(root) => self.subtree_heights(root),
+            None => return 0,
+        };
+        let height_of = |id: Option<NodeId>| id.map_or(0, |id| heights[slot(id)]);
+        // The longest path through a node goes down to the deepest node on both sides.
+        self.arena
+            .iter()
+            .map(|(_, node)| height_of(node.left()) + height_of(node.right()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Whether the subtrees of every node differ in height by at most one.
+    /// A tree failing this may be worth a `rebalance`.
+    pub fn is_balanced(&self) -> bool {
+        let heights = match self.root() {
+            Some(root) => self.subtree_heights(root),
+            None => return true,
+        };
+        let height_of = |id: Option<NodeId>| id.map_or(0, |id| heights[slot(id)]);
+        self.arena
+            .iter()
+            .all(|(_, node)| height_of(node.left()).abs_diff(height_of(node.right())) <= 1)
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        let depths = self.depths();
+        ArenaStats {
+            live: self.size(),
+            tombstones: self.arena.slots_len() - self.size(),
+            capacity: self.capacity(),
+            height: depths.last().map_or(0, |depth| depth + 1),
+            average_depth: if depths.is_empty() {
+                0.0
+            } else {
+                depths.iter().sum::<usize>() as f64 / depths.len() as f64
+            },
+        }
+    }
+
+    /// Removes the gaps left by deletions, rewriting node ids and shrinking the arena.
+    /// Returns the new id of each node indexed by the old one (`None` for gaps),
+    /// so that ids held by callers can be fixed up.
+    pub fn compact(&mut self) -> Vec<Option<NodeId>> {
+        let remap = self.arena.compact();
+        let f = |id: NodeId| remap[slot(id)].expect("link into vacant slot");
+        for node in self.arena.iter_mut() {
+            node.idx = f(node.idx);
+            node.set_parent(node.parent().map(f));
+            node.set_left(node.left().map(f));
+            node.set_right(node.right().map(f));
+        }
+        if !self.arena.is_empty() {
+            self.root_id = f(self.root_id);
+        }
+        remap
+    }
+
+    /// Compacts the arena after a deletion whenever vacant slots
+    /// make up more than `ratio` of it; `None`, the default, turns this off.
+    /// Compaction rewrites node ids, so with a threshold set, ids may change on any deletion;
+    /// hold `Handle`s to notice.
+    pub fn set_compaction_threshold(&mut self, ratio: Option<f64>) {
+        if let Some(ratio) = ratio {
+            assert!((0.0..=1.0).contains(&ratio), "ratio out of [0, 1]");
+        }
+        self.compaction_threshold = ratio;
+    }
+
+    pub fn compaction_threshold(&self) -> Option<f64> {
+        self.compaction_threshold
+    }
+
+    /// Most values the tree may hold, see `with_max_len`.
+    pub fn max_len(&self) -> Option<usize> {
+        self.max_len
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.max_len.is_some_and(|max_len| self.size() >= max_len)
+    }
+
+    pub(crate) fn maybe_compact(&mut self) {
+        if let Some(ratio) = self.compaction_threshold {
+            let slots = self.arena.slots_len();
+            let vacant = slots - self.arena.len();
+            if vacant as f64 > ratio * slots as f64 {
+                self.compact();
+            }
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
+
+    pub fn root(&self) -> Option<NodeId> {
+        if self.arena.is_empty() {
+            None
+        } else {
+            Some(self.root_id)
+        }
+    }
+
+    /// Handle of the node currently at `id`.
+    pub fn handle(&self, id: NodeId) -> Option<Handle> {
+        Some(Handle {
+            id,
+            generation: self.arena.generation(id)?,
+        })
+    }
+
+    /// Id of the handle's node, or `None` if the node is gone.
+    pub fn resolve(&self, handle: Handle) -> Option<NodeId> {
+        if self.arena.generation(handle.id)? == handle.generation {
+            Some(handle.id)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_by_handle(&self, handle: Handle) -> Option<&T> {
+        self.get(self.resolve(handle)?)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        Some(&self.arena.get(id)?.val)
+    }
+
+    /// Like `get`, skipping the check that `id` refers to a node.
+    ///
+    /// # Safety
+    ///
+    /// `id` must refer to a node of this tree, e.g. an id returned by `insert`
+    /// whose node wasn't deleted since, nor moved by `compact`.
+    #[cfg(feature = "unsafe-fast")]
+    #[allow(unsafe_code)]
+    pub unsafe fn get_unchecked(&self, id: NodeId) -> &T {
+        // SAFETY: the caller promises the slot of `id` is occupied.
+        unsafe { &self.arena.get_unchecked(id).val }
+    }
+
+    /// Like `meta`, skipping the check that `id` refers to a node.
+    ///
+    /// # Safety
+    ///
+    /// `id` must refer to a node of this tree, as for `get_unchecked`.
+    #[cfg(feature = "unsafe-fast")]
+    #[allow(unsafe_code)]
+    pub unsafe fn meta_unchecked(&self, id: NodeId) -> &M {
+        // SAFETY: the caller promises the slot of `id` is occupied.
+        unsafe { &self.arena.get_unchecked(id).meta }
+    }
+
+    /// Callers must not change the ordering of the value.
+    pub(crate) fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        Some(&mut self.arena.get_mut(id)?.val)
+    }
+
+    /// Searches with `f` telling how the wanted value compares to the given one.
+    pub(crate) fn search_by<F>(&self, mut f: F) -> Option<NodeId>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        if self.arena.is_empty() {
+            return None;
+        }
+        let mut cur = &self.arena[self.root_id];
+        loop {
+            let next = match f(&cur.val) {
+                Ordering::Less => cur.left(),
+                Ordering::Equal => break Some(cur.idx),
+                Ordering::Greater => cur.right(),
+            };
+            cur = &self.arena[next?];
+        }
+    }
+
+    /// Whether both trees have the same shape with equal values at each position.
+    pub fn structurally_eq<C2, M2>(&self, other: &ArenaTree<T, C2, M2>) -> bool
+    where
+        T: PartialEq,
+    {
+        if self.size() != other.size() {
+            return false;
+        }
+        if self.arena.is_empty() {
+            return true;
+        }
+        let mut stack = vec![(self.root_id, other.root_id)];
+        while let Some((a, b)) = stack.pop() {
+            let (a, b) = (&self.arena[a], &other.arena[b]);
+            if a.val != b.val {
+                return false;
+            }
+            for &(x, y) in [(a.left(), b.left()), (a.right(), b.right())].iter() {
+                match (x, y) {
+                    (None, None) => {}
+                    (Some(x), Some(y)) => stack.push((x, y)),
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    pub fn parent_of(&self, id: NodeId) -> Option<NodeId> {
+        self.arena.get(id)?.parent()
+    }
+
+    /// Ids of the ancestors of `id`, from its parent up to the root.
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_, T, C, M> {
+        Ancestors {
+            tree: self,
+            next: self.parent_of(id),
+        }
+    }
+
+    pub fn left_of(&self, id: NodeId) -> Option<NodeId> {
+        self.arena.get(id)?.left()
+    }
+
+    pub fn right_of(&self, id: NodeId) -> Option<NodeId> {
+        self.arena.get(id)?.right()
+    }
+
+    /// Returns the other child of this node's parent.
+    pub fn sibling_of(&self, id: NodeId) -> Option<NodeId> {
+        let parent = &self.arena[self.parent_of(id)?];
+        if parent.left() == Some(id) {
+            parent.right()
+        } else {
+            parent.left()
+        }
+    }
+
+    /// Points the link of `parent` that leads to `old` at `new` instead,
+    /// `None` meaning the root.
+    fn replace_child(&mut self, parent: Option<NodeId>, old: NodeId, new: NodeId) {
+        match parent {
+            None => self.root_id = new,
+            Some(parent_id) => {
+                let parent = &mut self.arena[parent_id];
+                if parent.left() == Some(old) {
+                    parent.set_left(Some(new));
+                } else {
+                    parent.set_right(Some(new));
+                }
+            }
+        }
+    }
+
+    /// Links `child` as the left or right child of `parent`.
+    pub(crate) fn link(&mut self, parent: NodeId, is_left: bool, child: Option<NodeId>) {
+        if is_left {
+            self.arena[parent].set_left(child);
+        } else {
+            self.arena[parent].set_right(child);
+        }
+        if let Some(child) = child {
+            self.arena[child].set_parent(Some(parent));
+        }
+    }
+
+    pub(crate) fn set_root(&mut self, id: NodeId) {
+        self.arena[id].set_parent(None);
+        self.root_id = id;
+    }
+
+    /// Unlinks the children of the subtree root `id`, returning them as detached subtrees.
+    pub(crate) fn detach_children(&mut self, id: NodeId) -> (Option<NodeId>, Option<NodeId>) {
+        let node = &mut self.arena[id];
+        let (left, right) = (node.left(), node.right());
+        node.set_left(None);
+        node.set_right(None);
+        for child in left.iter().chain(right.iter()) {
+            self.arena[*child].set_parent(None);
+        }
+        (left, right)
+    }
+
+    /// Removes the nodes of the detached subtree of `id`, returning their values in order.
+    pub(crate) fn take_subtree(&mut self, id: NodeId) -> Vec<T> {
+        self.subtree_ids(id)
+            .into_iter()
+            .map(|id| self.arena.remove(id).expect("linked node").val)
+            .collect()
+    }
+
+    /// Moves every node of `other` into this arena, keeping their links and metadata,
+    /// and returns the root of the moved subtree, detached from this tree.
+    pub(crate) fn adopt<C2>(&mut self, mut other: ArenaTree<T, C2, M>) -> Option<NodeId> {
+        let root = other.root()?;
+        let mut moved = None;
+        let mut stack = vec![(root, None)];
+        while let Some((id, pos)) = stack.pop() {
+            let node = other.arena.remove(id).expect("linked node");
+            let (left, right) = (node.left(), node.right());
+            let idx = self.arena.next_id();
+            let new_id = self.arena.push(Node::new(idx, node.val, node.meta));
+            match pos {
+                None => moved = Some(new_id),
+                Some((parent, is_left)) => self.link(parent, is_left, Some(new_id)),
+            }
+            stack.extend(left.map(|id| (id, Some((new_id, true)))));
+            stack.extend(right.map(|id| (id, Some((new_id, false)))));
+        }
+        moved
+    }
+
+    /// Lifts the right child of `id` in its place, returning the new subtree root.
+    /// Only links change, so node ids keep pointing at the same values.
+    pub(crate) fn rotate_left(&mut self, id: NodeId) -> NodeId {
+        let pivot = self.arena[id].right().expect("no right child to rotate");
+        let inner = self.arena[pivot].left();
+        let parent = self.arena[id].parent();
+        self.arena[id].set_right(inner);
+        if let Some(inner) = inner {
+            self.arena[inner].set_parent(Some(id));
+        }
+        self.arena[pivot].set_left(Some(id));
+        self.arena[id].set_parent(Some(pivot));
+        self.arena[pivot].set_parent(parent);
+        self.replace_child(parent, id, pivot);
+        pivot
+    }
+
+    /// Lifts the left child of `id` in its place, returning the new subtree root.
+    pub(crate) fn rotate_right(&mut self, id: NodeId) -> NodeId {
+        let pivot = self.arena[id].left().expect("no left child to rotate");
+        let inner = self.arena[pivot].right();
+        let parent = self.arena[id].parent();
+        self.arena[id].set_left(inner);
+        if let Some(inner) = inner {
+            self.arena[inner].set_parent(Some(id));
+        }
+        self.arena[pivot].set_right(Some(id));
+        self.arena[id].set_parent(Some(pivot));
+        self.arena[pivot].set_parent(parent);
+        self.replace_child(parent, id, pivot);
+        pivot
+    }
+
+    /// Rebalances the tree to the minimal height with the Day–Stout–Warren algorithm:
+    /// rotates it into a right-leaning vine, then folds the vine back into a complete tree.
+    /// Runs in O(n) time and O(1) extra space; only links change, so node ids stay valid.
+    pub fn rebalance(&mut self) {
+        let mut cur = self.root();
+        let mut size = 0;
+        while let Some(id) = cur {
+            if self.arena[id].left().is_some() {
+                cur = Some(self.rotate_right(id));
+            } else {
+                size += 1;
+                cur = self.arena[id].right();
+            }
+        }
+        let complete = (size + 1usize).next_power_of_two();
+        let complete = if complete == size + 1 {
+            complete
+        } else {
+            complete / 2
+        };
+        self.compress(size + 1 - complete);
+        let mut spine = complete - 1;
+        while spine > 1 {
+            spine /= 2;
+            self.compress(spine);
+        }
+    }
+
+    /// Rotates left at every other node of the right spine, `count` times.
+    fn compress(&mut self, count: usize) {
+        let mut cur = self.root();
+        for _ in 0..count {
+            let top = self.rotate_left(cur.expect("spine too short"));
+            cur = self.arena[top].right();
+        }
+    }
+
+    /// Relinks the subtree at `id` into a perfectly balanced shape, reusing its nodes in place.
+    /// Returns the new subtree root.
+    pub(crate) fn rebuild_subtree(&mut self, id: NodeId) -> NodeId {
+        let parent = self.arena[id].parent();
+        let ids = self.subtree_ids(id);
+        let root = self.link_balanced(&ids, parent).expect("empty subtree");
+        self.replace_child(parent, id, root);
+        root
+    }
+
+    /// Ids of the subtree of `id`, in order.
+    pub(crate) fn subtree_ids(&self, id: NodeId) -> Vec<NodeId> {
+        let mut ids = vec![];
+        let mut stack = vec![];
+        let mut cur = Some(id);
+        loop {
+            while let Some(id) = cur {
+                stack.push(id);
+                cur = self.arena[id].left();
+            }
+            match stack.pop() {
+                Some(id) => {
+                    ids.push(id);
+                    cur = self.arena[id].right();
+                }
+                None => break,
+            }
+        }
+        ids
+    }
+
+    /// Links the nodes, given in order, as a balanced subtree below `parent`.
+    fn link_balanced(&mut self, ids: &[NodeId], parent: Option<NodeId>) -> Option<NodeId> {
+        if ids.is_empty() {
+            return None;
+        }
+        let mid = ids.len() / 2;
+        let id = ids[mid];
+        let left = self.link_balanced(&ids[..mid], Some(id));
+        let right = self.link_balanced(&ids[mid + 1..], Some(id));
+        let node = &mut self.arena[id];
+        node.set_parent(parent);
+        node.set_left(left);
+        node.set_right(right);
+        Some(id)
+    }
+}
+
+impl<T> ArenaTree<T>
+where
+    T: Ord,
+{
+    /// Creates an empty tree with room for `capacity` nodes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            arena: Slots::with_capacity(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Creates an empty tree holding at most `max_len` values, with room for all of them,
+    /// so it never reallocates.
+    ///
+    /// `try_insert`, `try_get_or_insert_with` and `insert_sorted_batch` return
+    /// `InsertError::Full` rather than growing past the limit. `insert`, `insert_handle`
+    /// and `get_or_insert_with` stay infallible for trees without a limit,
+    /// and panic when a new value doesn't fit.
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self {
+            max_len: Some(max_len),
+            ..Self::with_capacity(max_len)
+        }
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::with_capacity(v.len());
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+
+    /// Builds a balanced tree in O(n) from values in increasing order,
+    /// where `from_vec` would degenerate into a list in O(n²).
+    /// Of equal values only the first is kept; panics if the values decrease.
+    pub fn from_sorted_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self::from_sorted_iter_with(Natural, iter)
+    }
+
+    pub fn from_sorted_slice(vals: &[T]) -> Self
+    where
+        T: Clone,
+    {
+        Self::from_sorted_iter(vals.iter().cloned())
+    }
+}
+
+impl<T> ArenaTree<T, Reversed>
+where
+    T: Ord,
+{
+    /// Creates an empty tree keeping the greatest value leftmost,
+    /// so in-order traversals yield values in descending order.
+    pub fn descending() -> Self {
+        Self::with_comparator(Reversed(Natural))
+    }
+}
+
+impl<T, K, F> ArenaTree<T, KeyFn<F>>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    /// Creates an empty tree ordered by the key `f` projects out of each value,
+    /// so `T` itself does not need to be `Ord`.
+    pub fn with_key_fn(f: F) -> Self {
+        Self::with_comparator(KeyFn(f))
+    }
+}
+
+impl<T, K, F, M> ArenaTree<T, KeyFn<F>, M>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+    M: Default,
+{
+    pub fn search_key(&self, key: &K) -> Option<NodeId> {
+        let f = &self.cmp.0;
+        self.search_by(|v| key.cmp(&f(v)))
+    }
+
+    pub fn get_by_key(&self, key: &K) -> Option<&T> {
+        self.get(self.search_key(key)?)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.search_key(key).is_some()
+    }
+
+    pub fn delete_key(&mut self, key: &K) -> bool {
+        self.take_key(key).is_some()
+    }
+
+    pub fn take_key(&mut self, key: &K) -> Option<T> {
+        let id = self.search_key(key)?;
+        Some(self.remove_id(id))
+    }
+}
+
+impl<T, C, M> ArenaTree<T, C, M>
+where
+    C: Comparator<T>,
+    M: Default,
+{
+    /// Builds a balanced tree ordered by `cmp` out of values given in its order,
+    /// see `from_sorted_iter`.
+    pub(crate) fn from_sorted_iter_with<I>(cmp: C, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let iter = iter.into_iter();
+        let mut t = Self::with_comparator_and_meta(cmp);
+        t.reserve(iter.size_hint().0);
+        let mut ids = Vec::with_capacity(iter.size_hint().0);
+        for val in iter {
+            if let Some(&last) = ids.last() {
+                match t.cmp.compare(&t.arena[last].val, &val) {
+                    Ordering::Less => {}
+                    Ordering::Equal => continue,
+                    Ordering::Greater => panic!("values out of order"),
+                }
+            }
+            ids.push(t.node(val));
+        }
+        if let Some(root) = t.link_balanced(&ids, None) {
+            t.root_id = root;
+        }
+        t
+    }
+
+    /// Pushes a detached node holding `val`, regardless of `max_len`,
+    /// which the public inserts check beforehand.
+    pub(crate) fn node(&mut self, val: T) -> NodeId {
+        let idx = self.arena.next_id();
+        self.arena.push(Node::new(idx, val, M::default()))
+    }
+
+    pub fn search_parent<Q>(&self, val: &Q) -> Option<(NodeId, bool)>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        if self.size() == 0 {
+            None
+        } else {
+            let mut cur = &self.arena[self.root_id];
+            loop {
+                cur = match self.cmp.compare(val, cur.val.borrow()) {
+                    Ordering::Less => match cur.left() {
+                        None => break Some((cur.idx, true)),
+                        Some(i) => &self.arena[i],
+                    },
+                    Ordering::Equal => {
+                        break cur.parent().map(|parent_id| {
+                            (parent_id, self.arena[parent_id].left() == Some(cur.idx))
+                        })
+                    }
+                    Ordering::Greater => match cur.right() {
+                        None => break Some((cur.idx, false)),
+                        Some(i) => &self.arena[i],
+                    },
+                }
+            }
+        }
+    }
+
+    fn is_root_val<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.cmp.compare(val, self.arena[self.root_id].val.borrow()) == Ordering::Equal
+    }
+
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.search_parent(val) {
+            None => {
+                if !self.arena.is_empty() && self.is_root_val(val) {
+                    Some(self.root_id)
+                } else {
+                    None
+                }
+            }
+            Some((parent_id, dir)) => {
+                let parent = &self.arena[parent_id];
+                if dir {
+                    parent.left()
+                } else {
+                    parent.right()
+                }
+            }
+        }
+    }
+
+    /// Finds the node equal to `val`, or the position a new node would be linked at.
+    pub(crate) fn locate<Q>(&self, val: &Q) -> Result<NodeId, Option<(NodeId, bool)>>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.search_parent(val) {
+            None => {
+                if !self.arena.is_empty() && self.is_root_val(val) {
+                    Ok(self.root_id)
+                } else {
+                    Err(None)
+                }
+            }
+            Some((parent_id, dir)) => {
+                let parent = &self.arena[parent_id];
+                match if dir { parent.left() } else { parent.right() } {
+                    Some(id) => Ok(id),
+                    None => Err(Some((parent_id, dir))),
+                }
+            }
+        }
+    }
+
+    /// Inserts values given in increasing order, skipping those already present.
+    ///
+    /// A batch large relative to the tree is merged with the nodes in order
+    /// and the whole tree relinked balanced, in O(n + m); a small one is inserted
+    /// value by value. Node ids stay valid either way.
+    /// Fails if a tree made by `with_max_len` can't hold the new values,
+    /// and panics if the values decrease, leaving the tree untouched either way.
+    pub fn insert_sorted_batch(&mut self, sorted: &[T]) -> Result<(), InsertError>
+    where
+        T: Clone,
+    {
+        let increasing = sorted
+            .windows(2)
+            .all(|pair| self.cmp.compare(&pair[0], &pair[1]) != Ordering::Greater);
+        assert!(increasing, "values out of order");
+        if let Some(max_len) = self.max_len {
+            let added = sorted
+                .iter()
+                .enumerate()
+                .filter(|(i, val)| {
+                    let dup = *i > 0 && self.cmp.compare(&sorted[i - 1], val) == Ordering::Equal;
+                    !dup && !self.contains(*val)
+                })
+                .count();
+            if self.size() + added > max_len {
+                return Err(InsertError::Full);
+            }
+        }
+        let size = self.size();
+        let log_size = (usize::BITS - size.leading_zeros()) as usize;
+        if sorted.len() * log_size < size {
+            for val in sorted.iter() {
+                self.insert(val.clone());
+            }
+            return Ok(());
+        }
+        let old = self
+            .root()
+            .map_or_else(Vec::new, |root| self.subtree_ids(root));
+        let mut ids = Vec::with_capacity(old.len() + sorted.len());
+        let mut old = old.into_iter().peekable();
+        for (i, val) in sorted.iter().enumerate() {
+            if i > 0 && self.cmp.compare(&sorted[i - 1], val) == Ordering::Equal {
+                continue;
+            }
+            while let Some(&id) = old.peek() {
+                match self.cmp.compare(&self.arena[id].val, val) {
+                    Ordering::Less => ids.push(id),
+                    Ordering::Equal | Ordering::Greater => break,
+                }
+                old.next();
+            }
+            let present = old
+                .peek()
+                .is_some_and(|id| self.cmp.compare(&self.arena[*id].val, val) == Ordering::Equal);
+            if !present {
+                ids.push(self.node(val.clone()));
+            }
+        }
+        ids.extend(old);
+        if let Some(root) = self.link_balanced(&ids, None) {
+            self.root_id = root;
+        }
+        Ok(())
+    }
+
+    /// Links a new node at `pos`, the root if `None`, without checking the ordering.
+    pub(crate) fn attach(&mut self, pos: Option<(NodeId, bool)>, val: T) -> NodeId {
+        let id = self.node(val);
+        match pos {
+            None => self.root_id = id,
+            Some((parent_id, dir)) => {
+                {
+                    let node = &mut self.arena[id];
+                    node.set_parent(Some(parent_id));
+                }
+                {
+                    let parent = &mut self.arena[parent_id];
+                    if dir {
+                        parent.set_left(Some(id));
+                    } else {
+                        parent.set_right(Some(id));
+                    }
+                }
+            }
+        }
+        self.debug_check_links(Some(id));
+        id
+    }
+
+    /// Inserts `val`, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    /// Panics if the tree is full, see `try_insert`.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        match self.locate(&val) {
+            Ok(id) => id,
+            Err(_) if self.is_full() => panic!("tree is full"),
+            Err(pos) => self.attach(pos, val),
+        }
+    }
+
+    /// Inserts `val`, returning the id of the new node,
+    /// or an error naming the existing node if an equal value is already present,
+    /// or if the tree is full.
+    pub fn try_insert(&mut self, val: T) -> Result<NodeId, InsertError> {
+        match self.locate(&val) {
+            Ok(id) => Err(InsertError::AlreadyPresent(id)),
+            Err(_) if self.is_full() => Err(InsertError::Full),
+            Err(pos) => Ok(self.attach(pos, val)),
+        }
+    }
+
+    /// Inserts like `insert`, returning a handle that detects staleness.
+    pub fn insert_handle(&mut self, val: T) -> Handle {
+        let id = self.insert(val);
+        self.handle(id).unwrap()
+    }
+
+    /// Looks up by a borrowed form and only builds the owned value with `f` if absent,
+    /// e.g. interning `&str` into a tree of `Box<str>` without a throwaway allocation.
+    /// Panics if the value is absent and the tree is full, see `try_get_or_insert_with`.
+    pub fn get_or_insert_with<Q, F>(&mut self, val: &Q, f: F) -> NodeId
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        F: FnOnce(&Q) -> T,
+    {
+        match self.try_get_or_insert_with(val, f) {
+            Ok(id) => id,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Like `get_or_insert_with`, failing with `InsertError::Full`
+    /// rather than panicking if the value is absent and the tree is full.
+    pub fn try_get_or_insert_with<Q, F>(&mut self, val: &Q, f: F) -> Result<NodeId, InsertError>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        F: FnOnce(&Q) -> T,
+    {
+        match self.locate(val) {
+            Ok(id) => Ok(id),
+            Err(_) if self.is_full() => Err(InsertError::Full),
+            Err(pos) => Ok(self.attach(pos, f(val))),
+        }
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.search_by(|v| self.cmp.compare(val, v.borrow()))
+            .is_some()
+    }
+
+    /// Number of edges on the path between the nodes of `a` and `b`,
+    /// `None` unless both are present.
+    pub fn distance<Q>(&self, a: &Q, b: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        // Both paths go down together to the lowest common ancestor.
+        let mut cur = self.root()?;
+        loop {
+            let node = &self.arena[cur];
+            let val = node.val.borrow();
+            cur = match (self.cmp.compare(a, val), self.cmp.compare(b, val)) {
+                (Ordering::Less, Ordering::Less) => node.left()?,
+                (Ordering::Greater, Ordering::Greater) => node.right()?,
+                _ => break,
+            };
+        }
+        Some(self.depth_below(cur, a)? + self.depth_below(cur, b)?)
+    }
+
+    /// Number of edges from `id` down to the node of `val`.
+    fn depth_below<Q>(&self, mut id: NodeId, val: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut depth = 0;
+        loop {
+            let node = &self.arena[id];
+            id = match self.cmp.compare(val, node.val.borrow()) {
+                Ordering::Less => node.left()?,
+                Ordering::Equal => return Some(depth),
+                Ordering::Greater => node.right()?,
+            };
+            depth += 1;
+        }
+    }
+
+    /// Values within `range` in the given order, skipping the subtrees outside of it.
+    pub fn traversal_within<'a, Q, R>(
+        &'a self,
+        typ: &Traversal,
+        range: R,
+    ) -> TraversalIter<'a, T, C, M, impl FnMut(&T) -> Ordering + 'a>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized + 'a,
+        R: RangeBounds<Q> + 'a,
+    {
+        self.traversal_by(typ, move |val| {
+            let val = val.borrow();
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => self.cmp.compare(val, start) != Ordering::Less,
+                Bound::Excluded(start) => self.cmp.compare(val, start) == Ordering::Greater,
+                Bound::Unbounded => true,
+            };
+            let before_end = match range.end_bound() {
+                Bound::Included(end) => self.cmp.compare(val, end) != Ordering::Greater,
+                Bound::Excluded(end) => self.cmp.compare(val, end) == Ordering::Less,
+                Bound::Unbounded => true,
+            };
+            match (after_start, before_end) {
+                (false, _) => Ordering::Greater,
+                (_, false) => Ordering::Less,
+                _ => Ordering::Equal,
+            }
+        })
+    }
+
+    /// Lazy in-order iterator over the values within `range`.
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, T, C, M>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let front = self.bound_id(
+            |val| match range.start_bound() {
+                Bound::Included(start) => self.cmp.compare(val.borrow(), start) != Ordering::Less,
+                Bound::Excluded(start) => {
+                    self.cmp.compare(val.borrow(), start) == Ordering::Greater
+                }
+                Bound::Unbounded => true,
+            },
+            true,
+        );
+        let back = self.bound_id(
+            |val| match range.end_bound() {
+                Bound::Included(end) => self.cmp.compare(val.borrow(), end) != Ordering::Greater,
+                Bound::Excluded(end) => self.cmp.compare(val.borrow(), end) == Ordering::Less,
+                Bound::Unbounded => true,
+            },
+            false,
+        );
+        match (front, back) {
+            (Some(front_id), Some(back_id))
+                if Comparator::<T>::compare(
+                    &self.cmp,
+                    &self.arena[front_id].val,
+                    &self.arena[back_id].val,
+                ) != Ordering::Greater =>
+            {
+                Iter {
+                    tree: self,
+                    front,
+                    back,
+                }
+            }
+            _ => Iter {
+                tree: self,
+                front: None,
+                back: None,
+            },
+        }
+    }
+
+    /// If `lower`, the smallest node `inside` holds for, `inside` holding from some value on;
+    /// otherwise the largest one, `inside` holding up to some value.
+    fn bound_id<F>(&self, inside: F, lower: bool) -> Option<NodeId>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut found = None;
+        let mut cur = self.root();
+        while let Some(id) = cur {
+            let node = &self.arena[id];
+            cur = if inside(&node.val) {
+                found = Some(id);
+                if lower {
+                    node.left()
+                } else {
+                    node.right()
+                }
+            } else if lower {
+                node.right()
+            } else {
+                node.left()
+            };
+        }
+        found
+    }
+
+    /// delete leaves a gap in arena, which is reused by the next insert.
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Deletes like `delete`, returning the removed value, which may carry more
+    /// than the part `val` was compared with.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.search(val)?;
+        Some(self.remove_id(id))
+    }
+
+    /// Removes the node at `id` without searching for its value, returning the value,
+    /// or `None` if the slot is vacant.
+    /// A reused slot holds another node by then, see `delete_by_handle` to notice.
+    pub fn delete_by_id(&mut self, id: NodeId) -> Option<T> {
+        self.arena.get(id)?;
+        Some(self.remove_id(id))
+    }
+
+    /// Removes the node of `handle`, returning its value,
+    /// or `None` if the node is already gone, even if its slot was since reused.
+    pub fn delete_by_handle(&mut self, handle: Handle) -> Option<T> {
+        let id = self.resolve(handle)?;
+        Some(self.remove_id(id))
+    }
+
+    /// Unlinks the node and takes its value out of the arena.
+    pub(crate) fn remove_id(&mut self, id: NodeId) -> T {
+        let (val, _) = self.unlink(id);
+        self.maybe_compact();
+        val
+    }
+
+    /// Unlinks the node and takes its value out of the arena, also returning
+    /// the lowest node whose subtree changed shape, where rebalancing starts.
+    pub(crate) fn unlink(&mut self, id: NodeId) -> (T, Option<NodeId>) {
+        let node = self.arena.remove(id).expect("vacant slot");
+        let (parent_id, left_id, right_id) = (node.parent(), node.left(), node.right());
+        let val = node.val;
+        macro_rules! update_parent {
+            ($parent_id: expr, $id: expr, $original_id: expr) => {
+                match ($parent_id, $id) {
+                    (None, None) => self.arena.clear(),
+                    (None, Some(id)) => {
+                        self.root_id = id;
+                    }
+                    (Some(parent_id), val) => {
+                        let parent = &mut self.arena[parent_id];
+                        if parent.left() == Some($original_id) {
+                            parent.set_left(val);
+                        } else {
+                            parent.set_right(val);
+                        }
+                    }
+                }
+            };
+        }
+        let mut start = parent_id;
+        match (left_id, right_id) {
+            (None, None) => update_parent!(parent_id, None, id),
+            (Some(left_id), Some(right_id)) => {
+                let candidate_id = self.most_left(right_id);
+                update_parent!(parent_id, Some(candidate_id), id);
+                let (candidate_parent_id, candidate_right) = {
+                    let candidate = &mut self.arena[candidate_id];
+                    let candidate_right = candidate.right();
+                    let candidate_parent_id = if right_id == candidate_id {
+                        Some(candidate_id)
+                    } else {
+                        candidate.parent()
+                    };
+
+                    candidate.set_right(Some(right_id));
+                    candidate.set_left(Some(left_id));
+                    candidate.set_parent(parent_id);
+
+                    (candidate_parent_id, candidate_right)
+                };
+                update_parent!(candidate_parent_id, candidate_right, candidate_id);
+                self.arena[left_id].set_parent(Some(candidate_id));
+                if right_id != candidate_id {
+                    self.arena[right_id].set_parent(Some(candidate_id));
+                }
+                if let Some(candidate_right) = candidate_right {
+                    self.arena[candidate_right].set_parent(candidate_parent_id);
+                }
+                start = candidate_parent_id;
+            }
+            (Some(left_id), None) => {
+                update_parent!(parent_id, Some(left_id), id);
+                self.arena[left_id].set_parent(parent_id);
+            }
+            (None, Some(right_id)) => {
+                update_parent!(parent_id, Some(right_id), id);
+                self.arena[right_id].set_parent(parent_id);
+            }
+        }
+        self.debug_check_links(parent_id.or_else(|| self.root()));
+        self.debug_check_links(start);
+        (val, start)
+    }
+
+    /// Checks the links between `id`, its parent and its children agree
+    /// in debug builds, or with the `checked` feature, so corruption is caught
+    /// where it happens.
+    fn debug_check_links(&self, id: Option<NodeId>) {
+        let id = match id {
+            Some(id) if CHECKED => id,
+            _ => return,
+        };
+        let node = &self.arena[id];
+        match node.parent() {
+            Some(parent_id) => {
+                let parent = &self.arena[parent_id];
+                assert!(
+                    parent.left() == Some(id) || parent.right() == Some(id),
+                    "node #{} isn't a child of its parent #{}",
+                    id,
+                    parent_id
+                );
+            }
+            None => assert_eq!(self.root_id, id, "node #{} has no parent", id),
+        }
+        for child in node.left().iter().chain(node.right().iter()) {
+            assert_eq!(
+                self.arena[*child].parent(),
+                Some(id),
+                "child #{} doesn't link back to #{}",
+                child,
+                id
+            );
+        }
+    }
+
+    pub fn traversal(&self, typ: &Traversal) -> Vec<&T> {
+        self.traversal_map(typ, |x| x)
+    }
+
+    pub fn traversal_map<'a, U, F>(&'a self, typ: &Traversal, mut f: F) -> Vec<U>
+    where
+        F: FnMut(&'a T) -> U,
+    {
+        let mut path = Vec::with_capacity(self.size());
+        let _: ControlFlow<()> = self.visit(typ, |val| {
+            path.push(f(val));
+            ControlFlow::Continue(())
+        });
+        path
+    }
+
+    /// Calls `f` on every node in the given order with its position in that order,
+    /// its id and its value.
+    pub fn for_each_with<F>(&self, typ: &Traversal, mut f: F)
+    where
+        F: FnMut(usize, NodeId, &T),
+    {
+        let ids = TraversalIds::new(self, typ, self.root());
+        for (idx, id) in ids.enumerate() {
+            f(idx, id, &self.arena[id].val);
+        }
+    }
+
+    /// Lazy iterator over the values in the given order, for pipelines that stop early
+    /// or fold as they go rather than collecting the whole `traversal` first.
+    pub fn traversal_iter(&self, typ: &Traversal) -> TraversalIter<'_, T, C, M> {
+        TraversalIter {
+            ids: TraversalIds::new(self, typ, self.root()),
+        }
+    }
+
+    /// Folds the values in the given order into an accumulator, without collecting them.
+    pub fn fold<B, F>(&self, typ: &Traversal, init: B, f: F) -> B
+    where
+        F: FnMut(B, &T) -> B,
+    {
+        self.traversal_iter(typ).fold(init, f)
+    }
+
+    /// Whether some value matches `pred`, walking in order until one does.
+    pub fn any<F>(&self, pred: F) -> bool
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.find(pred).is_some()
+    }
+
+    /// Whether every value matches `pred`, walking in order until one doesn't.
+    pub fn all<F>(&self, mut pred: F) -> bool
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.find(|val| !pred(val)).is_none()
+    }
+
+    /// Smallest value matching `pred`, walking in order until one does.
+    pub fn find<F>(&self, mut pred: F) -> Option<&T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.iter().find(|val| pred(val))
+    }
+
+    /// Like `traversal_iter`, yielding only the values for which `f` returns `Equal`.
+    ///
+    /// Like the callback of a search, `f` tells where the wanted values are
+    /// relative to the one it is given: `Less` if they are all smaller, `Greater`
+    /// if they are all greater, so whole subtrees that can't hold any are skipped.
+    pub fn traversal_by<F>(&self, typ: &Traversal, f: F) -> TraversalIter<'_, T, C, M, F>
+    where
+        F: FnMut(&T) -> Ordering,
+    {
+        TraversalIter {
+            ids: TraversalIds::pruned(self, typ, self.root(), f),
+        }
+    }
+
+    /// Like `traversal_iter` over the subtree of `id`, empty if `id` isn't in the tree.
+    pub fn subtree_iter(&self, id: NodeId, typ: &Traversal) -> TraversalIter<'_, T, C, M> {
+        let start = self.arena.get(id).map(|_| id);
+        TraversalIter {
+            ids: TraversalIds::new(self, typ, start),
+        }
+    }
+
+    /// Node ids in the given order, to act on the nodes afterwards.
+    pub fn traversal_ids(&self, typ: &Traversal) -> Vec<NodeId> {
+        let mut ids = Vec::with_capacity(self.size());
+        let _: ControlFlow<()> = self.visit_ids(typ, |id| {
+            ids.push(id);
+            ControlFlow::Continue(())
+        });
+        ids
+    }
+
+    /// Like `traversal_map`, with `f` run on worker threads of the rayon pool.
+    ///
+    /// The subtrees of the top few levels are mapped independently with
+    /// `rayon::join` and stitched back in order, which pays off when `f` is
+    /// expensive relative to walking the tree. Levels interleave the subtrees,
+    /// so BFS lays out the values first and maps them in chunks instead.
+    #[cfg(feature = "rayon")]
+    pub fn par_traversal_map<U, F>(&self, typ: &Traversal, f: F) -> Vec<U>
+    where
+        T: Send + Sync,
+        C: Sync,
+        M: Send + Sync,
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        use rayon::prelude::*;
+        if self.arena.is_empty() {
+            return vec![];
+        }
+        match typ.steps() {
+            None => self.traversal(typ).into_par_iter().map(&f).collect(),
+            // A couple of levels past one subtree per thread, deep enough to
+            // even out unbalanced subtrees, shallow enough to never overflow.
+            Some(steps) => {
+                let depth = rayon::current_num_threads()
+                    .next_power_of_two()
+                    .trailing_zeros()
+                    + 2;
+                self.par_subtree_map(self.root_id, typ, &steps, depth, &f)
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_subtree_map<U, F>(
+        &self,
+        id: NodeId,
+        typ: &Traversal,
+        steps: &[Step; 3],
+        depth: u32,
+        f: &F,
+    ) -> Vec<U>
+    where
+        T: Send + Sync,
+        C: Sync,
+        M: Send + Sync,
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        if depth == 0 {
+            return self.subtree_iter(id, typ).map(f).collect();
+        }
+        let node = &self.arena[id];
+        let map = |child: Option<NodeId>| {
+            child.map_or_else(Vec::new, |child| {
+                self.par_subtree_map(child, typ, steps, depth - 1, f)
+            })
+        };
+        let (mut left, mut right) = rayon::join(|| map(node.left()), || map(node.right()));
+        let mut out = Vec::with_capacity(left.len() + right.len() + 1);
+        for step in steps.iter() {
+            match step {
+                Step::N => out.push(f(&node.val)),
+                Step::L => out.append(&mut left),
+                Step::R => out.append(&mut right),
+            }
+        }
+        out
+    }
+
+    /// In-order (LNR) traversal using Morris threading,
+    /// which needs no stack or queue allocation.
+    /// Right links are threaded temporarily and restored before returning.
+    pub fn morris_inorder<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T),
+    {
+        if self.arena.is_empty() {
+            return;
+        }
+        let mut cur = Some(self.root_id);
+        while let Some(id) = cur {
+            match self.arena[id].left() {
+                None => {
+                    f(&self.arena[id].val);
+                    cur = self.arena[id].right();
+                }
+                Some(left_id) => {
+                    let mut pre = left_id;
+                    while let Some(right_id) = self.arena[pre].right() {
+                        if right_id == id {
+                            break;
+                        }
+                        pre = right_id;
+                    }
+                    if self.arena[pre].right().is_none() {
+                        self.arena[pre].set_right(Some(id));
+                        cur = Some(left_id);
+                    } else {
+                        self.arena[pre].set_right(None);
+                        f(&self.arena[id].val);
+                        cur = self.arena[id].right();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Level-order traversal with each depth of the tree grouped together.
+    pub fn levels(&self) -> Vec<Vec<&T>> {
+        self.by_level().collect()
+    }
+
+    /// Lazy level-order traversal yielding the values of one depth at a time.
+    pub fn by_level(&self) -> Levels<'_, T, C, M> {
+        Levels {
+            tree: self,
+            frontier: self.root().into_iter().collect(),
+        }
+    }
+
+    /// Appends the values in the given order to `buf`,
+    /// so that one buffer can be reused across traversals.
+    pub fn traversal_into<'a>(&'a self, typ: &Traversal, buf: &mut Vec<&'a T>) {
+        buf.reserve(self.size());
+        let _: ControlFlow<()> = self.visit(typ, |val| {
+            buf.push(val);
+            ControlFlow::Continue(())
+        });
+    }
+
+    /// Writes the `Display`-formatted values in the given order to `w`,
+    /// separated by `sep`.
+    pub fn traversal_write<W>(&self, typ: &Traversal, w: &mut W, sep: &str) -> io::Result<()>
+    where
+        W: io::Write,
+        T: fmt::Display,
+    {
+        let mut first = true;
+        match self.visit(typ, |val| {
+            let rv = if first {
+                write!(w, "{}", val)
+            } else {
+                write!(w, "{}{}", sep, val)
+            };
+            first = false;
+            match rv {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(e) => ControlFlow::Break(e),
+            }
+        }) {
+            ControlFlow::Continue(()) => Ok(()),
+            ControlFlow::Break(e) => Err(e),
+        }
+    }
+
+    /// Walks the tree in the given order until `f` breaks,
+    /// returning the value it broke with.
+    pub fn visit<'a, B, F>(&'a self, typ: &Traversal, mut f: F) -> ControlFlow<B>
+    where
+        F: FnMut(&'a T) -> ControlFlow<B>,
+    {
+        self.visit_ids(typ, |id| f(&self.arena[id].val))
+    }
+
+    /// Walks the node ids in the given order until `f` breaks.
+    pub(crate) fn visit_ids<B, F>(&self, typ: &Traversal, f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeId) -> ControlFlow<B>,
+    {
+        match self.root() {
+            Some(root) => self.visit_subtree_ids(typ, root, f),
+            None => ControlFlow::Continue(()),
+        }
+    }
+
+    /// Walks the node ids of the subtree of `id` in the given order until `f` breaks.
+    fn visit_subtree_ids<B, F>(&self, typ: &Traversal, id: NodeId, mut f: F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeId) -> ControlFlow<B>,
+    {
+        match typ {
+            Traversal::BFS => self.visit_in_bfs(id, &mut f),
+            _ => self.visit_in_dfs(typ, id, &mut f),
+        }
+    }
+
+    fn visit_in_bfs<B, F>(&self, id: NodeId, f: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeId) -> ControlFlow<B>,
+    {
+        let mut q = VecDeque::with_capacity(self.size());
+        let mut cur = &self.arena[id];
+
+        #[cfg(any(debug_assertions, feature = "checked"))]
+        use std::collections::HashSet;
+        #[cfg(any(debug_assertions, feature = "checked"))]
+        let mut set = HashSet::with_capacity(self.size());
+
+        loop {
+            f(cur.idx)?;
+
+            #[cfg(any(debug_assertions, feature = "checked"))]
+            if !set.insert(cur.idx) {
+                break ControlFlow::Continue(());
+            }
+
+            if let Some(left_id) = cur.left() {
+                q.push_back(left_id);
+            }
+            if let Some(right_id) = cur.right() {
+                q.push_back(right_id);
+            }
+            match q.pop_front() {
+                Some(id) => cur = &self.arena[id],
+                None => break ControlFlow::Continue(()),
+            }
+        }
+    }
+
+    /// Depth-first walk with an explicit stack rather than recursion,
+    /// so degenerate trees don't overflow the call stack.
+    fn visit_in_dfs<B, F>(&self, typ: &Traversal, id: NodeId, f: &mut F) -> ControlFlow<B>
+    where
+        F: FnMut(NodeId) -> ControlFlow<B>,
+    {
+        for id in TraversalIds::new(self, typ, Some(id)) {
+            f(id)?;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[test]
+fn bst_insert_root() {
+    let mut t = ArenaTree::default();
+    let root_id = t.insert(0usize);
+    assert_eq!(t.size(), 1);
+    assert_eq!(root_id, 0);
+
+    println!("arena: {:?}", t);
+}
+
+#[test]
+fn bst_insert_same_root_twice() {
+    let mut t = ArenaTree::default();
+    let root_id = t.insert(0usize);
+    assert_eq!(t.size(), 1);
+    assert_eq!(root_id, 0);
+
+    let new_id = t.insert(0usize);
+    assert_eq!(t.size(), 1);
+    assert_eq!(new_id, 0);
+
+    println!("arena: {:?}", t);
+}
+
+#[test]
+fn bst_insert_same_twice() {
+    let mut t = ArenaTree::default();
+    let root_id = t.insert(10usize);
+    let left_id = t.insert(0usize);
+    assert_eq!(t.size(), 2);
+    assert_eq!(t.arena[left_id].parent().unwrap(), root_id);
+
+    let new_id = t.insert(0usize);
+    assert_eq!(new_id, left_id);
+    assert_eq!(t.size(), 2);
+
+    println!("arena: {:?}", t);
+}
+
+#[test]
+fn bst_insert_less() {
+    let mut t = ArenaTree::default();
+    let root_id = t.insert(10usize);
+    let left_id = t.insert(0usize);
+    assert_eq!(t.size(), 2);
+    assert_eq!(t.arena[left_id].parent().unwrap(), root_id);
+
+    assert_eq!(t.arena[0].left().unwrap(), left_id);
+
+    println!("arena: {:?}", t);
+}
+
+#[test]
+fn bst_insert_greater() {
+    let mut t = ArenaTree::default();
+    let root_id = t.insert(0usize);
+    let left_id = t.insert(10usize);
+    assert_eq!(t.size(), 2);
+    assert_eq!(t.arena[left_id].parent().unwrap(), root_id);
+
+    assert_eq!(t.arena[0].right().unwrap(), left_id);
+
+    println!("arena: {:?}", t);
+}
+
+#[test]
+fn bst_traversal() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+
+    println!("arena: {:?}", t);
+
+    let testcases = [
+        (&Traversal::NLR, vec![&2, &1, &3]),
+        (&Traversal::LNR, vec![&1, &2, &3]),
+        (&Traversal::LRN, vec![&1, &3, &2]),
+        (&Traversal::NRL, vec![&2, &3, &1]),
+        (&Traversal::RNL, vec![&3, &2, &1]),
+        (&Traversal::RLN, vec![&3, &1, &2]),
+        (&Traversal::BFS, vec![&2, &1, &3]),
+    ];
+
+    for (mode, expect) in testcases.iter() {
+        println!("mode: {:?}, expect: {:?}", mode, expect);
+        assert_eq!(&t.traversal(mode), expect);
+    }
+}
+
+#[test]
+fn bst_traversal_complex() {
+    let t = ArenaTree::from_vec(vec![5, 1, 2, 4, 3]);
+
+    println!("arena: {:?}", t);
+
+    let testcases = [
+        (&Traversal::NLR, vec![&5, &1, &2, &4, &3]),
+        (&Traversal::LNR, vec![&1, &2, &3, &4, &5]),
+        (&Traversal::LRN, vec![&3, &4, &2, &1, &5]),
+        (&Traversal::NRL, vec![&5, &1, &2, &4, &3]),
+        (&Traversal::RNL, vec![&5, &4, &3, &2, &1]),
+        (&Traversal::RLN, vec![&3, &4, &2, &1, &5]),
+        (&Traversal::BFS, vec![&5, &1, &2, &4, &3]),
+    ];
+
+    for (mode, expect) in testcases.iter() {
+        println!("mode: {:?}, expect: {:?}", mode, expect);
+        assert_eq!(&t.traversal(mode), expect);
+    }
+}
+
+#[test]
+fn bst_delete_leaf() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    println!("arena: {:?}", t);
+
+    assert_eq!(
+        t.traversal(&Traversal::BFS),
+        vec![&4, &2, &6, &1, &3, &5, &7]
+    );
+
+    assert!(t.delete(&1));
+    assert_eq!(t.traversal(&Traversal::BFS), vec![&4, &2, &6, &3, &5, &7]);
+    assert!(!t.delete(&1));
+}
+
+#[test]
+fn bst_delete_node() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    println!("arena: {:?}", t);
+
+    assert_eq!(
+        t.traversal(&Traversal::BFS),
+        vec![&4, &2, &6, &1, &3, &5, &7]
+    );
+
+    assert!(t.delete(&4));
+    assert_eq!(t.traversal(&Traversal::BFS), vec![&5, &2, &6, &1, &3, &7]);
+    assert!(!t.delete(&4));
+}
+
+#[test]
+fn bst_delete_node_2() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    let testcases = [
+        (4, vec![&5, &2, &6, &1, &3, &7]),
+        (5, vec![&6, &2, &7, &1, &3]),
+        (6, vec![&7, &2, &1, &3]),
+        (7, vec![&2, &1, &3]),
+        (2, vec![&3, &1]),
+        (3, vec![&1]),
+        (1, vec![]),
+    ];
+    for (val, expect) in testcases.iter() {
+        println!("delete {:?}", val);
+        assert!(t.delete(val));
+        assert_eq!(t.traversal(&Traversal::BFS), *expect);
+    }
+}
+
+#[test]
+fn bst_most_left() {
+    let t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    println!("arena: {:?}", t);
+
+    assert_eq!(t.most_left(0), 3);
+    assert_eq!(t.most_left(1), 3);
+    assert_eq!(t.most_left(2), 5);
+}
+
+#[test]
+fn bst_navigation() {
+    let t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3]);
+    println!("arena: {:?}", t);
+
+    assert_eq!(t.parent_of(0), None);
+    assert_eq!(t.left_of(0), Some(1));
+    assert_eq!(t.right_of(0), Some(2));
+    assert_eq!(t.parent_of(3), Some(1));
+    assert_eq!(t.sibling_of(3), Some(4));
+    assert_eq!(t.sibling_of(1), Some(2));
+    assert_eq!(t.sibling_of(2), Some(1));
+    assert_eq!(t.sibling_of(0), None);
+    assert_eq!(t.left_of(2), None);
+    assert_eq!(t.left_of(42), None);
+}
+
+#[test]
+fn bst_visit_early_termination() {
+    let t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+
+    let mut visited = vec![];
+    let found = t.visit(&Traversal::LNR, |&val| {
+        visited.push(val);
+        if val > 3 {
+            ControlFlow::Break(val)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(found, ControlFlow::Break(4));
+    assert_eq!(visited, vec![1, 2, 3, 4]);
+
+    let found = t.visit(&Traversal::BFS, |&val| {
+        if val > 10 {
+            ControlFlow::Break(val)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(found, ControlFlow::Continue(()));
+}
+
+#[test]
+fn bst_traversal_map() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+
+    let prefix = "#";
+    assert_eq!(
+        t.traversal_map(&Traversal::LNR, |val| format!("{}{}", prefix, val)),
+        vec!["#1", "#2", "#3"]
+    );
+
+    let refs: Vec<&i32> = t.traversal_map(&Traversal::NLR, |val| val);
+    assert_eq!(refs, vec![&2, &1, &3]);
+
+    let mut count = 0;
+    let depths = t.traversal_map(&Traversal::BFS, |_| {
+        count += 1;
+        count
+    });
+    assert_eq!(depths, vec![1, 2, 3]);
+}
+
+#[test]
+fn bst_traversal_into() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+
+    let mut buf = vec![&0];
+    t.traversal_into(&Traversal::LNR, &mut buf);
+    assert_eq!(buf, vec![&0, &1, &2, &3]);
+
+    buf.clear();
+    t.traversal_into(&Traversal::RNL, &mut buf);
+    assert_eq!(buf, vec![&3, &2, &1]);
+}
+
+#[test]
+fn bst_traversal_write() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+
+    let mut out = Vec::new();
+    t.traversal_write(&Traversal::NLR, &mut out, ", ").unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "2, 1, 3");
+
+    let mut out = Vec::new();
+    ArenaTree::<i32>::default()
+        .traversal_write(&Traversal::NLR, &mut out, ", ")
+        .unwrap();
+    assert!(out.is_empty());
+}
+
+#[test]
+fn bst_leaves() {
+    let t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 7]);
+
+    assert_eq!(t.leaves().copied().collect::<Vec<_>>(), vec![1, 3, 7]);
+    assert_eq!(t.leaf_ids().collect::<Vec<_>>(), vec![3, 4, 5]);
+
+    let t = ArenaTree::<i32>::default();
+    assert_eq!(t.leaves().count(), 0);
+}
+
+#[test]
+fn bst_levels() {
+    let t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 7]);
+    assert_eq!(t.levels(), vec![vec![&4], vec![&2, &6], vec![&1, &3, &7]]);
+
+    let t = ArenaTree::from_vec(vec![1, 2, 3]);
+    assert_eq!(t.levels(), vec![vec![&1], vec![&2], vec![&3]]);
+
+    assert!(ArenaTree::<i32>::default().levels().is_empty());
+}
+
+#[test]
+fn bst_morris_inorder() {
+    let mut t = ArenaTree::from_vec(vec![5, 1, 2, 4, 3, 8, 6, 9]);
+    let expect = t.traversal_map(&Traversal::LNR, |&val| val);
+    let bfs = t.traversal_map(&Traversal::BFS, |&val| val);
+
+    let mut path = vec![];
+    t.morris_inorder(|&val| path.push(val));
+    assert_eq!(path, expect);
+    assert_eq!(t.traversal_map(&Traversal::BFS, |&val| val), bfs);
+}
+
+#[test]
+fn bst_iter_double_ended() {
+    let t = ArenaTree::from_vec(vec![5, 1, 2, 4, 3, 8, 6, 9]);
+
+    assert_eq!(
+        t.iter().copied().collect::<Vec<_>>(),
+        vec![1, 2, 3, 4, 5, 6, 8, 9]
+    );
+    assert_eq!(
+        t.iter().rev().copied().collect::<Vec<_>>(),
+        vec![9, 8, 6, 5, 4, 3, 2, 1]
+    );
+
+    let mut it = t.iter();
+    assert_eq!(it.next(), Some(&1));
+    assert_eq!(it.next_back(), Some(&9));
+    assert_eq!(it.next(), Some(&2));
+    assert_eq!(it.next_back(), Some(&8));
+    assert_eq!(it.copied().collect::<Vec<_>>(), vec![3, 4, 5, 6]);
+
+    assert_eq!(ArenaTree::<i32>::default().iter().next(), None);
+}
+
+#[test]
+fn bst_iter_after_delete() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    assert!(t.delete(&4));
+    assert!(t.delete(&2));
+    assert_eq!(t.iter().copied().collect::<Vec<_>>(), vec![1, 3, 5, 6, 7]);
+    assert_eq!(
+        t.iter().rev().copied().collect::<Vec<_>>(),
+        vec![7, 6, 5, 3, 1]
+    );
+}
+
+#[test]
+fn bst_range() {
+    let t = ArenaTree::from_vec(vec![5, 1, 2, 4, 3, 8, 6, 9]);
+    assert!(t.range(3..8).copied().eq(vec![3, 4, 5, 6]));
+    assert!(t.range(..=4).rev().copied().eq(vec![4, 3, 2, 1]));
+    assert!(t
+        .range((Bound::Excluded(6), Bound::Unbounded))
+        .copied()
+        .eq(vec![8, 9]));
+    assert_eq!(t.range(7..8).next(), None);
+    assert_eq!(t.range(10..).next(), None);
+    assert_eq!(t.range(..).count(), 8);
+}
+
+#[test]
+fn bst_snapshot() {
+    let mut t = ArenaTree::from_vec((0..200).collect());
+    let snapshot = t.snapshot();
+    for val in (0..200).filter(|val| val % 2 == 0) {
+        t.delete(&val);
+    }
+    t.insert(500);
+    let copy = snapshot.clone();
+    let later = t.snapshot();
+    t.compact();
+    for val in (1..200).step_by(2) {
+        t.delete(&val);
+    }
+
+    assert!(t.size() == 1 && !t.contains(&1));
+    assert!(snapshot.iter().copied().eq(0..200));
+    assert!(copy.iter().copied().eq(0..200));
+    assert_eq!(snapshot.height(), 200);
+    assert!(later
+        .iter()
+        .copied()
+        .eq((1..200).step_by(2).chain(Some(500))));
+    assert!(later.contains(&500) && !later.contains(&0));
+}
+
+#[test]
+fn bst_non_copy_values() {
+    let mut t = ArenaTree::default();
+    for name in ["bob", "alice", "carol", "dave"].iter() {
+        t.insert(name.to_string());
+    }
+    assert_eq!(t.insert("alice".to_string()), 1);
+    assert_eq!(t.search(&"carol".to_string()), Some(2));
+    assert_eq!(
+        t.traversal(&Traversal::LNR),
+        vec!["alice", "bob", "carol", "dave"]
+    );
+
+    assert!(t.delete(&"bob".to_string()));
+    assert_eq!(t.traversal(&Traversal::NLR), vec!["carol", "alice", "dave"]);
+}
+
+#[test]
+fn bst_with_comparator() {
+    let mut t = ArenaTree::with_comparator(|a: &String, b: &String| {
+        a.to_lowercase().cmp(&b.to_lowercase())
+    });
+    for name in ["bob", "Alice", "carol", "Dave"].iter() {
+        t.insert(name.to_string());
+    }
+    assert_eq!(t.insert("ALICE".to_string()), 1);
+    assert_eq!(t.search(&"CAROL".to_string()), Some(2));
+    assert_eq!(
+        t.traversal(&Traversal::LNR),
+        vec!["Alice", "bob", "carol", "Dave"]
+    );
+
+    let mut t = ArenaTree::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+    for val in [2, 1, 3].iter() {
+        t.insert(*val);
+    }
+    assert_eq!(t.iter().copied().collect::<Vec<_>>(), vec![3, 2, 1]);
+    assert!(t.delete(&3));
+    assert_eq!(t.iter().copied().collect::<Vec<_>>(), vec![2, 1]);
+}
+
+#[test]
+fn bst_borrow_lookup() {
+    let mut t = ArenaTree::from_vec(vec![
+        "bob".to_string(),
+        "alice".to_string(),
+        "carol".to_string(),
+    ]);
+    assert_eq!(t.search("alice"), Some(1));
+    assert_eq!(t.search("dave"), None);
+    assert!(t.contains("carol"));
+    assert!(!t.contains("dave"));
+
+    assert!(t.delete("bob"));
+    assert!(!t.contains("bob"));
+    assert_eq!(t.traversal(&Traversal::LNR), vec!["alice", "carol"]);
+}
+
+#[test]
+fn bst_insert_after_clear() {
+    let mut t = ArenaTree::from_vec(vec![2, 1, 3]);
+    assert!(t.delete(&1));
+    assert!(t.delete(&2));
+    assert!(t.delete(&3));
+    assert_eq!(t.size(), 0);
+
+    t.insert(5);
+    t.insert(4);
+    assert_eq!(t.traversal(&Traversal::LNR), vec![&4, &5]);
+}
+
+#[test]
+fn bst_unsized_keys() {
+    let mut t: ArenaTree<Box<str>> = ArenaTree::default();
+    let mut allocations = 0;
+    for word in ["b", "a", "b", "c", "a"].iter() {
+        t.get_or_insert_with(*word, |w| {
+            allocations += 1;
+            w.into()
+        });
+    }
+    assert_eq!(allocations, 3);
+    assert_eq!(t.search("c"), Some(2));
+    assert!(t.delete("a"));
+    assert_eq!(t.traversal(&Traversal::LNR), vec![&"b".into(), &"c".into()]);
+
+    let mut t: ArenaTree<Box<[u8]>> = ArenaTree::default();
+    t.insert(b"xyz".to_vec().into_boxed_slice());
+    assert_eq!(t.get_or_insert_with(&b"xyz"[..], |b| b.into()), 0);
+    assert!(t.contains(&b"xyz"[..]));
+}
+
+#[test]
+fn bst_node_size() {
+    use std::mem::size_of;
+    #[cfg(not(feature = "u32-index"))]
+    assert_eq!(size_of::<Node<u32>>(), 40);
+    #[cfg(feature = "u32-index")]
+    assert_eq!(size_of::<Node<u32>>(), 20);
+}
+
+#[test]
+fn bst_link_niche() {
+    use std::mem::size_of;
+    assert_eq!(size_of::<Link>(), size_of::<NodeId>());
+    assert_eq!(Link::new(Some(0)).get(), Some(0));
+    assert_eq!(Link::new(None).get(), None);
+    assert_eq!(format!("{:?}", Link::new(Some(3))), "Some(3)");
+}
+
+#[test]
+fn bst_with_key_fn() {
+    #[derive(Debug, PartialEq)]
+    struct Employee {
+        id: u32,
+        name: &'static str,
+    }
+
+    let mut t = ArenaTree::with_key_fn(|e: &Employee| e.id);
+    t.insert(Employee {
+        id: 3,
+        name: "carol",
+    });
+    t.insert(Employee {
+        id: 1,
+        name: "alice",
+    });
+    t.insert(Employee { id: 2, name: "bob" });
+    assert_eq!(t.insert(Employee { id: 1, name: "dup" }), 1);
+
+    assert_eq!(t.get_by_key(&2).map(|e| e.name), Some("bob"));
+    assert_eq!(t.search_key(&3), Some(0));
+    assert!(t.contains_key(&1));
+    assert!(!t.contains_key(&4));
+    assert_eq!(
+        t.traversal_map(&Traversal::LNR, |e| e.name),
+        vec!["alice", "bob", "carol"]
+    );
+
+    assert!(t.delete_key(&3));
+    assert!(!t.delete_key(&3));
+    assert_eq!(t.traversal_map(&Traversal::LNR, |e| e.id), vec![1, 2]);
+}
+
+#[test]
+fn bst_node_meta() {
+    let mut t: ArenaTree<i32, Natural, &str> = ArenaTree::with_comparator_and_meta(Natural);
+    let a = t.insert(2);
+    let b = t.insert(1);
+    assert_eq!(t.meta(a), Some(&""));
+
+    assert_eq!(t.set_meta(a, "red"), Some(""));
+    *t.meta_mut(b).unwrap() = "black";
+    assert_eq!(t.meta(a), Some(&"red"));
+    assert_eq!(t.meta(b), Some(&"black"));
+    assert_eq!(t.set_meta(42, "none"), None);
+
+    assert!(t.delete(&1));
+    assert_eq!(t.meta(b), None);
+}
+
+#[test]
+fn bst_descending() {
+    let mut t = ArenaTree::descending();
+    for val in [4, 2, 6, 1, 3, 5, 7].iter() {
+        t.insert(*val);
+    }
+    assert_eq!(
+        t.traversal(&Traversal::LNR),
+        vec![&7, &6, &5, &4, &3, &2, &1]
+    );
+    assert_eq!(t.iter().next(), Some(&7));
+    assert_eq!(t.iter().next_back(), Some(&1));
+    assert!(t.contains(&5));
+    assert!(t.delete(&4));
+    assert_eq!(
+        t.iter().copied().collect::<Vec<_>>(),
+        vec![7, 6, 5, 3, 2, 1]
+    );
+
+    let mut t = ArenaTree::descending();
+    t.insert("b".to_string());
+    t.insert("a".to_string());
+    assert_eq!(t.search("a"), Some(1));
+}
+
+#[test]
+fn bst_clone_compacts() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    assert!(t.delete(&4));
+    assert!(t.delete(&1));
+    assert_eq!(t.arena.slots_len(), 7);
+
+    let c = t.clone();
+    assert_eq!(c.size(), 5);
+    assert_eq!(c.arena.slots_len(), 5);
+    assert_eq!(c.traversal(&Traversal::BFS), t.traversal(&Traversal::BFS));
+    assert_eq!(c.iter().rev().collect::<Vec<_>>(), vec![&7, &6, &5, &3, &2]);
+    assert_eq!(c.get(c.root_id), Some(&5));
+    assert_eq!(c.left_of(c.root_id).and_then(|id| c.get(id)), Some(&2));
+
+    let c = ArenaTree::<i32>::default().clone();
+    assert_eq!(c.size(), 0);
+}
+
+#[test]
+fn bst_eq() {
+    let a = ArenaTree::from_vec(vec![2, 1, 3]);
+    let b = ArenaTree::from_vec(vec![1, 2, 3]);
+    let mut c = ArenaTree::from_vec(vec![2, 1, 3, 4]);
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert!(c.delete(&4));
+    assert_eq!(a, c);
+    assert_ne!(a, ArenaTree::from_vec(vec![2, 1, 5]));
+    assert_eq!(ArenaTree::<i32>::default(), ArenaTree::default());
+}
+
+#[test]
+fn bst_structurally_eq() {
+    let a = ArenaTree::from_vec(vec![2, 1, 3]);
+    let b = ArenaTree::from_vec(vec![2, 3, 1]);
+    let c = ArenaTree::from_vec(vec![1, 2, 3]);
+    assert!(a.structurally_eq(&b));
+    assert!(!a.structurally_eq(&c));
+
+    let mut d = ArenaTree::from_vec(vec![5, 2, 1, 3]);
+    assert!(d.delete(&5));
+    assert!(a.structurally_eq(&d));
+    assert!(!a.structurally_eq(&ArenaTree::from_vec(vec![2, 0, 3])));
+}
+
+#[test]
+fn bst_hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+
+    fn hash_of(t: &ArenaTree<i32>) -> u64 {
+        let mut h = DefaultHasher::new();
+        t.hash(&mut h);
+        h.finish()
+    }
+
+    let a = ArenaTree::from_vec(vec![2, 1, 3]);
+    let mut b = ArenaTree::from_vec(vec![3, 1, 2, 4]);
+    assert_ne!(hash_of(&a), hash_of(&b));
+    assert!(b.delete(&4));
+    assert_eq!(hash_of(&a), hash_of(&b));
+
+    let mut memo = HashMap::new();
+    memo.insert(a, "seen");
+    assert_eq!(memo.get(&b), Some(&"seen"));
+}
+
+#[test]
+fn bst_ord() {
+    let a = ArenaTree::from_vec(vec![2, 1, 3]);
+    let b = ArenaTree::from_vec(vec![1, 2]);
+    let c = ArenaTree::from_vec(vec![1, 4]);
+    assert!(b < a);
+    assert!(a < c);
+    assert!(ArenaTree::default() < b);
+
+    let mut trees = vec![c, a, b, ArenaTree::from_vec(vec![3, 2, 1])];
+    trees.sort();
+    trees.dedup();
+    assert_eq!(
+        trees
+            .iter()
+            .map(|t| t.iter().copied().collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        vec![vec![1, 2], vec![1, 2, 3], vec![1, 4]]
+    );
+}
+
+#[test]
+fn bst_reuse_deleted_slots() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    assert!(t.delete(&2));
+    assert!(t.delete(&6));
+    assert_eq!(t.insert(8), 2);
+    assert_eq!(t.insert(0), 1);
+    assert_eq!(t.arena.slots_len(), 7);
+
+    for round in 0..100 {
+        let val = 10 + round;
+        t.insert(val);
+        assert!(t.delete(&val));
+    }
+    assert_eq!(t.arena.slots_len(), 8);
+    assert_eq!(
+        t.iter().copied().collect::<Vec<_>>(),
+        vec![0, 1, 3, 4, 5, 7, 8]
+    );
+}
+
+#[test]
+fn bst_compact() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    assert!(t.delete(&4));
+    assert!(t.delete(&1));
+    let bfs: Vec<_> = t.traversal_map(&Traversal::BFS, |&val| val);
+    let six = t.search(&6).unwrap();
+
+    let remap = t.compact();
+    assert_eq!(
+        remap,
+        vec![None, Some(0), Some(1), None, Some(2), Some(3), Some(4)]
+    );
+    assert_eq!(t.arena.slots_len(), 5);
+    assert_eq!(t.traversal_map(&Traversal::BFS, |&val| val), bfs);
+    assert_eq!(t.search(&6), remap[slot(six)]);
+    assert_eq!(t.iter().rev().collect::<Vec<_>>(), vec![&7, &6, &5, &3, &2]);
+
+    assert_eq!(t.insert(8), 5);
+}
+
+#[test]
+fn bst_capacity() {
+    let mut t = ArenaTree::default();
+    t.reserve(10);
+    assert!(t.capacity() >= 10);
+    for val in 0..10 {
+        t.insert(val);
+    }
+
+    assert!(t.delete(&9));
+    assert!(t.delete(&8));
+    assert!(t.delete(&2));
+    let capacity = t.capacity();
+    t.reserve(3);
+    assert_eq!(t.capacity(), capacity);
+
+    t.shrink_to_fit();
+    assert_eq!(t.arena.slots_len(), 8);
+    assert!(t.capacity() >= 8);
+    assert_eq!(t.insert(2), 2);
+    assert_eq!(t.insert(9), 8);
+    assert_eq!(t.iter().count(), 9);
+}
+
+#[test]
+fn bst_with_capacity() {
+    let t = ArenaTree::<i32>::with_capacity(16);
+    assert!(t.capacity() >= 16);
+    assert_eq!(t.size(), 0);
+
+    let t = ArenaTree::from_vec((0..100).collect());
+    assert_eq!(t.capacity(), 2 * crate::slots::CHUNK_LEN);
+}
+
+#[test]
+fn bst_memory_usage() {
+    use crate::slots::CHUNK_LEN;
+    use std::mem::size_of;
+    let mut t = ArenaTree::default();
+    assert_eq!(t.memory_usage(), 0);
+
+    t.insert(1);
+    let chunk = CHUNK_LEN * size_of::<Option<Node<i32>>>();
+    assert!(t.memory_usage() >= chunk);
+    let before = t.memory_usage();
+    for val in 2..=CHUNK_LEN as i32 {
+        t.insert(val);
     }
+    assert!(t.delete(&1));
+    assert!(t.memory_usage() > before);
+    assert!(t.memory_usage() < 2 * chunk);
+}
 
-    fn most_left(&self, id: usize) -> usize {
-        let mut cur = &self.arena[id];
-        loop {
-            cur = match cur.left {
-                Some(id) => &self.arena[id],
-                None => break cur.idx,
-            };
-        }
+#[test]
+fn bst_compaction_threshold() {
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9, 2, 6, 10]);
+    t.set_compaction_threshold(Some(0.25));
+    let id = t.search(&10).unwrap();
+    let h = t.handle(id).unwrap();
+    assert!(t.delete(&1));
+    assert!(t.delete(&2));
+    assert_eq!(t.arena.slots_len(), 10);
+    assert_eq!(t.resolve(h), Some(9));
+
+    assert!(t.delete(&3));
+    assert_eq!(t.arena.slots_len(), 7);
+    assert_eq!(t.resolve(h), None);
+    assert_eq!(t.traversal(&Traversal::LNR), [&4, &5, &6, &7, &8, &9, &10]);
+
+    t.set_compaction_threshold(None);
+    for val in [4, 5, 6].iter() {
+        assert!(t.delete(val));
     }
+    assert_eq!(t.arena.slots_len(), 7);
+}
 
-    /// delete may produce a gap in arena.
-    pub fn delete(&mut self, val: T) -> bool {
-        match self.search(val) {
-            None => false,
-            Some(id) => {
-                let (parent_id, right_id, left_id) = {
-                    let cur = &self.arena[id];
-                    (cur.parent, cur.right, cur.left)
-                };
-                macro_rules! update_parent {
-                    ($parent_id: expr, $id: expr, $original_id: expr) => {
-                        match ($parent_id, $id) {
-                            (None, None) => self.arena.clear(),
-                            (None, Some(id)) => {
-                                self.root_id = id;
-                            }
-                            (Some(parent_id), val) => {
-                                let parent = &mut self.arena[parent_id];
-                                if parent.left == Some($original_id) {
-                                    parent.left = val;
-                                } else {
-                                    parent.right = val;
-                                }
-                            }
-                        }
-                    };
-                }
-                match (left_id, right_id) {
-                    (None, None) => update_parent!(parent_id, None, id),
-                    (Some(left_id), Some(right_id)) => {
-                        let candidate_id = self.most_left(right_id);
-                        update_parent!(parent_id, Some(candidate_id), id);
-                        let (candidate_parent_id, candidate_right) = {
-                            let candidate = &mut self.arena[candidate_id];
-                            let candidate_right = candidate.right;
-                            let candidate_parent_id = if right_id == candidate_id {
-                                Some(candidate_id)
-                            } else {
-                                candidate.parent
-                            };
-
-                            candidate.right = Some(right_id);
-                            candidate.left = Some(left_id);
-                            candidate.parent = parent_id;
-
-                            (candidate_parent_id, candidate_right)
-                        };
-                        update_parent!(candidate_parent_id, candidate_right, candidate_id);
-                    }
-                    (Some(left_id), None) => {
-                        update_parent!(parent_id, Some(left_id), id);
-                        self.arena[left_id].parent = parent_id;
-                    }
-                    (None, Some(right_id)) => {
-                        update_parent!(parent_id, Some(right_id), id);
-                        self.arena[right_id].parent = parent_id;
-                    }
-                }
-                true
-            }
+#[test]
+fn bst_into_sorted_vec() {
+    let mut t = ArenaTree::from_vec(
+        [5, 3, 8, 1, 4]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>(),
+    );
+    assert!(t.delete("3"));
+    assert_eq!(t.into_sorted_vec(), ["1", "4", "5", "8"]);
+    assert!(ArenaTree::<i32>::default().into_sorted_vec().is_empty());
+}
+
+#[test]
+fn bst_rebalance() {
+    for &size in [0, 1, 2, 7, 100, 127].iter() {
+        let mut t = ArenaTree::from_vec((0..size).collect());
+        let id = t.root();
+        t.rebalance();
+        assert_eq!(t.height(), (size as f64 + 1.0).log2().ceil() as usize);
+        assert!(t.iter().copied().eq(0..size));
+        assert_eq!(id.and_then(|id| t.get(id)), id.map(|_| &0));
+        if let Some(root) = t.root() {
+            assert_eq!(t.parent_of(root), None);
         }
     }
+}
 
-    pub fn traversal(&self, typ: &Traversal) -> Vec<T> {
-        self.traversal_map(typ, |x| x)
+#[test]
+fn bst_from_sorted() {
+    let t = ArenaTree::from_sorted_iter(0..1000);
+    assert_eq!(t.size(), 1000);
+    assert_eq!(t.height(), 10);
+    assert!(t.iter().copied().eq(0..1000));
+
+    let t = ArenaTree::from_sorted_slice(&[1, 2, 2, 3]);
+    assert_eq!(t.traversal(&Traversal::NLR), [&2, &1, &3]);
+    assert_eq!(t.root().and_then(|id| t.parent_of(id)), None);
+    assert_eq!(ArenaTree::<i32>::from_sorted_slice(&[]).root(), None);
+}
+
+#[test]
+#[should_panic(expected = "values out of order")]
+fn bst_from_sorted_rejects_unsorted() {
+    ArenaTree::from_sorted_slice(&[1, 3, 2]);
+}
+
+#[test]
+fn bst_growth_keeps_nodes_in_place() {
+    let mut t = ArenaTree::default();
+    let id = t.insert(0);
+    let ptr = t.get(id).unwrap() as *const i32;
+    for val in 1..1000 {
+        t.insert(val);
     }
+    assert_eq!(t.get(id).unwrap() as *const i32, ptr);
+    assert!(t.capacity() >= 1000);
+}
 
-    pub fn traversal_map(&self, typ: &Traversal, f: fn(T) -> T) -> Vec<T> {
-        if self.arena.is_empty() {
-            return vec![];
-        }
-        let mut path = Vec::with_capacity(self.size());
-        match typ {
-            Traversal::BFS => self.traversal_map_in_bfs(f, &mut path),
-            _ => self.recursive_traversal_map_in_dfs(typ, f, Some(self.root_id), &mut path),
-        }
-        path
+#[test]
+fn bst_handles() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6]);
+    let h = t.insert_handle(1);
+    assert_eq!(t.insert_handle(1), h);
+    assert_eq!(t.get_by_handle(h), Some(&1));
+    assert_eq!(t.resolve(h), Some(h.id()));
+
+    assert!(t.delete(&1));
+    assert_eq!(t.resolve(h), None);
+    let id = t.insert(3);
+    assert_eq!(id, h.id());
+    assert_eq!(t.get_by_handle(h), None);
+    assert_eq!(t.get(h.id()), Some(&3));
+
+    let six = t.handle(2).unwrap();
+    let three = t.handle(id).unwrap();
+    assert!(t.delete(&2));
+    t.compact();
+    assert_eq!(t.get_by_handle(six), None);
+    assert_eq!(t.get_by_handle(three), None);
+    assert_eq!(t.get_by_handle(t.handle(0).unwrap()), Some(&4));
+
+    let four = t.handle(0).unwrap();
+    for val in [4, 6, 3].iter() {
+        assert!(t.delete(val));
     }
+    t.insert(5);
+    assert_eq!(t.get_by_handle(four), None);
+    assert_eq!(t.handle(42), None);
+}
 
-    fn traversal_map_in_bfs(&self, f: fn(T) -> T, path: &mut Vec<T>) {
-        use std::collections::VecDeque;
-        let mut q = VecDeque::with_capacity(self.size());
-        let mut cur = &self.arena[self.root_id];
+#[test]
+fn bst_clear() {
+    let mut t = ArenaTree::from_vec(vec![2, 1, 3]);
+    let handle = t.handle(t.root().unwrap()).unwrap();
+    t.clear();
+    assert_eq!(t.size(), 0);
+    assert!(t.resolve(handle).is_none());
+    t.insert(5);
+    assert_eq!(t.traversal(&Traversal::LNR), [&5]);
+}
 
-        #[cfg(debug_assertions)]
-        use std::collections::HashSet;
-        #[cfg(debug_assertions)]
-        let mut set = HashSet::with_capacity(self.size());
+#[test]
+fn bst_handle_stale_after_compact() {
+    let mut t = ArenaTree::from_vec(vec![1, 2, 3]);
+    let three = t.handle(t.search(&3).unwrap()).unwrap();
+    assert!(t.delete(&2));
+    t.compact();
+    // The slot 3 moved out of is reclaimed by 10.
+    assert_eq!(t.insert(10), three.id());
+    assert_eq!(t.resolve(three), None);
+    assert_eq!(t.get_by_handle(three), None);
+    let three = t.handle(t.search(&3).unwrap()).unwrap();
+    assert_eq!(t.get_by_handle(three), Some(&3));
+}
 
-        loop {
-            path.push(f(cur.val));
+#[test]
+fn bst_stats() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    assert_eq!(t.height(), 3);
+    assert!(t.delete(&5));
+    assert!(t.delete(&7));
 
-            #[cfg(debug_assertions)]
-            if !set.insert(cur.idx) {
-                break;
-            }
+    let stats = t.stats();
+    println!("stats: {:?}", stats);
+    assert_eq!(stats.live, 5);
+    assert_eq!(stats.tombstones, 2);
+    assert!(stats.capacity >= 7);
+    assert_eq!(stats.height, 3);
+    assert_eq!(stats.average_depth, 1.2);
 
-            if cur.left.is_some() {
-                q.push_back(cur.left.unwrap());
-            }
-            if cur.right.is_some() {
-                q.push_back(cur.right.unwrap());
-            }
-            match q.pop_front() {
-                Some(id) => cur = &self.arena[id],
-                None => break,
-            }
-        }
-    }
+    assert_eq!(ArenaTree::from_vec((0..10).collect()).height(), 10);
+    let stats = ArenaTree::<i32>::default().stats();
+    assert_eq!((stats.live, stats.height), (0, 0));
+    assert_eq!(stats.average_depth, 0.0);
+}
 
-    fn recursive_traversal_map_in_dfs(
-        &self,
-        typ: &Traversal,
-        f: fn(T) -> T,
-        id: Option<usize>,
-        path: &mut Vec<T>,
-    ) {
-        match id {
-            None => {}
-            Some(id) => {
-                let node = &self.arena[id];
-                macro_rules! R {
-                    () => {
-                        self.recursive_traversal_map_in_dfs(typ, f, node.right, path);
-                    };
-                }
-                macro_rules! L {
-                    () => {
-                        self.recursive_traversal_map_in_dfs(typ, f, node.left, path);
-                    };
-                }
-                macro_rules! N {
-                    () => {
-                        path.push(f(node.val));
-                    };
-                }
-                macro_rules! invoke_marcos {
-					($($name: ident),*) => {{
-						$($name!();)*
-					}};
-				}
-                match typ {
-                    Traversal::NLR => invoke_marcos!(N, L, R),
-                    Traversal::LNR => invoke_marcos!(L, N, R),
-                    Traversal::LRN => invoke_marcos!(L, R, N),
-                    Traversal::NRL => invoke_marcos!(N, R, L),
-                    Traversal::RNL => invoke_marcos!(R, N, L),
-                    Traversal::RLN => invoke_marcos!(R, L, N),
-                    Traversal::BFS => unreachable!(),
-                }
-            }
-        }
+#[test]
+fn bst_balance_factor() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
+    assert!(t.is_balanced());
+    let root = t.root().unwrap();
+    assert_eq!(t.balance_factor(root), Some(0));
+    assert!(t.delete(&5));
+    assert!(t.delete(&7));
+    assert_eq!(t.balance_factor(root), Some(1));
+    assert!(t.is_balanced());
+    assert!(t.delete(&6));
+    assert_eq!(t.balance_factor(root), Some(2));
+    assert!(!t.is_balanced());
+    let id = t.search(&2).unwrap();
+    assert_eq!(t.balance_factor(id), Some(0));
+    assert!(ArenaTree::<i32>::default().is_balanced());
+
+    // Degenerate trees deep enough to overflow a recursive walk.
+    let mut t = ArenaTree::default();
+    let mut pos = None;
+    for val in 0..200_000 {
+        pos = Some((t.attach(pos, val), false));
     }
+    assert!(!t.is_balanced());
+    assert_eq!(t.balance_factor(t.root().unwrap()), Some(-199_999));
+    t.rebalance();
+    assert!(t.is_balanced());
 }
 
 #[test]
-fn bst_insert_root() {
+fn bst_insert_sorted_batch() {
+    let mut t = ArenaTree::from_vec(vec![10, 20, 30, 40]);
+    let id = t.search(&30).unwrap();
+    t.insert_sorted_batch(&[1, 5, 5, 20, 25, 50, 60]).unwrap();
+    assert!(t.iter().copied().eq(vec![1, 5, 10, 20, 25, 30, 40, 50, 60]));
+    assert_eq!(t.size(), 9);
+    assert_eq!(t.search(&30), Some(id));
+    assert!(t.is_balanced());
+
+    // Small batches go through plain inserts.
+    let mut t = ArenaTree::from_sorted_iter(0..1000);
+    t.insert_sorted_batch(&[2000, 3000]).unwrap();
+    assert_eq!(t.size(), 1002);
+    assert_eq!(t.iter().next_back(), Some(&3000));
+
     let mut t = ArenaTree::default();
-    let root_id = t.insert(0usize);
-    assert_eq!(t.size(), 1);
-    assert_eq!(root_id, 0);
+    t.insert_sorted_batch(&(0..100).collect::<Vec<_>>())
+        .unwrap();
+    assert!(t.iter().copied().eq(0..100));
+    assert_eq!(t.height(), 7);
+}
 
-    println!("arena: {:?}", t);
+#[test]
+#[should_panic(expected = "values out of order")]
+fn bst_insert_sorted_batch_small_out_of_order() {
+    let mut t = ArenaTree::from_sorted_iter(0..1000);
+    t.insert_sorted_batch(&[5, 3]).unwrap();
 }
 
 #[test]
-fn bst_insert_same_root_twice() {
+fn bst_insert_sorted_batch_full() {
+    let mut t = ArenaTree::with_max_len(5);
+    for val in [10, 20, 30].iter() {
+        t.insert(*val);
+    }
+    // Values already present don't count against the cap.
+    t.insert_sorted_batch(&[5, 10, 20, 25]).unwrap();
+    assert_eq!(t.size(), 5);
+    assert_eq!(
+        t.insert_sorted_batch(&[1, 2, 3, 4, 6, 7, 8]),
+        Err(InsertError::Full)
+    );
+    assert!(t.iter().copied().eq(vec![5, 10, 20, 25, 30]));
+    assert_eq!(t.validate(), Ok(()));
+}
+
+#[test]
+fn bst_traversal_degenerate() {
+    // Deep enough to overflow a recursive walk.
     let mut t = ArenaTree::default();
-    let root_id = t.insert(0usize);
-    assert_eq!(t.size(), 1);
-    assert_eq!(root_id, 0);
+    let mut pos = None;
+    for val in 0..100_000 {
+        pos = Some((t.attach(pos, val), false));
+    }
+    assert!(t
+        .traversal(&Traversal::NLR)
+        .into_iter()
+        .copied()
+        .eq(0..100_000));
+    assert!(t
+        .traversal(&Traversal::LNR)
+        .into_iter()
+        .copied()
+        .eq(0..100_000));
+    assert!(t
+        .traversal(&Traversal::LRN)
+        .into_iter()
+        .copied()
+        .eq((0..100_000).rev()));
+    assert!(t
+        .traversal(&Traversal::RLN)
+        .into_iter()
+        .copied()
+        .eq((0..100_000).rev()));
+    let found = t.visit(&Traversal::RNL, |val| {
+        if val % 1000 == 999 {
+            ControlFlow::Break(*val)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(found, ControlFlow::Break(99_999));
+}
 
-    let new_id = t.insert(0usize);
-    assert_eq!(t.size(), 1);
-    assert_eq!(new_id, 0);
+#[cfg(feature = "rayon")]
+#[test]
+fn bst_par_traversal_map() {
+    let t = ArenaTree::from_vec((0..1000).map(|val| val * 7 % 1000).collect());
+    for typ in [
+        Traversal::NLR,
+        Traversal::LRN,
+        Traversal::RNL,
+        Traversal::BFS,
+    ]
+    .iter()
+    {
+        let expected = t.traversal_map(typ, |val| val.to_string());
+        assert_eq!(t.par_traversal_map(typ, |val| val.to_string()), expected);
+    }
+    // Splits below the root even when a side is missing.
+    let t = ArenaTree::from_vec(vec![1, 2, 3, 4, 0]);
+    assert_eq!(
+        t.par_traversal_map(&Traversal::LNR, |val| *val),
+        [0, 1, 2, 3, 4]
+    );
+    assert!(ArenaTree::<i32>::default()
+        .par_traversal_map(&Traversal::LNR, |val| *val)
+        .is_empty());
+}
 
-    println!("arena: {:?}", t);
+#[test]
+fn bst_traversal_ids() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4]);
+    let ids = t.traversal_ids(&Traversal::LRN);
+    let vals: Vec<_> = ids.iter().map(|id| *t.get(*id).unwrap()).collect();
+    assert_eq!(vals, [1, 4, 3, 8, 5]);
+    assert_eq!(t.traversal_ids(&Traversal::BFS)[0], t.root().unwrap());
+    assert!(ArenaTree::<i32>::default()
+        .traversal_ids(&Traversal::NLR)
+        .is_empty());
 }
 
 #[test]
-fn bst_insert_same_twice() {
-    let mut t = ArenaTree::default();
-    let root_id = t.insert(10usize);
-    let left_id = t.insert(0usize);
-    assert_eq!(t.size(), 2);
-    assert_eq!(t.arena[left_id].parent.unwrap(), root_id);
+fn bst_paths() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 9]);
+    let paths: Vec<_> = t.paths().collect();
+    assert_eq!(
+        paths,
+        [vec![&5, &3, &1], vec![&5, &3, &4], vec![&5, &8, &9]]
+    );
+    let sums: Vec<i32> = t.paths().map(|path| path.into_iter().sum()).collect();
+    assert_eq!(sums, [9, 12, 22]);
+    assert_eq!(
+        ArenaTree::from_vec(vec![1]).paths().collect::<Vec<_>>(),
+        [vec![&1]]
+    );
+    assert_eq!(ArenaTree::<i32>::default().paths().count(), 0);
+}
 
-    let new_id = t.insert(0usize);
-    assert_eq!(new_id, left_id);
-    assert_eq!(t.size(), 2);
+#[test]
+fn bst_ancestors() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 9]);
+    let id = t.search_by(|val| 4.cmp(val)).unwrap();
+    let vals: Vec<_> = t.ancestors(id).map(|id| t.get(id).unwrap()).collect();
+    assert_eq!(vals, [&3, &5]);
+    assert_eq!(t.ancestors(id).count(), 2);
+    assert_eq!(t.ancestors(t.root().unwrap()).next(), None);
+}
 
-    println!("arena: {:?}", t);
+#[test]
+fn bst_subtree_iter() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 9, 7]);
+    let id = t.search_by(|val| 8.cmp(val)).unwrap();
+    assert!(t
+        .subtree_iter(id, &Traversal::LNR)
+        .copied()
+        .eq(vec![7, 8, 9]));
+    assert!(t
+        .subtree_iter(id, &Traversal::LRN)
+        .copied()
+        .eq(vec![7, 9, 8]));
+    let id = t.search_by(|val| 3.cmp(val)).unwrap();
+    assert!(t
+        .subtree_iter(id, &Traversal::BFS)
+        .copied()
+        .eq(vec![3, 1, 4]));
+    let root = t.root().unwrap();
+    assert!(t
+        .subtree_iter(root, &Traversal::NLR)
+        .eq(t.traversal(&Traversal::NLR)));
 }
 
 #[test]
-fn bst_insert_less() {
-    let mut t = ArenaTree::default();
-    let root_id = t.insert(10usize);
-    let left_id = t.insert(0usize);
-    assert_eq!(t.size(), 2);
-    assert_eq!(t.arena[left_id].parent.unwrap(), root_id);
+fn bst_boundary() {
+    let t = ArenaTree::from_vec(vec![20, 8, 22, 4, 12, 25, 10, 14]);
+    assert_eq!(t.boundary(), [&20, &8, &4, &10, &14, &25, &22]);
+    // Without a left subtree, the left edge is the root alone.
+    let t = ArenaTree::from_vec(vec![1, 5, 3, 7, 6]);
+    assert_eq!(t.boundary(), [&1, &3, &6, &7, &5]);
+    assert_eq!(ArenaTree::from_vec(vec![1]).boundary(), [&1]);
+    assert!(ArenaTree::<i32>::default().boundary().is_empty());
+}
 
-    assert_eq!(t.arena[0].left.unwrap(), left_id);
+#[test]
+fn bst_euler_tour() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+    let ids = t.traversal_ids(&Traversal::LNR);
+    let (one, two, three) = (ids[0], ids[1], ids[2]);
+    use EulerStep::*;
+    assert_eq!(
+        t.euler_tour(),
+        [
+            Enter(two),
+            Enter(one),
+            Exit(one),
+            Enter(three),
+            Exit(three),
+            Exit(two)
+        ]
+    );
 
-    println!("arena: {:?}", t);
+    let t = ArenaTree::from_vec((0..50).map(|val| val * 7 % 50).collect());
+    let tour = t.euler_tour();
+    assert_eq!(tour.len(), 100);
+    let enters: Vec<_> = tour
+        .iter()
+        .filter_map(|step| match step {
+            Enter(id) => Some(*id),
+            Exit(_) => None,
+        })
+        .collect();
+    assert_eq!(enters, t.traversal_ids(&Traversal::NLR));
+    assert!(ArenaTree::<i32>::default().euler_tour().is_empty());
 }
 
 #[test]
-fn bst_insert_greater() {
+fn bst_distance() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9, 6]);
+    assert_eq!(t.distance(&1, &4), Some(2));
+    assert_eq!(t.distance(&1, &6), Some(5));
+    assert_eq!(t.distance(&8, &6), Some(2));
+    assert_eq!(t.distance(&5, &9), Some(2));
+    assert_eq!(t.distance(&7, &7), Some(0));
+    assert_eq!(t.distance(&1, &2), None);
+    assert_eq!(ArenaTree::<i32>::default().distance(&1, &1), None);
+}
+
+#[test]
+fn bst_diameter() {
+    assert_eq!(ArenaTree::<i32>::default().diameter(), 0);
+    assert_eq!(ArenaTree::from_vec(vec![1]).diameter(), 0);
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9, 6]);
+    assert_eq!(t.diameter(), 5);
+    // The longest path doesn't have to go through the root.
+    let t = ArenaTree::from_vec(vec![1, 10, 5, 4, 3, 2, 7, 8, 9]);
+    assert_eq!(t.diameter(), 6);
+
     let mut t = ArenaTree::default();
-    let root_id = t.insert(0usize);
-    let left_id = t.insert(10usize);
-    assert_eq!(t.size(), 2);
-    assert_eq!(t.arena[left_id].parent.unwrap(), root_id);
+    let mut pos = None;
+    for val in 0..100_000 {
+        pos = Some((t.attach(pos, val), false));
+    }
+    assert_eq!(t.diameter(), 99_999);
+}
 
-    assert_eq!(t.arena[0].right.unwrap(), left_id);
+#[test]
+fn bst_width() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9, 6]);
+    assert_eq!(t.level_widths(), [1, 2, 4, 1]);
+    assert_eq!(t.width(), 4);
+    assert_eq!(t.level_widths().len(), t.height());
+    assert_eq!(ArenaTree::from_vec((0..5).collect()).width(), 1);
+    assert_eq!(ArenaTree::<i32>::default().width(), 0);
+}
 
-    println!("arena: {:?}", t);
+#[test]
+fn bst_traversal_iter() {
+    let t = ArenaTree::from_vec((0..100).map(|val| val * 37 % 100).collect());
+    for typ in [
+        Traversal::NLR,
+        Traversal::LNR,
+        Traversal::LRN,
+        Traversal::NRL,
+        Traversal::RNL,
+        Traversal::RLN,
+        Traversal::BFS,
+    ]
+    .iter()
+    {
+        assert!(t.traversal_iter(typ).eq(t.traversal(typ)));
+    }
+    let small: Vec<_> = t.traversal_iter(&Traversal::LNR).take(3).collect();
+    assert_eq!(small, [&0, &1, &2]);
+    let evens = t
+        .traversal_iter(&Traversal::BFS)
+        .filter(|val| *val % 2 == 0);
+    assert_eq!(evens.count(), 50);
+    assert_eq!(
+        ArenaTree::<i32>::default()
+            .traversal_iter(&Traversal::BFS)
+            .next(),
+        None
+    );
 }
 
 #[test]
-fn bst_traversal() {
-    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+fn bst_for_each_with() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4]);
+    let mut positions = std::collections::HashMap::new();
+    t.for_each_with(&Traversal::LNR, |idx, id, val| {
+        assert_eq!(t.get(id), Some(val));
+        positions.insert(*val, idx);
+    });
+    assert_eq!(positions[&1], 0);
+    assert_eq!(positions[&5], 3);
+    let mut seen = vec![];
+    t.for_each_with(&Traversal::BFS, |idx, _, val| seen.push((idx, *val)));
+    assert_eq!(seen, [(0, 5), (1, 3), (2, 8), (3, 1), (4, 4)]);
+}
 
-    println!("arena: {:?}", t);
+#[test]
+fn bst_traversal_pruned() {
+    let t = ArenaTree::from_vec(vec![50, 30, 70, 20, 40, 60, 80, 35, 45]);
+    let within: Vec<_> = t.traversal_within(&Traversal::NLR, 35..=60).collect();
+    assert_eq!(within, [&50, &40, &35, &45, &60]);
+    let within: Vec<_> = t.traversal_within(&Traversal::BFS, 36..).collect();
+    assert_eq!(within, [&50, &70, &40, &60, &80, &45]);
+    assert_eq!(t.traversal_within(&Traversal::LNR, 90..).count(), 0);
 
-    let testcases = vec![
-        (&Traversal::NLR, vec![2, 1, 3]),
-        (&Traversal::LNR, vec![1, 2, 3]),
-        (&Traversal::LRN, vec![1, 3, 2]),
-        (&Traversal::NRL, vec![2, 3, 1]),
-        (&Traversal::RNL, vec![3, 2, 1]),
-        (&Traversal::RLN, vec![3, 1, 2]),
-        (&Traversal::BFS, vec![2, 1, 3]),
-    ];
+    // Only the nodes on the way to the range are looked at.
+    let mut seen = 0;
+    let found: Vec<_> = t
+        .traversal_by(&Traversal::LNR, |val| {
+            seen += 1;
+            match *val {
+                40..=45 => Ordering::Equal,
+                val if val < 40 => Ordering::Greater,
+                _ => Ordering::Less,
+            }
+        })
+        .collect();
+    assert_eq!(found, [&40, &45]);
+    assert_eq!(seen, 5);
+}
 
-    for (mode, expect) in testcases.iter() {
-        println!("mode: {:?}, expect: {:?}", mode, expect);
-        assert_eq!(&t.traversal(mode), expect);
-    }
+#[test]
+fn bst_fold() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4]);
+    assert_eq!(t.fold(&Traversal::LNR, 0, |acc, val| acc + val), 21);
+    assert_eq!(
+        t.fold(&Traversal::BFS, i32::MAX, |acc, val| acc.min(*val)),
+        1
+    );
+    let joined = t.fold(&Traversal::NLR, String::new(), |acc, val| {
+        acc + &val.to_string()
+    });
+    assert_eq!(joined, "53148");
+    assert_eq!(
+        ArenaTree::<i32>::default().fold(&Traversal::LRN, 7, |acc, _| acc + 1),
+        7
+    );
 }
 
 #[test]
-fn bst_traversal_complex() {
-    let t = ArenaTree::from_vec(vec![5, 1, 2, 4, 3]);
+fn bst_any_all_find() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4]);
+    let mut seen = 0;
+    assert!(t.any(|val| {
+        seen += 1;
+        *val == 3
+    }));
+    assert_eq!(seen, 2);
+    assert!(!t.any(|val| *val > 10));
+    assert!(t.all(|val| *val > 0));
+    assert!(!t.all(|val| *val < 5));
+    assert_eq!(t.find(|val| val % 2 == 0), Some(&4));
+    assert_eq!(t.find(|val| *val > 10), None);
+    assert!(ArenaTree::<i32>::default().all(|_| false));
+}
 
-    println!("arena: {:?}", t);
+#[test]
+fn bst_by_level() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4, 9]);
+    let mut levels = t.by_level();
+    assert_eq!(levels.next(), Some(vec![&5]));
+    assert_eq!(levels.next(), Some(vec![&3, &8]));
+    // Breadth-limited search, the last level is never built.
+    let found = t.by_level().take(2).flatten().any(|val| *val == 1);
+    assert!(!found);
+    assert_eq!(t.by_level().count(), 3);
+    assert_eq!(ArenaTree::<i32>::default().by_level().next(), None);
+}
 
-    let testcases = vec![
-        (&Traversal::NLR, vec![5, 1, 2, 4, 3]),
-        (&Traversal::LNR, vec![1, 2, 3, 4, 5]),
-        (&Traversal::LRN, vec![3, 4, 2, 1, 5]),
-        (&Traversal::NRL, vec![5, 1, 2, 4, 3]),
-        (&Traversal::RNL, vec![5, 4, 3, 2, 1]),
-        (&Traversal::RLN, vec![3, 4, 2, 1, 5]),
-        (&Traversal::BFS, vec![5, 1, 2, 4, 3]),
-    ];
+#[cfg(any(debug_assertions, feature = "checked"))]
+#[test]
+#[should_panic(expected = "doesn't link back")]
+fn bst_debug_check_links() {
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8]);
+    let (three, eight) = (t.search(&3).unwrap(), t.search(&8).unwrap());
+    t.arena[three].set_parent(Some(eight));
+    t.delete(&8);
+}
 
-    for (mode, expect) in testcases.iter() {
-        println!("mode: {:?}, expect: {:?}", mode, expect);
-        assert_eq!(&t.traversal(mode), expect);
+#[cfg(any(debug_assertions, feature = "checked"))]
+#[test]
+fn bst_bfs_cycle_guard() {
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8]);
+    let (root, eight) = (t.root().unwrap(), t.search(&8).unwrap());
+    t.arena[eight].set_left(Some(root));
+    assert_eq!(t.traversal(&Traversal::BFS), [&5, &3, &8, &5]);
+}
+
+#[cfg(feature = "unsafe-fast")]
+#[test]
+#[allow(unsafe_code)]
+fn bst_get_unchecked() {
+    let mut t = ArenaTree::from_vec((0..200).collect());
+    t.delete(&5);
+    for id in t.traversal_ids(&Traversal::LNR) {
+        // SAFETY: the ids come from the tree itself.
+        let (val, meta) = unsafe { (t.get_unchecked(id), t.meta_unchecked(id)) };
+        assert_eq!(Some(val), t.get(id));
+        assert_eq!(meta, &());
     }
 }
 
 #[test]
-fn bst_delete_leaf() {
-    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
-    println!("arena: {:?}", t);
+fn bst_search_shared() {
+    let t = Arc::new(ArenaTree::from_vec(
+        (0..100).map(|val| val * 37 % 100).collect(),
+    ));
+    std::thread::scope(|s| {
+        for start in 0..4 {
+            let t = Arc::clone(&t);
+            s.spawn(move || {
+                for val in (start..100).step_by(4) {
+                    let id = t.search(&val).unwrap();
+                    assert_eq!(t.get(id), Some(&val));
+                    assert_eq!(t.search_parent(&val).is_none(), id == t.root().unwrap());
+                }
+            });
+        }
+    });
+}
 
-    assert_eq!(t.traversal(&Traversal::BFS), vec![4, 2, 6, 1, 3, 5, 7]);
+#[test]
+fn bst_btreeset_conversions() {
+    let set: BTreeSet<_> = (0..100).map(|val| val * 37 % 100).collect();
+    let t = ArenaTree::from(set.clone());
+    assert!(t.iter().eq(set.iter()));
+    assert!(t.is_balanced());
+    assert_eq!(BTreeSet::from(t), set);
 
-    assert_eq!(t.delete(1), true);
-    assert_eq!(t.traversal(&Traversal::BFS), vec![4, 2, 6, 3, 5, 7]);
-    assert_eq!(t.delete(1), false);
+    let t = ArenaTree::descending();
+    let t = (0..10).fold(t, |mut t, val| {
+        t.insert(val);
+        t
+    });
+    assert_eq!(BTreeSet::from(t), (0..10).collect());
 }
 
 #[test]
-fn bst_delete_node() {
-    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
-    println!("arena: {:?}", t);
+fn bst_take() {
+    let by_key = |(key, _): &(i32, &str)| *key;
+    let mut t = ArenaTree::with_key_fn(by_key);
+    for entry in [(2, "two"), (1, "one"), (3, "three")].iter().copied() {
+        t.insert(entry);
+    }
+    assert_eq!(t.take(&(3, "")), Some((3, "three")));
+    assert_eq!(t.take(&(3, "")), None);
+    assert_eq!(t.take_key(&1), Some((1, "one")));
+    assert!(t.iter().copied().eq(vec![(2, "two")]));
+}
 
-    assert_eq!(t.traversal(&Traversal::BFS), vec![4, 2, 6, 1, 3, 5, 7]);
+#[test]
+fn bst_delete_by_id() {
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8, 1, 4]);
+    let id = t.search(&3).unwrap();
+    let handle = t.handle(id).unwrap();
+    assert_eq!(t.delete_by_id(id), Some(3));
+    assert_eq!(t.delete_by_id(id), None);
+    assert!(t.iter().copied().eq(vec![1, 4, 5, 8]));
+    assert_eq!(t.validate(), Ok(()));
 
-    assert_eq!(t.delete(4), true);
-    assert_eq!(t.traversal(&Traversal::BFS), vec![5, 2, 6, 1, 3, 7]);
-    assert_eq!(t.delete(4), false);
+    // The slot is reused by 6, the stale handle doesn't remove it.
+    assert_eq!(t.insert(6), id);
+    assert_eq!(t.delete_by_handle(handle), None);
+    let handle = t.handle(id).unwrap();
+    assert_eq!(t.delete_by_handle(handle), Some(6));
+    assert_eq!(t.size(), 4);
 }
 
 #[test]
-fn bst_delete_node_2() {
-    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
-    let testcases = vec![
-        (4, vec![5, 2, 6, 1, 3, 7]),
-        (5, vec![6, 2, 7, 1, 3]),
-        (6, vec![7, 2, 1, 3]),
-        (7, vec![2, 1, 3]),
-        (2, vec![3, 1]),
-        (3, vec![1]),
-        (1, vec![]),
-    ];
-    for (val, expect) in testcases.iter() {
-        println!("delete {:?}", val);
-        assert_eq!(t.delete(*val), true);
-        assert_eq!(t.traversal(&Traversal::BFS), *expect);
-    }
+fn bst_try_insert() {
+    let mut t = ArenaTree::default();
+    let id = t.try_insert(5).unwrap();
+    assert!(t.try_insert(3).is_ok());
+    assert_eq!(t.try_insert(5), Err(InsertError::AlreadyPresent(id)));
+    assert_eq!(t.size(), 2);
+    assert_eq!(
+        t.try_insert(5).unwrap_err().to_string(),
+        format!("value already present at node #{}", id)
+    );
 }
 
 #[test]
-fn bst_most_left() {
-    let t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5, 7]);
-    println!("arena: {:?}", t);
+fn bst_max_len() {
+    let mut t = ArenaTree::with_max_len(3);
+    assert_eq!(t.max_len(), Some(3));
+    let capacity = t.capacity();
+    assert!(capacity >= 3);
+    for val in [5, 3, 8].iter() {
+        t.try_insert(*val).unwrap();
+    }
+    assert!(t.is_full());
+    assert_eq!(t.try_insert(1), Err(InsertError::Full));
+    assert!(matches!(
+        t.try_insert(5),
+        Err(InsertError::AlreadyPresent(_))
+    ));
+    // Inserting a present value doesn't need room.
+    assert_eq!(t.insert(3), t.search(&3).unwrap());
+    assert_eq!(
+        t.try_get_or_insert_with(&8, |val| *val),
+        Ok(t.search(&8).unwrap())
+    );
+    assert_eq!(
+        t.try_get_or_insert_with(&9, |_| unreachable!()),
+        Err(InsertError::Full)
+    );
+    assert!(t.delete(&5));
+    assert!(t.try_insert(1).is_ok());
+    assert_eq!((t.size(), t.capacity()), (3, capacity));
+    assert!(!ArenaTree::<i32>::default().is_full());
+}
 
-    assert_eq!(t.most_left(0), 3);
-    assert_eq!(t.most_left(1), 3);
-    assert_eq!(t.most_left(2), 5);
+#[test]
+#[should_panic(expected = "tree is full")]
+fn bst_max_len_insert_panics() {
+    let mut t = ArenaTree::with_max_len(1);
+    t.insert(1);
+    t.insert(2);
 }
@@ -0,0 +1,436 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::avl::AvlBalance;
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::{Add, Bound, Deref, RangeBounds};
+
+/// Monoid summarizing the values of a subtree.
+/// `combine` must be associative, with `Default::default()` as its identity.
+pub trait Aggregate<T> {
+    type Value: Clone + Default;
+
+    fn lift(&self, val: &T) -> Self::Value;
+
+    fn combine(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+/// Counts the values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Size;
+
+impl<T> Aggregate<T> for Size {
+    type Value = usize;
+
+    fn lift(&self, _: &T) -> usize {
+        1
+    }
+
+    fn combine(&self, a: &usize, b: &usize) -> usize {
+        a + b
+    }
+}
+
+/// Adds up the values, starting from `T::default()`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sum;
+
+impl<T> Aggregate<T> for Sum
+where
+    T: Clone + Default + Add<Output = T>,
+{
+    type Value = T;
+
+    fn lift(&self, val: &T) -> T {
+        val.clone()
+    }
+
+    fn combine(&self, a: &T, b: &T) -> T {
+        a.clone() + b.clone()
+    }
+}
+
+/// Smallest value, by `Ord` of the values rather than the tree comparator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Min;
+
+impl<T> Aggregate<T> for Min
+where
+    T: Clone + Ord,
+{
+    type Value = Option<T>;
+
+    fn lift(&self, val: &T) -> Option<T> {
+        Some(val.clone())
+    }
+
+    fn combine(&self, a: &Option<T>, b: &Option<T>) -> Option<T> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b).clone()),
+            _ => a.clone().or_else(|| b.clone()),
+        }
+    }
+}
+
+/// Largest value, by `Ord` of the values rather than the tree comparator.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Max;
+
+impl<T> Aggregate<T> for Max
+where
+    T: Clone + Ord,
+{
+    type Value = Option<T>;
+
+    fn lift(&self, val: &T) -> Option<T> {
+        Some(val.clone())
+    }
+
+    fn combine(&self, a: &Option<T>, b: &Option<T>) -> Option<T> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(a.max(b).clone()),
+            _ => a.clone().or_else(|| b.clone()),
+        }
+    }
+}
+
+/// Metadata of an `AugmentedTree` node.
+#[derive(Debug, Clone, Default)]
+pub struct Summary<V> {
+    /// Height of the subtree, a leaf being 1.
+    pub height: u32,
+    /// Aggregate of the values of the subtree.
+    pub value: V,
+}
+
+/// Tree keeping an aggregate of every subtree, so the aggregate of any range of values
+/// is answered in O(log n) by `aggregate_of_range`.
+///
+/// The tree is balanced like `AvlArenaTree`, and the aggregates are kept up to date
+/// through insertions, deletions and rotations.
+/// See the balanced variants of `ArenaTree` for its API.
+#[derive(Debug, Clone)]
+pub struct AugmentedTree<T, A, C = Natural>
+where
+    A: Aggregate<T>,
+{
+    tree: ArenaTree<T, C, Summary<A::Value>>,
+    agg: A,
+}
+
+impl<T, A> Default for AugmentedTree<T, A>
+where
+    T: Ord,
+    A: Aggregate<T> + Default,
+{
+    fn default() -> Self {
+        Self::new(A::default())
+    }
+}
+
+impl<T, A> AugmentedTree<T, A>
+where
+    T: Ord,
+    A: Aggregate<T>,
+{
+    pub fn new(agg: A) -> Self {
+        Self::with_comparator(Natural, agg)
+    }
+}
+
+/// Tree keeping the sum of every subtree, the reference use of `AugmentedTree`.
+pub type SumTree<T, C = Natural> = AugmentedTree<T, Sum, C>;
+
+impl<T, C> SumTree<T, C>
+where
+    T: Clone + Default + Add<Output = T>,
+    C: Comparator<T>,
+{
+    /// Sum of all values.
+    pub fn sum(&self) -> T {
+        self.aggregate()
+    }
+
+    /// Sum of the values within `range`, in O(log n).
+    pub fn sum_range<Q, R>(&self, range: R) -> T
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.aggregate_of_range(range)
+    }
+}
+
+impl<T, A, C> Deref for AugmentedTree<T, A, C>
+where
+    A: Aggregate<T>,
+{
+    type Target = ArenaTree<T, C, Summary<A::Value>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, A, C> AugmentedTree<T, A, C>
+where
+    A: Aggregate<T>,
+{
+    pub fn with_comparator(cmp: C, agg: A) -> Self {
+        Self {
+            tree: ArenaTree::with_comparator_and_meta(cmp),
+            agg,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    /// Aggregate of all values.
+    pub fn aggregate(&self) -> A::Value {
+        self.value_of(self.tree.root())
+    }
+
+    fn value_of(&self, id: Option<NodeId>) -> A::Value {
+        id.and_then(|id| self.tree.meta(id))
+            .map(|summary| summary.value.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl<T, A, C> AvlBalance<T, C, Summary<A::Value>> for AugmentedTree<T, A, C>
+where
+    A: Aggregate<T>,
+{
+    fn tree(&self) -> &ArenaTree<T, C, Summary<A::Value>> {
+        &self.tree
+    }
+
+    fn tree_mut(&mut self) -> &mut ArenaTree<T, C, Summary<A::Value>> {
+        &mut self.tree
+    }
+
+    fn height_of(&self, id: Option<NodeId>) -> u32 {
+        id.and_then(|id| self.tree.meta(id))
+            .map_or(0, |summary| summary.height)
+    }
+
+    fn update(&mut self, id: NodeId) {
+        let (left, right) = (self.tree.left_of(id), self.tree.right_of(id));
+        let val = self.agg.lift(self.tree.get(id).expect("linked node"));
+        let value = self.agg.combine(
+            &self.agg.combine(&self.value_of(left), &val),
+            &self.value_of(right),
+        );
+        let height = 1 + self.height_of(left).max(self.height_of(right));
+        self.tree.set_meta(id, Summary { height, value });
+    }
+}
+
+impl<T, A, C> AugmentedTree<T, A, C>
+where
+    A: Aggregate<T>,
+    C: Comparator<T>,
+{
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.tree.search(val)
+    }
+
+    /// Inserts `val`, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let size = self.tree.size();
+        let id = self.tree.insert(val);
+        if self.tree.size() != size {
+            self.rebalance(Some(id));
+        }
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Removes the value equal to `val`, returning it.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.tree.search(val)?;
+        let (val, start) = self.tree.unlink(id);
+        self.rebalance(start);
+        Some(val)
+    }
+
+    /// Aggregate of the values within `range`, in O(log n).
+    pub fn aggregate_of_range<Q, R>(&self, range: R) -> A::Value
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range_value(self.tree.root(), range.start_bound(), range.end_bound())
+    }
+
+    fn below<Q>(&self, val: &T, start: Bound<&Q>) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match start {
+            Bound::Unbounded => false,
+            Bound::Included(start) => {
+                self.tree.comparator().compare(val.borrow(), start) == Ordering::Less
+            }
+            Bound::Excluded(start) => {
+                self.tree.comparator().compare(val.borrow(), start) != Ordering::Greater
+            }
+        }
+    }
+
+    fn above<Q>(&self, val: &T, end: Bound<&Q>) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match end {
+            Bound::Unbounded => false,
+            Bound::Included(end) => {
+                self.tree.comparator().compare(val.borrow(), end) == Ordering::Greater
+            }
+            Bound::Excluded(end) => {
+                self.tree.comparator().compare(val.borrow(), end) != Ordering::Less
+            }
+        }
+    }
+
+    /// Aggregate of the subtree values within both bounds.
+    fn range_value<Q>(&self, id: Option<NodeId>, start: Bound<&Q>, end: Bound<&Q>) -> A::Value
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = match (id, start, end) {
+            (None, _, _) => return A::Value::default(),
+            (id, Bound::Unbounded, Bound::Unbounded) => return self.value_of(id),
+            (Some(id), _, _) => id,
+        };
+        let val = self.tree.get(id).expect("linked node");
+        if self.below(val, start) {
+            self.range_value(self.tree.right_of(id), start, end)
+        } else if self.above(val, end) {
+            self.range_value(self.tree.left_of(id), start, end)
+        } else {
+            // The split point: everything left is below `end`, everything right above `start`.
+            let left = self.range_value(self.tree.left_of(id), start, Bound::Unbounded);
+            let right = self.range_value(self.tree.right_of(id), Bound::Unbounded, end);
+            self.agg
+                .combine(&self.agg.combine(&left, &self.agg.lift(val)), &right)
+        }
+    }
+}
+
+#[test]
+fn augment_aggregates() {
+    let mut t = AugmentedTree::new(Sum);
+    for val in 1..=100 {
+        t.insert(val);
+    }
+    assert_eq!(t.aggregate(), 5050);
+    assert_eq!(t.aggregate_of_range(10..20), (10..20).sum::<i32>());
+    assert_eq!(t.aggregate_of_range(..=10), 55);
+    assert_eq!(t.aggregate_of_range(95..), 585);
+    assert_eq!(t.aggregate_of_range(200..300), 0);
+    for val in (1..=100).filter(|val| val % 2 == 0) {
+        assert!(t.delete(&val));
+    }
+    assert_eq!(t.aggregate(), 2500);
+    assert_eq!(t.aggregate_of_range(10..=20), 75);
+    assert!(t.height() <= 8);
+
+    let mut t = AugmentedTree::new(Max);
+    for val in [5, 3, 8, 1, 4].iter() {
+        t.insert(*val);
+    }
+    assert_eq!(t.aggregate_of_range(2..5), Some(4));
+    assert_eq!(t.aggregate_of_range(6..8), None);
+}
+
+#[test]
+fn augment_custom_aggregate() {
+    /// Length of the longest string.
+    struct Longest;
+
+    impl Aggregate<String> for Longest {
+        type Value = usize;
+
+        fn lift(&self, val: &String) -> usize {
+            val.len()
+        }
+
+        fn combine(&self, a: &usize, b: &usize) -> usize {
+            *a.max(b)
+        }
+    }
+
+    let mut t = AugmentedTree::new(Longest);
+    for word in ["kiwi", "apple", "banana", "fig"].iter() {
+        t.insert(word.to_string());
+    }
+    assert_eq!(t.aggregate(), 6);
+    assert_eq!(
+        t.aggregate_of_range::<str, _>((Bound::Included("c"), Bound::Unbounded)),
+        4
+    );
+    assert_eq!(t.meta(t.root().unwrap()).unwrap().height, 3);
+}
+
+#[test]
+fn augment_sum_tree() {
+    let mut t = SumTree::default();
+    for val in [5, 3, 8, 1, 4, 7, 9, 2, 6].iter() {
+        t.insert(*val);
+    }
+    assert_eq!(t.sum(), 45);
+    assert_eq!(t.sum_range(3..=7), 25);
+    assert_eq!(t.sum_range(3..7), 18);
+    assert_eq!(t.sum_range(10..), 0);
+    assert!(t.delete(&5));
+    assert_eq!(t.sum_range(3..=7), 20);
+    assert_eq!(SumTree::<u64>::default().sum(), 0);
+}
+
+#[test]
+fn augment_take_and_clear() {
+    let mut t = SumTree::default();
+    for val in 1..=10 {
+        t.insert(val);
+    }
+    assert_eq!(t.take(&4), Some(4));
+    assert_eq!(t.take(&4), None);
+    assert_eq!(t.sum(), 51);
+    assert_eq!(t.sum_range(3..=5), 8);
+    t.clear();
+    assert_eq!(t.sum(), 0);
+    t.insert(7);
+    assert_eq!(t.sum(), 7);
+}
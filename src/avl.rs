@@ -0,0 +1,482 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::{Bound, Deref, RangeBounds};
+
+/// AVL balanced variant of `ArenaTree`.
+///
+/// Every node keeps the height of its subtree as metadata, and the heights of
+/// sibling subtrees never differ by more than one, so the tree height stays O(log n)
+/// even for sorted input. See the balanced variants of `ArenaTree` for its API.
+#[derive(Debug, Clone)]
+pub struct AvlArenaTree<T, C = Natural> {
+    tree: ArenaTree<T, C, u32>,
+}
+
+impl<T> Default for AvlArenaTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> AvlArenaTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+/// AVL rebalancing of an `ArenaTree` keeping the height of every subtree
+/// in its metadata, along with anything else `update` derives from the children.
+pub(crate) trait AvlBalance<T, C, M> {
+    fn tree(&self) -> &ArenaTree<T, C, M>;
+
+    fn tree_mut(&mut self) -> &mut ArenaTree<T, C, M>;
+
+    /// Height of the subtree, 0 if `None`.
+    fn height_of(&self, id: Option<NodeId>) -> u32;
+
+    /// Recomputes the metadata of `id` from its children.
+    fn update(&mut self, id: NodeId);
+
+    /// Left height minus right height.
+    fn balance_of(&self, id: NodeId) -> i64 {
+        i64::from(self.height_of(self.tree().left_of(id)))
+            - i64::from(self.height_of(self.tree().right_of(id)))
+    }
+
+    fn rotate_left(&mut self, id: NodeId) -> NodeId {
+        let top = self.tree_mut().rotate_left(id);
+        self.update(id);
+        self.update(top);
+        top
+    }
+
+    fn rotate_right(&mut self, id: NodeId) -> NodeId {
+        let top = self.tree_mut().rotate_right(id);
+        self.update(id);
+        self.update(top);
+        top
+    }
+
+    /// Fixes the metadata from `start` up to the root, rotating where unbalanced.
+    fn rebalance(&mut self, start: Option<NodeId>) {
+        let mut cur = start;
+        while let Some(mut id) = cur {
+            self.update(id);
+            match self.balance_of(id) {
+                2 => {
+                    let left_id = self.tree().left_of(id).expect("left heavy");
+                    if self.balance_of(left_id) < 0 {
+                        self.rotate_left(left_id);
+                    }
+                    id = self.rotate_right(id);
+                }
+                -2 => {
+                    let right_id = self.tree().right_of(id).expect("right heavy");
+                    if self.balance_of(right_id) > 0 {
+                        self.rotate_right(right_id);
+                    }
+                    id = self.rotate_left(id);
+                }
+                _ => {}
+            }
+            cur = self.tree().parent_of(id);
+        }
+    }
+}
+
+impl<T, C> AvlBalance<T, C, u32> for AvlArenaTree<T, C> {
+    fn tree(&self) -> &ArenaTree<T, C, u32> {
+        &self.tree
+    }
+
+    fn tree_mut(&mut self) -> &mut ArenaTree<T, C, u32> {
+        &mut self.tree
+    }
+
+    fn height_of(&self, id: Option<NodeId>) -> u32 {
+        id.and_then(|id| self.tree.meta(id)).copied().unwrap_or(0)
+    }
+
+    fn update(&mut self, id: NodeId) {
+        let left = self.height_of(self.tree.left_of(id));
+        let right = self.height_of(self.tree.right_of(id));
+        self.tree.set_meta(id, 1 + left.max(right));
+    }
+}
+
+impl<T, C> Deref for AvlArenaTree<T, C> {
+    type Target = ArenaTree<T, C, u32>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, C> AvlArenaTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            tree: ArenaTree::with_comparator_and_meta(cmp),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    /// Joins the detached subtrees `left` and `right` below the detached node `pivot`,
+    /// every value of `left` being smaller than the pivot and every value of `right` greater.
+    /// Returns the root of the joined subtree.
+    ///
+    /// The pivot goes down the spine of the higher subtree until the subtree it takes over
+    /// is about as high as the other one, so only that path is rebalanced.
+    fn join_at(&mut self, left: Option<NodeId>, pivot: NodeId, right: Option<NodeId>) -> NodeId {
+        let (left_height, right_height) = (self.height_of(left), self.height_of(right));
+        let is_higher = |t: &Self, id: &NodeId, other: u32| t.height_of(Some(*id)) > other + 1;
+        let (parent, left, right) = if left_height > right_height + 1 {
+            let mut parent = left.expect("higher subtree");
+            while let Some(id) = self
+                .tree
+                .right_of(parent)
+                .filter(|id| is_higher(self, id, right_height))
+            {
+                parent = id;
+            }
+            (Some((parent, false)), self.tree.right_of(parent), right)
+        } else if right_height > left_height + 1 {
+            let mut parent = right.expect("higher subtree");
+            while let Some(id) = self
+                .tree
+                .left_of(parent)
+                .filter(|id| is_higher(self, id, left_height))
+            {
+                parent = id;
+            }
+            (Some((parent, true)), left, self.tree.left_of(parent))
+        } else {
+            (None, left, right)
+        };
+        self.tree.link(pivot, true, left);
+        self.tree.link(pivot, false, right);
+        if let Some((parent, is_left)) = parent {
+            self.tree.link(parent, is_left, Some(pivot));
+        }
+        self.rebalance(Some(pivot));
+        let mut top = pivot;
+        while let Some(id) = self.tree.parent_of(top) {
+            top = id;
+        }
+        top
+    }
+
+    /// Splits the detached subtree of `id` into the values matching `goes_left`
+    /// and the others, `goes_left` matching a prefix of the values in order.
+    fn split_at<F>(&mut self, id: Option<NodeId>, goes_left: &F) -> (Option<NodeId>, Option<NodeId>)
+    where
+        F: Fn(&C, &T) -> bool,
+    {
+        let id = match id {
+            Some(id) => id,
+            None => return (None, None),
+        };
+        let (left, right) = self.tree.detach_children(id);
+        let val = self.tree.get(id).expect("linked node");
+        if goes_left(self.tree.comparator(), val) {
+            let (low, high) = self.split_at(right, goes_left);
+            (Some(self.join_at(left, id, low)), high)
+        } else {
+            let (low, high) = self.split_at(left, goes_left);
+            (low, Some(self.join_at(high, id, right)))
+        }
+    }
+
+    /// Removes the largest value of the detached subtree of `id`,
+    /// returning the subtree left and the detached node of the value.
+    fn pop_max_at(&mut self, id: NodeId) -> (Option<NodeId>, NodeId) {
+        let (left, right) = self.tree.detach_children(id);
+        match right {
+            None => (left, id),
+            Some(right) => {
+                let (rest, max) = self.pop_max_at(right);
+                (Some(self.join_at(left, id, rest)), max)
+            }
+        }
+    }
+
+    /// Joins two detached subtrees, every value of `left` being smaller than every value of `right`.
+    fn join2_at(&mut self, left: Option<NodeId>, right: Option<NodeId>) -> Option<NodeId> {
+        match left {
+            None => right,
+            Some(left) => {
+                let (rest, max) = self.pop_max_at(left);
+                Some(self.join_at(rest, max, right))
+            }
+        }
+    }
+}
+
+impl<T, C> AvlArenaTree<T, C>
+where
+    C: Comparator<T>,
+{
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.tree.search(val)
+    }
+
+    /// Inserts `val` and rebalances, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let size = self.tree.size();
+        let id = self.tree.insert(val);
+        if self.tree.size() != size {
+            self.rebalance(Some(id));
+        }
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Removes the value equal to `val` and rebalances, returning it.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.tree.search(val)?;
+        let (val, start) = self.tree.unlink(id);
+        self.rebalance(start);
+        Some(val)
+    }
+
+    /// Removes the values within `range`, returning them in order.
+    ///
+    /// The range is cut out of the tree by splitting it at both bounds,
+    /// and the parts around it are joined back, in O(log n) plus the removed values.
+    pub fn remove_range<Q, R>(&mut self, range: R) -> Vec<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let (start, end) = (range.start_bound(), range.end_bound());
+        let below_start = |cmp: &C, val: &T| match start {
+            Bound::Unbounded => false,
+            Bound::Included(start) => cmp.compare(val.borrow(), start) == Ordering::Less,
+            Bound::Excluded(start) => cmp.compare(val.borrow(), start) != Ordering::Greater,
+        };
+        let before_end = |cmp: &C, val: &T| match end {
+            Bound::Unbounded => true,
+            Bound::Included(end) => cmp.compare(val.borrow(), end) != Ordering::Greater,
+            Bound::Excluded(end) => cmp.compare(val.borrow(), end) == Ordering::Less,
+        };
+        let (low, rest) = self.split_at(self.tree.root(), &below_start);
+        let (within, high) = self.split_at(rest, &before_end);
+        let removed = within.map_or_else(Vec::new, |id| self.tree.take_subtree(id));
+        if let Some(root) = self.join2_at(low, high) {
+            self.tree.set_root(root);
+        }
+        removed
+    }
+
+    /// Joins two trees around `pivot`, every value of `left` being smaller than `pivot`
+    /// and every value of `right` greater, keeping the comparator of the larger tree.
+    ///
+    /// Splicing takes O(log n), but the nodes of the smaller tree are first moved
+    /// into the arena of the larger one, which invalidates their ids.
+    /// Panics if the values are out of order.
+    pub fn join(left: Self, pivot: T, right: Self) -> Self {
+        let below = left.root().is_none_or(|root| {
+            let max = left.get(left.most_right(root)).expect("linked node");
+            left.comparator().compare(max, &pivot) == Ordering::Less
+        });
+        let above = right.root().is_none_or(|root| {
+            let min = right.get(right.most_left(root)).expect("linked node");
+            right.comparator().compare(&pivot, min) == Ordering::Less
+        });
+        assert!(below && above, "values out of order");
+        let (mut t, left, right) = if left.size() >= right.size() {
+            let mut t = left;
+            let right = t.tree.adopt(right.tree);
+            let left = t.root();
+            (t, left, right)
+        } else {
+            let mut t = right;
+            let left = t.tree.adopt(left.tree);
+            let right = t.root();
+            (t, left, right)
+        };
+        let pivot = t.tree.node(pivot);
+        let root = t.join_at(left, pivot, right);
+        t.tree.set_root(root);
+        t
+    }
+
+    /// Joins two trees, every value of `left` being smaller than every value of `right`,
+    /// using the largest value of `left` as the pivot.
+    /// Panics if the values are out of order.
+    pub fn join2(mut left: Self, right: Self) -> Self {
+        let root = match left.root() {
+            Some(root) => root,
+            None => return right,
+        };
+        let max = left.tree.most_right(root);
+        let (pivot, start) = left.tree.unlink(max);
+        left.rebalance(start);
+        Self::join(left, pivot, right)
+    }
+}
+
+#[cfg(test)]
+fn avl_check<T, C>(t: &AvlArenaTree<T, C>, id: Option<NodeId>) -> u32 {
+    match id {
+        None => 0,
+        Some(id) => {
+            let left = avl_check(t, t.left_of(id));
+            let right = avl_check(t, t.right_of(id));
+            assert!((i64::from(left) - i64::from(right)).abs() <= 1);
+            assert_eq!(t.meta(id), Some(&(1 + left.max(right))));
+            1 + left.max(right)
+        }
+    }
+}
+
+#[test]
+fn avl_sorted_insertions_stay_logarithmic() {
+    let t = AvlArenaTree::from_vec((0..1000).collect());
+    assert_eq!(t.size(), 1000);
+    // An AVL tree of height h has at least fib(h + 2) - 1 nodes.
+    assert!(t.height() <= 14);
+    avl_check(&t, t.root());
+    assert!(t.iter().copied().eq(0..1000));
+}
+
+#[test]
+fn avl_delete_rebalances() {
+    let mut t = AvlArenaTree::new();
+    let mut x: u64 = 42;
+    for _ in 0..500 {
+        x = x
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        t.insert((x >> 33) % 1000);
+    }
+    let mut expected: Vec<_> = t.iter().copied().collect();
+    for val in (0..1000).step_by(3) {
+        assert_eq!(t.delete(&val), expected.contains(&val));
+        avl_check(&t, t.root());
+    }
+    expected.retain(|val| val % 3 != 0);
+    assert!(t.iter().eq(expected.iter()));
+
+    let id = t.search(&expected[0]).unwrap();
+    assert_eq!(t.get(id), Some(&expected[0]));
+    assert_eq!(t.parent_of(t.root().unwrap()), None);
+}
+
+#[test]
+fn avl_join() {
+    for (left, right) in [(0, 0), (0, 5), (1, 300), (300, 1), (40, 60), (500, 7)].iter() {
+        let low = AvlArenaTree::from_vec((0..*left).collect());
+        let high = AvlArenaTree::from_vec((left + 1..left + 1 + right).collect());
+        let t = AvlArenaTree::join(low, *left, high);
+        avl_check(&t, t.root());
+        assert_eq!(t.size(), left + right + 1);
+        assert!(t.iter().copied().eq(0..left + right + 1));
+        assert_eq!(t.parent_of(t.root().unwrap()), None);
+    }
+}
+
+#[test]
+fn avl_join2() {
+    let low = AvlArenaTree::from_vec((0..100).collect());
+    let high = AvlArenaTree::from_vec((100..110).collect());
+    let mut t = AvlArenaTree::join2(low, high);
+    avl_check(&t, t.root());
+    assert!(t.iter().copied().eq(0..110));
+    assert!(t.delete(&50));
+    t.insert(200);
+    avl_check(&t, t.root());
+
+    let t = AvlArenaTree::join2(AvlArenaTree::new(), t);
+    assert_eq!(t.size(), 110);
+    let t = AvlArenaTree::join2(t, AvlArenaTree::new());
+    avl_check(&t, t.root());
+    assert_eq!(t.size(), 110);
+}
+
+#[test]
+fn avl_remove_range() {
+    let mut t = AvlArenaTree::from_vec((0..200).map(|val| val * 7 % 200).collect());
+    assert!(t.remove_range(500..600).is_empty());
+    assert_eq!(t.remove_range(20..30), (20..30).collect::<Vec<_>>());
+    assert_eq!(t.remove_range(..=5), (0..=5).collect::<Vec<_>>());
+    assert_eq!(
+        t.remove_range((Bound::Excluded(150), Bound::Unbounded)),
+        (151..200).collect::<Vec<_>>()
+    );
+    avl_check(&t, t.root());
+    assert!(t.iter().copied().eq((6..20).chain(30..=150)));
+    assert_eq!(t.size(), 14 + 121);
+    t.insert(25);
+    assert!(t.search(&25).is_some());
+
+    assert_eq!(t.remove_range(..).len(), 136);
+    assert_eq!((t.size(), t.root()), (0, None));
+}
+
+#[test]
+#[should_panic(expected = "values out of order")]
+fn avl_join_out_of_order() {
+    let low = AvlArenaTree::from_vec((0..10).collect());
+    let high = AvlArenaTree::from_vec((5..20).collect());
+    AvlArenaTree::join(low, 30, high);
+}
+
+#[test]
+#[should_panic(expected = "values out of order")]
+fn avl_join2_overlapping() {
+    let low = AvlArenaTree::from_vec((0..10).collect());
+    let high = AvlArenaTree::from_vec((9..20).collect());
+    AvlArenaTree::join2(low, high);
+}
+
+#[test]
+fn avl_take_and_clear() {
+    let mut t = AvlArenaTree::from_vec((0..100).collect());
+    for val in (0..100).step_by(2) {
+        assert_eq!(t.take(&val), Some(val));
+        avl_check(&t, t.root());
+    }
+    assert_eq!(t.take(&0), None);
+    assert_eq!(t.size(), 50);
+    t.clear();
+    assert_eq!((t.size(), t.root()), (0, None));
+    t.insert(1);
+    avl_check(&t, t.root());
+}
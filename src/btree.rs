@@ -0,0 +1,575 @@
+use crate::arena::NodeId;
+use crate::compare::{Comparator, Natural};
+use crate::slots::Slots;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+/// Fanout of `BTree::new`.
+pub const DEFAULT_FANOUT: usize = 16;
+
+#[derive(Debug)]
+struct BNode<T> {
+    /// Sorted values, `fanout - 1` at most.
+    vals: Vec<T>,
+    /// One more than `vals`, or empty for a leaf.
+    children: Vec<NodeId>,
+}
+
+impl<T> BNode<T> {
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+enum Insertion<T> {
+    Present,
+    Done,
+    /// The node overflowed and was split, the median and the new right node
+    /// go into the parent.
+    Split(T, NodeId),
+}
+
+/// B-tree storing many values per node, for read-heavy workloads
+/// where chasing the links of a binary tree thrashes the cache.
+///
+/// Every node but the root holds between `ceil(fanout / 2) - 1` and `fanout - 1` values,
+/// and all leaves are at the same depth, so the height stays within `log(n) / log(fanout / 2)`.
+/// Nodes live in an arena like the nodes of `ArenaTree`.
+#[derive(Debug)]
+pub struct BTree<T, C = Natural> {
+    arena: Slots<BNode<T>>,
+    root: Option<NodeId>,
+    len: usize,
+    fanout: usize,
+    cmp: C,
+}
+
+impl<T> Default for BTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> BTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panics if `fanout`, the most children a node has, is below 3.
+    pub fn with_fanout(fanout: usize) -> Self {
+        Self::with_comparator_and_fanout(Natural, fanout)
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+/// In-order iterator over the values of a `BTree`.
+pub struct Iter<'a, T, C = Natural> {
+    tree: &'a BTree<T, C>,
+    /// Nodes from the root down, with the index of the next value of each.
+    stack: Vec<(NodeId, usize)>,
+    /// Position of the first value past the end of the iteration.
+    stop: Option<(NodeId, usize)>,
+}
+
+impl<'a, T, C> Iter<'a, T, C> {
+    fn push_left(&mut self, mut id: NodeId) {
+        loop {
+            self.stack.push((id, 0));
+            match self.tree.arena[id].children.first() {
+                Some(&child) => id = child,
+                None => break,
+            }
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = self.tree;
+        loop {
+            let (id, idx) = self.stack.last_mut()?;
+            let node = &tree.arena[*id];
+            if *idx < node.vals.len() {
+                if self.stop == Some((*id, *idx)) {
+                    self.stack.clear();
+                    return None;
+                }
+                let val = &node.vals[*idx];
+                *idx += 1;
+                if !node.is_leaf() {
+                    let child = node.children[*idx];
+                    self.push_left(child);
+                }
+                return Some(val);
+            }
+            self.stack.pop();
+        }
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a BTree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C> BTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_comparator_and_fanout(cmp, DEFAULT_FANOUT)
+    }
+
+    pub fn with_comparator_and_fanout(cmp: C, fanout: usize) -> Self {
+        assert!(fanout >= 3, "fanout below 3");
+        Self {
+            arena: Slots::default(),
+            root: None,
+            len: 0,
+            fanout,
+            cmp,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn fanout(&self) -> usize {
+        self.fanout
+    }
+
+    /// Number of levels of nodes.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut cur = self.root;
+        while let Some(id) = cur {
+            height += 1;
+            cur = self.arena[id].children.first().copied();
+        }
+        height
+    }
+
+    pub fn clear(&mut self) {
+        self.arena.clear();
+        self.root = None;
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, C> {
+        let mut iter = Iter {
+            tree: self,
+            stack: vec![],
+            stop: None,
+        };
+        if let Some(root) = self.root {
+            iter.push_left(root);
+        }
+        iter
+    }
+
+    /// Path to the first value for which `before` doesn't hold, `before` holding
+    /// up to some value, with the index of the next value of every node on the way.
+    fn seek<F>(&self, before: F) -> Vec<(NodeId, usize)>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut stack = vec![];
+        let mut cur = self.root;
+        while let Some(id) = cur {
+            let node = &self.arena[id];
+            let idx = node.vals.partition_point(|val| before(val));
+            stack.push((id, idx));
+            cur = node.children.get(idx).copied();
+        }
+        stack
+    }
+
+    /// Position of the next value along a path from `seek`.
+    fn next_position(&self, stack: &[(NodeId, usize)]) -> Option<(NodeId, usize)> {
+        stack
+            .iter()
+            .rev()
+            .find(|(id, idx)| *idx < self.arena[*id].vals.len())
+            .copied()
+    }
+
+    /// Fewest values a node other than the root holds.
+    fn min_vals(&self) -> usize {
+        self.fanout.div_ceil(2) - 1
+    }
+
+    /// Moves the median of an overflowing node and the values above it out to a new node.
+    fn split(&mut self, id: NodeId) -> Insertion<T> {
+        let node = &mut self.arena[id];
+        let mid = node.vals.len() / 2;
+        let vals = node.vals.split_off(mid + 1);
+        let median = node.vals.pop().expect("overflowing node");
+        let children = if node.is_leaf() {
+            vec![]
+        } else {
+            node.children.split_off(mid + 1)
+        };
+        Insertion::Split(median, self.arena.push(BNode { vals, children }))
+    }
+
+    /// Removes the largest value of the subtree.
+    fn pop_max(&mut self, id: NodeId) -> T {
+        let node = &mut self.arena[id];
+        if node.is_leaf() {
+            return node.vals.pop().expect("non-empty node");
+        }
+        let idx = node.children.len() - 1;
+        let child = node.children[idx];
+        let val = self.pop_max(child);
+        self.fix_child(id, idx);
+        val
+    }
+
+    /// Refills the `idx`-th child of `id` if it went below `min_vals`,
+    /// borrowing a value from a sibling or merging with one.
+    fn fix_child(&mut self, id: NodeId, idx: usize) {
+        let min = self.min_vals();
+        let parent = &self.arena[id];
+        let child = parent.children[idx];
+        if self.arena[child].vals.len() >= min {
+            return;
+        }
+        let left = idx.checked_sub(1).map(|i| parent.children[i]);
+        let right = parent.children.get(idx + 1).copied();
+
+        match (left, right) {
+            (Some(left), _) if self.arena[left].vals.len() > min => {
+                let sibling = &mut self.arena[left];
+                let val = sibling.vals.pop().expect("non-empty node");
+                let grandchild = sibling.children.pop();
+                let sep = mem::replace(&mut self.arena[id].vals[idx - 1], val);
+                let node = &mut self.arena[child];
+                node.vals.insert(0, sep);
+                if let Some(grandchild) = grandchild {
+                    node.children.insert(0, grandchild);
+                }
+            }
+            (_, Some(right)) if self.arena[right].vals.len() > min => {
+                let sibling = &mut self.arena[right];
+                let val = sibling.vals.remove(0);
+                let grandchild = if sibling.is_leaf() {
+                    None
+                } else {
+                    Some(sibling.children.remove(0))
+                };
+                let sep = mem::replace(&mut self.arena[id].vals[idx], val);
+                let node = &mut self.arena[child];
+                node.vals.push(sep);
+                node.children.extend(grandchild);
+            }
+            (Some(_), _) => self.merge(id, idx - 1),
+            (None, _) => self.merge(id, idx),
+        }
+    }
+
+    /// Merges the `idx`-th and the next child of `id`, with the value separating them.
+    fn merge(&mut self, id: NodeId, idx: usize) {
+        let parent = &mut self.arena[id];
+        let sep = parent.vals.remove(idx);
+        let right = parent.children.remove(idx + 1);
+        let left = parent.children[idx];
+        let right = self.arena.remove(right).expect("linked node");
+        let node = &mut self.arena[left];
+        node.vals.push(sep);
+        node.vals.extend(right.vals);
+        node.children.extend(right.children);
+    }
+}
+
+impl<T, C> BTree<T, C>
+where
+    C: Comparator<T>,
+{
+    /// Index of `val` within the node, or of the child it would be found under.
+    fn position<Q>(&self, id: NodeId, val: &Q) -> Result<usize, usize>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.arena[id]
+            .vals
+            .binary_search_by(|v| self.cmp.compare(v.borrow(), val))
+    }
+
+    pub fn get<Q>(&self, val: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut cur = self.root;
+        while let Some(id) = cur {
+            let node = &self.arena[id];
+            match self.position(id, val) {
+                Ok(idx) => return Some(&node.vals[idx]),
+                Err(idx) => cur = node.children.get(idx).copied(),
+            }
+        }
+        None
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.get(val).is_some()
+    }
+
+    /// Lazy in-order iterator over the values within `range`.
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, T, C>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut stack = self.seek(|val| match range.start_bound() {
+            Bound::Included(start) => self.cmp.compare(val.borrow(), start) == Ordering::Less,
+            Bound::Excluded(start) => self.cmp.compare(val.borrow(), start) != Ordering::Greater,
+            Bound::Unbounded => false,
+        });
+        let stop = match range.end_bound() {
+            Bound::Included(end) => self.next_position(
+                &self.seek(|val| self.cmp.compare(val.borrow(), end) != Ordering::Greater),
+            ),
+            Bound::Excluded(end) => self.next_position(
+                &self.seek(|val| self.cmp.compare(val.borrow(), end) == Ordering::Less),
+            ),
+            Bound::Unbounded => None,
+        };
+        if let (Some((id, idx)), Some((stop_id, stop_idx))) = (self.next_position(&stack), stop) {
+            let (first, past) = (
+                &self.arena[id].vals[idx],
+                &self.arena[stop_id].vals[stop_idx],
+            );
+            if Comparator::<T>::compare(&self.cmp, first, past) == Ordering::Greater {
+                stack.clear();
+            }
+        }
+        Iter {
+            tree: self,
+            stack,
+            stop,
+        }
+    }
+
+    /// Inserts `val`, returning whether it was not present yet.
+    pub fn insert(&mut self, val: T) -> bool {
+        let root = match self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(self.arena.push(BNode {
+                    vals: vec![val],
+                    children: vec![],
+                }));
+                self.len = 1;
+                return true;
+            }
+        };
+        match self.insert_at(root, val) {
+            Insertion::Present => return false,
+            Insertion::Done => {}
+            Insertion::Split(median, right) => {
+                self.root = Some(self.arena.push(BNode {
+                    vals: vec![median],
+                    children: vec![root, right],
+                }));
+            }
+        }
+        self.len += 1;
+        true
+    }
+
+    fn insert_at(&mut self, id: NodeId, val: T) -> Insertion<T> {
+        let idx = match self.position(id, &val) {
+            Ok(_) => return Insertion::Present,
+            Err(idx) => idx,
+        };
+        if self.arena[id].is_leaf() {
+            self.arena[id].vals.insert(idx, val);
+        } else {
+            let child = self.arena[id].children[idx];
+            match self.insert_at(child, val) {
+                Insertion::Split(median, right) => {
+                    let node = &mut self.arena[id];
+                    node.vals.insert(idx, median);
+                    node.children.insert(idx + 1, right);
+                }
+                insertion => return insertion,
+            }
+        }
+        if self.arena[id].vals.len() < self.fanout {
+            Insertion::Done
+        } else {
+            self.split(id)
+        }
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let root = match self.root {
+            Some(root) => root,
+            None => return false,
+        };
+        if self.delete_at(root, val).is_none() {
+            return false;
+        }
+        self.len -= 1;
+        let node = &self.arena[root];
+        if node.vals.is_empty() {
+            // The root lost its last value, its only child takes over.
+            self.root = node.children.first().copied();
+            self.arena.remove(root);
+        }
+        true
+    }
+
+    fn delete_at<Q>(&mut self, id: NodeId, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let leaf = self.arena[id].is_leaf();
+        match self.position(id, val) {
+            Ok(idx) if leaf => Some(self.arena[id].vals.remove(idx)),
+            Ok(idx) => {
+                // Replaced by its predecessor, the largest value of the left subtree.
+                let pred = self.pop_max(self.arena[id].children[idx]);
+                let val = mem::replace(&mut self.arena[id].vals[idx], pred);
+                self.fix_child(id, idx);
+                Some(val)
+            }
+            Err(_) if leaf => None,
+            Err(idx) => {
+                let val = self.delete_at(self.arena[id].children[idx], val)?;
+                self.fix_child(id, idx);
+                Some(val)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn btree_check<T: Ord, C>(t: &BTree<T, C>, id: NodeId, is_root: bool) -> usize {
+    let node = &t.arena[id];
+    assert!(node.vals.len() < t.fanout());
+    assert!(is_root || node.vals.len() >= t.min_vals());
+    assert!(node.vals.windows(2).all(|w| w[0] < w[1]));
+    if node.is_leaf() {
+        return 1;
+    }
+    assert_eq!(node.children.len(), node.vals.len() + 1);
+    let depths: Vec<_> = node
+        .children
+        .iter()
+        .map(|child| btree_check(t, *child, false))
+        .collect();
+    assert!(depths.windows(2).all(|w| w[0] == w[1]));
+    depths[0] + 1
+}
+
+#[test]
+fn btree_insert_and_delete() {
+    for fanout in 3..=6 {
+        let mut t = BTree::with_fanout(fanout);
+        for val in (0..300).map(|val| val * 7 % 300) {
+            assert!(t.insert(val));
+        }
+        assert!(!t.insert(7));
+        assert_eq!(t.len(), 300);
+        assert_eq!(btree_check(&t, t.root.unwrap(), true), t.height());
+        assert!(t.iter().copied().eq(0..300));
+
+        for val in (0..300).filter(|val| val % 3 != 0) {
+            assert!(t.delete(&val));
+            btree_check(&t, t.root.unwrap(), true);
+        }
+        assert!(!t.delete(&1));
+        assert_eq!(t.len(), 100);
+        assert!(t.iter().copied().eq((0..300).step_by(3)));
+        assert_eq!(t.get(&3), Some(&3));
+        assert!(!t.contains(&4));
+
+        for val in (0..300).step_by(3) {
+            assert!(t.delete(&val));
+        }
+        assert!(t.is_empty());
+        assert_eq!(t.height(), 0);
+        assert_eq!(t.arena.len(), 0);
+    }
+}
+
+#[test]
+fn btree_fanout_bounds_height() {
+    let t = BTree::from_vec((0..10_000).collect());
+    assert_eq!(t.fanout(), DEFAULT_FANOUT);
+    assert!(t.height() <= 5);
+
+    let mut t = BTree::with_comparator(|a: &String, b: &String| b.cmp(a));
+    for word in ["kiwi", "apple", "banana", "fig"].iter() {
+        t.insert(word.to_string());
+    }
+    assert_eq!(
+        t.iter().map(String::as_str).collect::<Vec<_>>(),
+        ["kiwi", "fig", "banana", "apple"]
+    );
+    assert!(t.contains(&"fig".to_string()));
+}
+
+#[test]
+fn btree_range() {
+    let mut t = BTree::with_fanout(3);
+    for val in 0..100 {
+        t.insert(val * 2);
+    }
+    for start in 0..200 {
+        for end in start..=200 {
+            assert!(t
+                .range(start..end)
+                .copied()
+                .eq((start..end).filter(|val| val % 2 == 0)));
+        }
+    }
+    assert!(t.range(..=10).copied().eq((0..=10).step_by(2)));
+    assert!(t
+        .range((Bound::Excluded(190), Bound::Unbounded))
+        .copied()
+        .eq(vec![192, 194, 196, 198]));
+    assert_eq!(
+        t.range((Bound::Included(60), Bound::Excluded(50))).next(),
+        None
+    );
+}
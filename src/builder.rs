@@ -0,0 +1,147 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::validate::ValidationError;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// A child was given to a builder without a value.
+    MissingValue,
+    /// The nodes don't form a valid binary search tree.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingValue => write!(f, "node without a value"),
+            BuildError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BuildError::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Builds a tree of an explicit shape, e.g.
+/// `ArenaTreeBuilder::new().root(5).left(|b| b.node(3)).right(|b| b.node(8))`.
+#[derive(Debug)]
+pub struct ArenaTreeBuilder<T> {
+    val: Option<T>,
+    left: Option<Box<ArenaTreeBuilder<T>>>,
+    right: Option<Box<ArenaTreeBuilder<T>>>,
+}
+
+impl<T> Default for ArenaTreeBuilder<T> {
+    fn default() -> Self {
+        Self {
+            val: None,
+            left: None,
+            right: None,
+        }
+    }
+}
+
+impl<T> ArenaTreeBuilder<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn root(self, val: T) -> Self {
+        self.node(val)
+    }
+
+    /// Sets the value of the node being built.
+    pub fn node(mut self, val: T) -> Self {
+        self.val = Some(val);
+        self
+    }
+
+    pub fn left<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        self.left = Some(Box::new(f(Self::default())));
+        self
+    }
+
+    pub fn right<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        self.right = Some(Box::new(f(Self::default())));
+        self
+    }
+
+    /// Builds the tree, with node ids assigned in pre-order,
+    /// checked by `ArenaTree::validate`.
+    pub fn build(self) -> Result<ArenaTree<T>, BuildError> {
+        let mut t = ArenaTree::default();
+        if self.val.is_none() && self.left.is_none() && self.right.is_none() {
+            return Ok(t);
+        }
+        let mut stack = vec![(None, self)];
+        while let Some((pos, b)) = stack.pop() {
+            let id: NodeId = t.attach(pos, b.val.ok_or(BuildError::MissingValue)?);
+            if let Some(right) = b.right {
+                stack.push((Some((id, false)), *right));
+            }
+            if let Some(left) = b.left {
+                stack.push((Some((id, true)), *left));
+            }
+        }
+        t.validate().map_err(BuildError::Invalid)?;
+        Ok(t)
+    }
+}
+
+#[test]
+fn builder_shape() {
+    let t = ArenaTreeBuilder::new()
+        .root(5)
+        .left(|b| b.node(3).left(|b| b.node(1)))
+        .right(|b| b.node(8).left(|b| b.node(6)).right(|b| b.node(9)))
+        .build()
+        .unwrap();
+    println!("arena: {:?}", t);
+
+    assert_eq!(
+        t.traversal(&crate::arena::Traversal::BFS),
+        vec![&5, &3, &8, &1, &6, &9]
+    );
+    assert_eq!(t.left_of(0), Some(1));
+    assert_eq!(t.left_of(1), Some(2));
+    assert_eq!(t.right_of(0), Some(3));
+
+    let t = ArenaTreeBuilder::<i32>::new().build().unwrap();
+    assert_eq!(t.size(), 0);
+}
+
+#[test]
+fn builder_validation() {
+    let rv = ArenaTreeBuilder::new()
+        .root(5)
+        .left(|b| b.node(3).right(|b| b.node(7)))
+        .build();
+    let err = rv.unwrap_err();
+    assert_eq!(err, BuildError::Invalid(ValidationError::OutOfOrder(2)));
+    assert_eq!(err.to_string(), "node #2 violates the BST ordering");
+
+    let rv = ArenaTreeBuilder::new().root(5).right(|b| b.node(5)).build();
+    assert_eq!(
+        rv.unwrap_err(),
+        BuildError::Invalid(ValidationError::OutOfOrder(1))
+    );
+
+    let rv = ArenaTreeBuilder::new().root(5).left(|b| b).build();
+    assert_eq!(rv.unwrap_err(), BuildError::MissingValue);
+}
@@ -0,0 +1,124 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// Ordering strategy used by trees to arrange their values.
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/// Orders values by their `Ord` implementation.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Natural;
+
+impl<T> Comparator<T> for Natural
+where
+    T: Ord + ?Sized,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T, F> Comparator<T> for F
+where
+    T: ?Sized,
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Flips the ordering of the wrapped comparator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Reversed<C = Natural>(pub C);
+
+impl<T, C> Comparator<T> for Reversed<C>
+where
+    T: ?Sized,
+    C: Comparator<T>,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(b, a)
+    }
+}
+
+/// Orders values by a key projected out of them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KeyFn<F>(pub F);
+
+impl<T, K, F> Comparator<T> for KeyFn<F>
+where
+    K: Ord,
+    F: Fn(&T) -> K,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        (self.0)(a).cmp(&(self.0)(b))
+    }
+}
+
+macro_rules! total_float {
+    ($($name: ident($t: ty)),*) => {
+        $(
+            /// Float ordered by `total_cmp`, so it can be stored in trees.
+            ///
+            /// Equality agrees with the ordering rather than with `==` on floats:
+            /// `-0.0` is less than `0.0`, and a NaN equals a NaN with the same bits.
+            /// NaNs with the sign bit cleared sort after the positive infinity,
+            /// those with it set before the negative infinity.
+            #[derive(Debug, Default, Clone, Copy)]
+            pub struct $name(pub $t);
+
+            impl From<$t> for $name {
+                fn from(val: $t) -> Self {
+                    Self(val)
+                }
+            }
+
+            impl PartialEq for $name {
+                fn eq(&self, other: &Self) -> bool {
+                    self.cmp(other) == Ordering::Equal
+                }
+            }
+
+            impl Eq for $name {}
+
+            impl PartialOrd for $name {
+                fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                    Some(self.cmp(other))
+                }
+            }
+
+            impl Ord for $name {
+                fn cmp(&self, other: &Self) -> Ordering {
+                    self.0.total_cmp(&other.0)
+                }
+            }
+
+            impl Hash for $name {
+                fn hash<H: Hasher>(&self, state: &mut H) {
+                    self.0.to_bits().hash(state);
+                }
+            }
+        )*
+    };
+}
+
+total_float!(TotalF64(f64), TotalF32(f32));
+
+#[test]
+fn compare_total_float() {
+    use crate::arena::ArenaTree;
+
+    let vals = vec![1.5, f64::NAN, -0.0, f64::NEG_INFINITY, 0.0, -f64::NAN, 1.5];
+    let t = ArenaTree::from_vec(vals.into_iter().map(TotalF64).collect());
+    let sorted: Vec<_> = t.iter().map(|val| val.0.to_bits()).collect();
+    let expected = [-f64::NAN, f64::NEG_INFINITY, -0.0, 0.0, 1.5, f64::NAN];
+    assert_eq!(
+        sorted,
+        expected.iter().map(|val| val.to_bits()).collect::<Vec<_>>()
+    );
+    assert!(t.contains(&TotalF64(f64::NAN)));
+    assert!(!t.contains(&TotalF64(2.0)));
+    assert!(TotalF32(-0.0) < TotalF32::from(0.0));
+}
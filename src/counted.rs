@@ -0,0 +1,144 @@
+use crate::map::ArenaMap;
+use std::borrow::Borrow;
+
+/// Multiset keeping a count per distinct value.
+#[derive(Debug)]
+pub struct CountedArenaTree<T> {
+    map: ArenaMap<T, usize>,
+    len: usize,
+}
+
+impl<T> Default for CountedArenaTree<T> {
+    fn default() -> Self {
+        Self {
+            map: ArenaMap::default(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> CountedArenaTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+
+    /// Number of values, counting duplicates.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn distinct_len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Inserts the value, returning its count afterwards.
+    pub fn insert(&mut self, val: T) -> usize {
+        self.len += 1;
+        match self.map.get_mut(&val) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.map.insert(val, 1);
+                1
+            }
+        }
+    }
+
+    pub fn count<Q>(&self, val: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.get(val).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.contains_key(val)
+    }
+
+    /// Removes one occurrence of the value, returning whether it was present.
+    pub fn delete_one<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.map.get_mut(val) {
+            None => return false,
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.map.remove(val);
+            }
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Removes every occurrence of the value, returning how many were removed.
+    pub fn delete_all<Q>(&mut self, val: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let count = self.map.remove(val).unwrap_or(0);
+        self.len -= count;
+        count
+    }
+
+    /// Iterates over the distinct values with their counts in order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&T, usize)> {
+        self.map.iter().map(|(val, &count)| (val, count))
+    }
+}
+
+#[test]
+fn counted_insert_count() {
+    let mut t = CountedArenaTree::new();
+    assert_eq!(t.insert(3), 1);
+    assert_eq!(t.insert(1), 1);
+    assert_eq!(t.insert(3), 2);
+    assert_eq!(t.insert(3), 3);
+    assert_eq!(t.len(), 4);
+    assert_eq!(t.distinct_len(), 2);
+    assert_eq!(t.count(&3), 3);
+    assert_eq!(t.count(&2), 0);
+    assert_eq!(t.iter().collect::<Vec<_>>(), vec![(&1, 1), (&3, 3)]);
+
+    println!("tree: {:?}", t);
+}
+
+#[test]
+fn counted_delete() {
+    let mut t = CountedArenaTree::from_vec(vec![2, 1, 2, 3, 2, 1]);
+    assert!(t.delete_one(&2));
+    assert_eq!(t.count(&2), 2);
+    assert!(t.delete_one(&3));
+    assert!(!t.delete_one(&3));
+    assert!(!t.contains(&3));
+    assert_eq!(t.len(), 4);
+
+    assert_eq!(t.delete_all(&2), 2);
+    assert_eq!(t.delete_all(&2), 0);
+    assert_eq!(t.len(), 2);
+    assert_eq!(t.iter().collect::<Vec<_>>(), vec![(&1, 2)]);
+}
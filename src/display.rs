@@ -0,0 +1,436 @@
+use crate::arena::{ArenaTree, NodeId};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Controls how `ArenaTree::pretty` renders a tree.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FormatOptions {
+    /// Append the node id to each value, e.g. `5 #0`.
+    pub node_ids: bool,
+    /// Draw branches with `|--` and `` `-- `` instead of box-drawing characters.
+    pub ascii: bool,
+}
+
+/// Renders the tree as an indented drawing, one node per line.
+/// A missing child is drawn as `∅` (or `-` in ASCII) when its sibling exists.
+pub struct Pretty<'a, T, C, M> {
+    tree: &'a ArenaTree<T, C, M>,
+    opts: FormatOptions,
+}
+
+impl<T, C, M> ArenaTree<T, C, M> {
+    pub fn pretty(&self, opts: FormatOptions) -> Pretty<'_, T, C, M> {
+        Pretty { tree: self, opts }
+    }
+}
+
+impl<'a, T, C, M> Pretty<'a, T, C, M>
+where
+    T: fmt::Display,
+{
+    fn write_node(&self, f: &mut fmt::Formatter<'_>, id: NodeId) -> fmt::Result {
+        write!(f, "{}", self.tree.get(id).unwrap())?;
+        if self.opts.node_ids {
+            write!(f, " #{}", id)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl<'a, T, C, M> fmt::Display for Pretty<'a, T, C, M>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (tee, elbow, pipe, empty) = if self.opts.ascii {
+            ("|-- ", "`-- ", "|   ", "-")
+        } else {
+            ("├── ", "└── ", "│   ", "∅")
+        };
+        let root_id = match self.tree.root() {
+            None => return Ok(()),
+            Some(id) => id,
+        };
+        self.write_node(f, root_id)?;
+
+        let children = |id| match (self.tree.left_of(id), self.tree.right_of(id)) {
+            (None, None) => vec![],
+            (left, right) => vec![(left, false), (right, true)],
+        };
+        let mut stack: Vec<_> = children(root_id)
+            .into_iter()
+            .rev()
+            .map(|(id, last)| (id, last, String::new()))
+            .collect();
+        while let Some((id, last, prefix)) = stack.pop() {
+            write!(f, "{}{}", prefix, if last { elbow } else { tee })?;
+            let id = match id {
+                None => {
+                    writeln!(f, "{}", empty)?;
+                    continue;
+                }
+                Some(id) => id,
+            };
+            self.write_node(f, id)?;
+            let prefix = format!("{}{}", prefix, if last { "    " } else { pipe });
+            for (child, last) in children(id).into_iter().rev() {
+                stack.push((child, last, prefix.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T, C, M> fmt::Display for ArenaTree<T, C, M>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pretty(FormatOptions::default()).fmt(f)
+    }
+}
+
+/// Debug view of a tree that doesn't depend on where the nodes live in the arena,
+/// taken by `ArenaTree::canonical_debug`.
+///
+/// Nodes are listed in order, and every id is replaced by the in-order position
+/// of the node, so trees of the same shape and values print the same
+/// whatever their insertion history and the gaps in their arenas.
+pub struct CanonicalDebug<'a, T, C, M> {
+    tree: &'a ArenaTree<T, C, M>,
+}
+
+impl<T, C, M> ArenaTree<T, C, M> {
+    pub fn canonical_debug(&self) -> CanonicalDebug<'_, T, C, M> {
+        CanonicalDebug { tree: self }
+    }
+}
+
+/// Node of a `CanonicalDebug`, with its links as in-order positions.
+struct CanonicalNode<'a, T, M> {
+    idx: usize,
+    val: &'a T,
+    meta: &'a M,
+    parent: Option<usize>,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl<'a, T, M> fmt::Debug for CanonicalNode<'a, T, M>
+where
+    T: fmt::Debug,
+    M: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("idx", &self.idx)
+            .field("val", self.val)
+            .field("meta", self.meta)
+            .field("parent", &self.parent)
+            .field("left", &self.left)
+            .field("right", &self.right)
+            .finish()
+    }
+}
+
+impl<'a, T, C, M> fmt::Debug for CanonicalDebug<'a, T, C, M>
+where
+    T: fmt::Debug,
+    M: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tree = self.tree;
+        let ids = tree.root().map_or(vec![], |root| tree.subtree_ids(root));
+        let pos: HashMap<NodeId, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let canonical = |id: Option<NodeId>| id.map(|id| pos[&id]);
+        let nodes = ids.iter().enumerate().map(|(idx, id)| CanonicalNode {
+            idx,
+            val: tree.get(*id).expect("linked node"),
+            meta: tree.meta(*id).expect("linked node"),
+            parent: canonical(tree.parent_of(*id)),
+            left: canonical(tree.left_of(*id)),
+            right: canonical(tree.right_of(*id)),
+        });
+        f.debug_struct("ArenaTree")
+            .field("root", &canonical(tree.root()))
+            .field("nodes", &nodes.collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Controls how `ArenaTree::to_dot_with` writes a tree.
+#[derive(Debug, Clone)]
+pub struct DotOptions {
+    /// Label each node with its value, on by default.
+    pub labels: bool,
+    /// Append the node id to each label, e.g. `5 #0`.
+    pub node_ids: bool,
+    /// Nodes to fill in, e.g. the ones a deletion touched.
+    pub highlight: HashSet<NodeId>,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        Self {
+            labels: true,
+            node_ids: false,
+            highlight: HashSet::new(),
+        }
+    }
+}
+
+/// Quotes `s` as a DOT string.
+fn dot_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl<T, C, M> ArenaTree<T, C, M>
+where
+    T: fmt::Display,
+{
+    /// Graphviz DOT definition of the tree, to be piped to `dot`.
+    /// A missing child is drawn as an invisible node when its sibling exists,
+    /// so left and right children keep their sides.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(&DotOptions::default())
+    }
+
+    /// Like `to_dot`, labelling and highlighting nodes as `opts` tells.
+    pub fn to_dot_with(&self, opts: &DotOptions) -> String {
+        let mut dot = String::from("digraph {\n");
+        let mut stack: Vec<_> = self.root().into_iter().collect();
+        while let Some(id) = stack.pop() {
+            let mut label = String::new();
+            if opts.labels {
+                label.push_str(&self.get(id).unwrap().to_string());
+            }
+            if opts.node_ids {
+                if !label.is_empty() {
+                    label.push(' ');
+                }
+                label.push_str(&format!("#{}", id));
+            }
+            let style = if opts.highlight.contains(&id) {
+                ", style=filled, fillcolor=yellow"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    n{} [label={}{}];\n",
+                id,
+                dot_quote(&label),
+                style
+            ));
+            let children = match (self.left_of(id), self.right_of(id)) {
+                (None, None) => continue,
+                (left, right) => [(left, "l"), (right, "r")],
+            };
+            for (child, side) in children.iter() {
+                match child {
+                    Some(child) => dot.push_str(&format!("    n{} -> n{};\n", id, child)),
+                    None => dot.push_str(&format!(
+                        "    n{0}{1} [shape=point, style=invis];\n    n{0} -> n{0}{1} [style=invis];\n",
+                        id, side
+                    )),
+                }
+            }
+            stack.extend(self.right_of(id));
+            stack.extend(self.left_of(id));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Quotes `s` as a Mermaid node label, escaping what Mermaid would parse.
+fn mermaid_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("#quot;"),
+            '#' => quoted.push_str("#35;"),
+            '<' => quoted.push_str("#lt;"),
+            '>' => quoted.push_str("#gt;"),
+            '\n' => quoted.push_str("<br>"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl<T, C, M> ArenaTree<T, C, M>
+where
+    T: fmt::Display,
+{
+    /// Mermaid `graph TD` definition of the tree, for embedding in markdown.
+    /// A missing child is drawn as a hidden node when its sibling exists,
+    /// so left and right children keep their sides.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("graph TD\n");
+        let mut hidden = false;
+        let mut stack: Vec<_> = self.root().into_iter().collect();
+        while let Some(id) = stack.pop() {
+            let label = mermaid_quote(&self.get(id).unwrap().to_string());
+            mermaid.push_str(&format!("    n{}[{}]\n", id, label));
+            let children = match (self.left_of(id), self.right_of(id)) {
+                (None, None) => continue,
+                (left, right) => [(left, "l"), (right, "r")],
+            };
+            for (child, side) in children.iter() {
+                match child {
+                    Some(child) => mermaid.push_str(&format!("    n{} --> n{}\n", id, child)),
+                    None => {
+                        hidden = true;
+                        mermaid.push_str(&format!(
+                            "    n{0}{1}[\" \"]:::hidden\n    n{0} ~~~ n{0}{1}\n",
+                            id, side
+                        ));
+                    }
+                }
+            }
+            stack.extend(self.right_of(id));
+            stack.extend(self.left_of(id));
+        }
+        if hidden {
+            mermaid.push_str("    classDef hidden fill:none,stroke:none\n");
+        }
+        mermaid
+    }
+}
+
+#[test]
+fn display_tree() {
+    let t = ArenaTree::from_vec(vec![5, 3, 8, 1, 6, 9, 7]);
+    assert_eq!(
+        t.to_string(),
+        "\
+5
+├── 3
+│   ├── 1
+│   └── ∅
+└── 8
+    ├── 6
+    │   ├── ∅
+    │   └── 7
+    └── 9
+"
+    );
+    assert_eq!(ArenaTree::<i32>::default().to_string(), "");
+}
+
+#[test]
+fn display_options() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3]);
+    let opts = FormatOptions {
+        node_ids: true,
+        ascii: true,
+    };
+    assert_eq!(
+        t.pretty(opts).to_string(),
+        "\
+2 #0
+|-- 1 #1
+`-- 3 #2
+"
+    );
+}
+
+#[test]
+fn display_canonical_debug() {
+    let a = ArenaTree::from_vec(vec![2, 1, 3]);
+    let mut b = ArenaTree::from_vec(vec![5, 2, 0, 3, 1]);
+    b.delete(&0);
+    b.delete(&5);
+    // Same shape as `a`, with different ids and gaps in the arena.
+    assert_ne!(format!("{:?}", a), format!("{:?}", b));
+    assert_eq!(
+        format!("{:?}", a.canonical_debug()),
+        format!("{:?}", b.canonical_debug())
+    );
+    assert_eq!(
+        format!("{:?}", a.canonical_debug()),
+        "ArenaTree { root: Some(1), nodes: [\
+Node { idx: 0, val: 1, meta: (), parent: Some(1), left: None, right: None }, \
+Node { idx: 1, val: 2, meta: (), parent: None, left: Some(0), right: Some(2) }, \
+Node { idx: 2, val: 3, meta: (), parent: Some(1), left: None, right: None }] }"
+    );
+    assert_eq!(
+        format!("{:?}", ArenaTree::<i32>::default().canonical_debug()),
+        "ArenaTree { root: None, nodes: [] }"
+    );
+}
+
+#[test]
+fn display_to_dot() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3, 4]);
+    assert_eq!(
+        t.to_dot(),
+        "\
+digraph {
+    n0 [label=\"2\"];
+    n0 -> n1;
+    n0 -> n2;
+    n1 [label=\"1\"];
+    n2 [label=\"3\"];
+    n2l [shape=point, style=invis];
+    n2 -> n2l [style=invis];
+    n2 -> n3;
+    n3 [label=\"4\"];
+}
+"
+    );
+    let opts = DotOptions {
+        labels: false,
+        node_ids: true,
+        highlight: vec![3].into_iter().collect(),
+    };
+    let dot = t.to_dot_with(&opts);
+    assert!(dot.contains("    n2 [label=\"#2\"];\n"));
+    assert!(dot.contains("    n3 [label=\"#3\", style=filled, fillcolor=yellow];\n"));
+
+    let t = ArenaTree::from_vec(vec!["say \"hi\""]);
+    assert!(t.to_dot().contains(r#"n0 [label="say \"hi\""];"#));
+    assert_eq!(ArenaTree::<i32>::default().to_dot(), "digraph {\n}\n");
+}
+
+#[test]
+fn display_to_mermaid() {
+    let t = ArenaTree::from_vec(vec![2, 1, 3, 4]);
+    assert_eq!(
+        t.to_mermaid(),
+        "\
+graph TD
+    n0[\"2\"]
+    n0 --> n1
+    n0 --> n2
+    n1[\"1\"]
+    n2[\"3\"]
+    n2l[\" \"]:::hidden
+    n2 ~~~ n2l
+    n2 --> n3
+    n3[\"4\"]
+    classDef hidden fill:none,stroke:none
+"
+    );
+    let t = ArenaTree::from_vec(vec!["<a href=\"#\">"]);
+    assert_eq!(
+        t.to_mermaid(),
+        "graph TD\n    n0[\"#lt;a href=#quot;#35;#quot;#gt;\"]\n"
+    );
+    assert_eq!(ArenaTree::<i32>::default().to_mermaid(), "graph TD\n");
+}
@@ -0,0 +1,111 @@
+use std::ops::{AddAssign, Bound, RangeBounds, Sub};
+
+/// Fenwick (binary indexed) tree: prefix sums and point updates in O(log n),
+/// in a single `Vec` the size of the input.
+#[derive(Debug, Clone)]
+pub struct FenwickTree<T> {
+    /// `sums[i - 1]` holds the sum of the `i & i.wrapping_neg()` values ending at index `i - 1`.
+    sums: Vec<T>,
+}
+
+impl<T> FenwickTree<T>
+where
+    T: Copy + Default + AddAssign,
+{
+    /// Creates a tree of `len` zeros, `T::default()` being zero.
+    pub fn new(len: usize) -> Self {
+        Self {
+            sums: vec![T::default(); len],
+        }
+    }
+
+    /// Builds the tree in O(n).
+    pub fn from_vec(mut sums: Vec<T>) -> Self {
+        for i in 1..=sums.len() {
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= sums.len() {
+                let sum = sums[i - 1];
+                sums[parent - 1] += sum;
+            }
+        }
+        Self { sums }
+    }
+
+    pub fn len(&self) -> usize {
+        self.sums.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sums.is_empty()
+    }
+
+    /// Adds `delta` to the value at `idx`, panicking if it is out of bounds.
+    pub fn add(&mut self, idx: usize, delta: T) {
+        assert!(idx < self.len(), "index out of bounds");
+        let mut i = idx + 1;
+        while i <= self.len() {
+            self.sums[i - 1] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Sum of the first `end` values.
+    pub fn prefix_sum(&self, end: usize) -> T {
+        assert!(end <= self.len(), "index out of bounds");
+        let mut sum = T::default();
+        let mut i = end;
+        while i > 0 {
+            sum += self.sums[i - 1];
+            i &= i - 1;
+        }
+        sum
+    }
+
+    /// Sum of the values in `range`.
+    pub fn range_sum<R>(&self, range: R) -> T
+    where
+        R: RangeBounds<usize>,
+        T: Sub<Output = T>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len(),
+        };
+        assert!(start <= end, "range out of bounds");
+        self.prefix_sum(end) - self.prefix_sum(start)
+    }
+}
+
+#[test]
+fn fenwick_prefix_sums() {
+    let vals = vec![5, 3, 8, 1, 4, 7, 9, 2];
+    let mut t = FenwickTree::from_vec(vals.clone());
+    for end in 0..=vals.len() {
+        assert_eq!(t.prefix_sum(end), vals[..end].iter().sum::<i32>());
+    }
+    assert_eq!(t.range_sum(2..5), 13);
+    assert_eq!(t.range_sum(..), 39);
+    assert_eq!(t.range_sum(7..=7), 2);
+
+    t.add(3, 10);
+    assert_eq!(t.prefix_sum(4), 27);
+    assert_eq!(t.range_sum(3..4), 11);
+}
+
+#[test]
+fn fenwick_counting() {
+    let mut t = FenwickTree::new(10);
+    for val in [3, 7, 3, 9, 0].iter() {
+        t.add(*val, 1usize);
+    }
+    // Number of values below 5.
+    assert_eq!(t.prefix_sum(5), 3);
+    assert_eq!(t.range_sum(3..=7), 3);
+    assert!(!t.is_empty());
+}
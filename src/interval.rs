@@ -0,0 +1,152 @@
+use crate::arena::NodeId;
+use crate::augment::{Aggregate, AugmentedTree};
+use crate::compare::Comparator;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// Orders intervals by low endpoint, then by high endpoint.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ByBounds;
+
+impl<K> Comparator<Range<K>> for ByBounds
+where
+    K: Ord,
+{
+    fn compare(&self, a: &Range<K>, b: &Range<K>) -> Ordering {
+        a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
+    }
+}
+
+/// Largest high endpoint of a subtree.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxEnd;
+
+impl<K> Aggregate<Range<K>> for MaxEnd
+where
+    K: Clone + Ord,
+{
+    type Value = Option<K>;
+
+    fn lift(&self, val: &Range<K>) -> Option<K> {
+        Some(val.end.clone())
+    }
+
+    fn combine(&self, a: &Option<K>, b: &Option<K>) -> Option<K> {
+        a.clone().max(b.clone())
+    }
+}
+
+/// Set of half-open intervals answering which of them overlap a query interval
+/// in O(log n + m) for m matches.
+///
+/// Intervals are ordered by their low endpoint, and every subtree knows the largest
+/// high endpoint within, so subtrees ending before the query are skipped whole.
+#[derive(Debug, Clone)]
+pub struct IntervalTree<K>
+where
+    K: Clone + Ord,
+{
+    tree: AugmentedTree<Range<K>, MaxEnd, ByBounds>,
+}
+
+impl<K> Default for IntervalTree<K>
+where
+    K: Clone + Ord,
+{
+    fn default() -> Self {
+        Self {
+            tree: AugmentedTree::with_comparator(ByBounds, MaxEnd),
+        }
+    }
+}
+
+impl<K> IntervalTree<K>
+where
+    K: Clone + Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.size() == 0
+    }
+
+    /// Inserts the interval, returning whether it was not present yet.
+    pub fn insert(&mut self, interval: Range<K>) -> bool {
+        let len = self.len();
+        self.tree.insert(interval);
+        self.len() != len
+    }
+
+    pub fn contains(&self, interval: &Range<K>) -> bool {
+        self.tree.contains(interval)
+    }
+
+    pub fn remove(&mut self, interval: &Range<K>) -> bool {
+        self.tree.delete(interval)
+    }
+
+    /// Iterates over the intervals ordered by low endpoint.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Range<K>> {
+        self.tree.iter()
+    }
+
+    /// Intervals sharing at least one point with `query`, ordered by low endpoint.
+    pub fn overlapping(&self, query: Range<K>) -> Vec<&Range<K>> {
+        let mut found = vec![];
+        self.collect_overlapping(self.tree.root(), &query, &mut found);
+        found
+    }
+
+    fn collect_overlapping<'a>(
+        &'a self,
+        id: Option<NodeId>,
+        query: &Range<K>,
+        found: &mut Vec<&'a Range<K>>,
+    ) {
+        let id = match id {
+            Some(id) => id,
+            None => return,
+        };
+        match &self.tree.meta(id).expect("linked node").value {
+            Some(end) if *end > query.start => {}
+            _ => return,
+        }
+        self.collect_overlapping(self.tree.left_of(id), query, found);
+        let interval = self.tree.get(id).expect("linked node");
+        if interval.start < query.end {
+            if interval.end > query.start {
+                found.push(interval);
+            }
+            // Everything to the right starts no earlier than this interval.
+            self.collect_overlapping(self.tree.right_of(id), query, found);
+        }
+    }
+}
+
+#[test]
+fn interval_overlapping() {
+    let mut t = IntervalTree::new();
+    for interval in [15..20, 10..30, 17..19, 5..20, 12..15, 30..40].iter() {
+        assert!(t.insert(interval.clone()));
+    }
+    assert!(!t.insert(10..30));
+    assert_eq!(t.len(), 6);
+    assert_eq!(
+        t.overlapping(14..16),
+        [&(5..20), &(10..30), &(12..15), &(15..20)]
+    );
+    assert_eq!(t.overlapping(30..31), [&(30..40)]);
+    assert_eq!(t.overlapping(40..50), Vec::<&std::ops::Range<i32>>::new());
+    assert_eq!(t.overlapping(0..5), Vec::<&std::ops::Range<i32>>::new());
+
+    assert!(t.remove(&(10..30)));
+    assert!(!t.contains(&(10..30)));
+    assert_eq!(t.overlapping(25..35), [&(30..40)]);
+    assert_eq!(t.iter().next(), Some(&(5..20)));
+}
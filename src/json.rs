@@ -0,0 +1,590 @@
+//! JSON import and export of the tree structure, independent of serde.
+//!
+//! A tree is written as nested `{"value": .., "left": .., "right": ..}` objects,
+//! `null` standing for a missing child and for the empty tree, and values are
+//! converted by `JsonRecord`. Reading checks the tree with `ArenaTree::validate`.
+//! Neither direction recurses along the tree, so degenerate trees are fine.
+
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::Comparator;
+use crate::validate::ValidationError;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::mem;
+
+/// Parsed JSON document.
+/// Dropping it doesn't recurse, formatting, cloning and comparing it do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// Number as written, so integers of any size survive.
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Moves the children of a container out onto `stack`.
+    fn take_children(&mut self, stack: &mut Vec<Json>) {
+        match self {
+            Json::Array(items) => stack.append(items),
+            Json::Object(fields) => stack.extend(fields.drain(..).map(|(_, val)| val)),
+            _ => {}
+        }
+    }
+}
+
+/// Drops the nested values one by one, so deep documents don't overflow the stack.
+impl Drop for Json {
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        self.take_children(&mut stack);
+        while let Some(mut json) = stack.pop() {
+            json.take_children(&mut stack);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum JsonError {
+    /// The input isn't JSON, stopping at the given byte offset.
+    Syntax(usize),
+    /// A node is neither `null` nor an object with a `value` and children.
+    BadNode,
+    /// A `value` can't be decoded by `JsonRecord::decode`.
+    BadValue,
+    /// The nodes don't form a valid binary search tree.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Syntax(pos) => write!(f, "invalid JSON at byte {}", pos),
+            JsonError::BadNode => write!(f, "node isn't an object with a value"),
+            JsonError::BadValue => write!(f, "value can't be decoded"),
+            JsonError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonError::Invalid(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Value convertible to and from JSON, see `ArenaTree::to_json`.
+pub trait JsonRecord: Sized {
+    fn encode(&self) -> Json;
+
+    fn decode(json: &Json) -> Option<Self>;
+}
+
+macro_rules! impl_json_record {
+    ($($t: ty),*) => {
+        $(
+            impl JsonRecord for $t {
+                fn encode(&self) -> Json {
+                    Json::Number(self.to_string())
+                }
+
+                fn decode(json: &Json) -> Option<Self> {
+                    match json {
+                        Json::Number(n) => n.parse().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_json_record!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! impl_json_record_float {
+    ($($t: ty),*) => {
+        $(
+            /// Infinities and NaNs have no JSON form, they are written as `null`,
+            /// which doesn't decode.
+            impl JsonRecord for $t {
+                fn encode(&self) -> Json {
+                    if self.is_finite() {
+                        Json::Number(self.to_string())
+                    } else {
+                        Json::Null
+                    }
+                }
+
+                fn decode(json: &Json) -> Option<Self> {
+                    match json {
+                        Json::Number(n) => n.parse().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_json_record_float!(f32, f64);
+
+impl JsonRecord for bool {
+    fn encode(&self) -> Json {
+        Json::Bool(*self)
+    }
+
+    fn decode(json: &Json) -> Option<Self> {
+        match json {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl JsonRecord for String {
+    fn encode(&self) -> Json {
+        Json::String(self.clone())
+    }
+
+    fn decode(json: &Json) -> Option<Self> {
+        match json {
+            Json::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, val)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", val)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+/// Container still being parsed, for objects along with the key of the next value.
+enum Frame {
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>, String),
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> JsonError {
+        JsonError::Syntax(self.pos)
+    }
+
+    fn byte(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    /// Next byte after whitespace.
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.byte() {
+            self.pos += 1;
+        }
+        self.byte()
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        let found = self.peek() == Some(b);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), JsonError> {
+        if self.eat(b) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn literal(&mut self, lit: &str) -> Result<(), JsonError> {
+        if self.src[self.pos..].starts_with(lit) {
+            self.pos += lit.len();
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Skips digits, returning whether there was any.
+    fn digits(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.byte() {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    fn number(&mut self) -> Result<String, JsonError> {
+        let start = self.pos;
+        if self.byte() == Some(b'-') {
+            self.pos += 1;
+        }
+        let int = self.pos;
+        if !self.digits() || (self.src.as_bytes()[int] == b'0' && self.pos > int + 1) {
+            return Err(self.error());
+        }
+        if self.byte() == Some(b'.') {
+            self.pos += 1;
+            if !self.digits() {
+                return Err(self.error());
+            }
+        }
+        if let Some(b'e' | b'E') = self.byte() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.byte() {
+                self.pos += 1;
+            }
+            if !self.digits() {
+                return Err(self.error());
+            }
+        }
+        Ok(self.src[start..self.pos].to_string())
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        let hex = match self.src.get(self.pos..self.pos + 4) {
+            Some(hex) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => hex,
+            _ => return Err(self.error()),
+        };
+        let code = u32::from_str_radix(hex, 16).map_err(|_| self.error())?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    /// Code point of a `\u` escape, the `\u` being consumed already.
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let mut code = self.hex4()?;
+        if (0xd800..0xdc00).contains(&code) {
+            self.literal("\\u")?;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error());
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        char::from_u32(code).ok_or_else(|| self.error())
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut s = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(b) = self.byte() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            s.push_str(&self.src[start..self.pos]);
+            match self.byte() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(b'\\') => self.pos += 1,
+                _ => return Err(self.error()),
+            }
+            let c = match self.byte() {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    self.pos += 1;
+                    s.push(self.unicode_escape()?);
+                    continue;
+                }
+                _ => return Err(self.error()),
+            };
+            self.pos += 1;
+            s.push(c);
+        }
+    }
+
+    fn key(&mut self) -> Result<String, JsonError> {
+        self.peek();
+        let key = self.string()?;
+        self.expect(b':')?;
+        Ok(key)
+    }
+}
+
+impl Json {
+    /// Parses a whole document, keeping the open containers on a stack
+    /// rather than recursing, so nesting depth is only bounded by memory.
+    pub fn parse(src: &str) -> Result<Json, JsonError> {
+        let mut p = Parser { src, pos: 0 };
+        let mut stack = vec![];
+        loop {
+            let mut val = match p.peek().ok_or_else(|| p.error())? {
+                b'{' => {
+                    p.pos += 1;
+                    if p.eat(b'}') {
+                        Json::Object(vec![])
+                    } else {
+                        stack.push(Frame::Object(vec![], p.key()?));
+                        continue;
+                    }
+                }
+                b'[' => {
+                    p.pos += 1;
+                    if p.eat(b']') {
+                        Json::Array(vec![])
+                    } else {
+                        stack.push(Frame::Array(vec![]));
+                        continue;
+                    }
+                }
+                b'"' => Json::String(p.string()?),
+                b'n' => {
+                    p.literal("null")?;
+                    Json::Null
+                }
+                b't' => {
+                    p.literal("true")?;
+                    Json::Bool(true)
+                }
+                b'f' => {
+                    p.literal("false")?;
+                    Json::Bool(false)
+                }
+                _ => Json::Number(p.number()?),
+            };
+            // Hands the value to its container, closing the containers ending here.
+            loop {
+                match stack.last_mut() {
+                    None if p.peek().is_none() => return Ok(val),
+                    None => return Err(p.error()),
+                    Some(Frame::Array(items)) => {
+                        items.push(val);
+                        if p.eat(b',') {
+                            break;
+                        }
+                        p.expect(b']')?;
+                    }
+                    Some(Frame::Object(fields, key)) => {
+                        fields.push((mem::take(key), val));
+                        if p.eat(b',') {
+                            *key = p.key()?;
+                            break;
+                        }
+                        p.expect(b'}')?;
+                    }
+                }
+                val = match stack.pop().expect("open container") {
+                    Frame::Array(items) => Json::Array(items),
+                    Frame::Object(fields, _) => Json::Object(fields),
+                };
+            }
+        }
+    }
+}
+
+/// Pending output of `ArenaTree::to_json`.
+enum Emit {
+    Node(Option<NodeId>),
+    Text(&'static str),
+}
+
+impl<T, C, M> ArenaTree<T, C, M>
+where
+    T: JsonRecord,
+{
+    /// Writes the tree as nested `{"value", "left", "right"}` objects.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let mut stack = vec![Emit::Node(self.root())];
+        while let Some(emit) = stack.pop() {
+            match emit {
+                Emit::Text(text) => json.push_str(text),
+                Emit::Node(None) => json.push_str("null"),
+                Emit::Node(Some(id)) => {
+                    let val = self.get(id).unwrap().encode();
+                    write!(json, "{{\"value\":{},\"left\":", val).unwrap();
+                    stack.push(Emit::Text("}"));
+                    stack.push(Emit::Node(self.right_of(id)));
+                    stack.push(Emit::Text(",\"right\":"));
+                    stack.push(Emit::Node(self.left_of(id)));
+                }
+            }
+        }
+        json
+    }
+}
+
+impl<T, C> ArenaTree<T, C>
+where
+    T: JsonRecord,
+    C: Comparator<T> + Default,
+{
+    /// Reads a tree written by `to_json`, keeping its shape.
+    /// Missing children may be left out of the objects instead of being `null`.
+    pub fn from_json(src: &str) -> Result<Self, JsonError> {
+        let mut t = ArenaTree::with_comparator(C::default());
+        let mut stack = vec![(Json::parse(src)?, None)];
+        while let Some((mut json, pos)) = stack.pop() {
+            let fields = match &mut json {
+                Json::Null => continue,
+                Json::Object(fields) => mem::take(fields),
+                _ => return Err(JsonError::BadNode),
+            };
+            let (mut val, mut left, mut right) = (None, Json::Null, Json::Null);
+            for (key, json) in fields.into_iter() {
+                match key.as_str() {
+                    "value" => val = Some(T::decode(&json).ok_or(JsonError::BadValue)?),
+                    "left" => left = json,
+                    "right" => right = json,
+                    _ => return Err(JsonError::BadNode),
+                }
+            }
+            let id = t.attach(pos, val.ok_or(JsonError::BadNode)?);
+            stack.push((right, Some((id, false))));
+            stack.push((left, Some((id, true))));
+        }
+        t.validate().map_err(JsonError::Invalid)?;
+        Ok(t)
+    }
+}
+
+#[test]
+fn json_roundtrip() {
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5]);
+    t.delete(&4);
+    let json = t.to_json();
+    assert_eq!(
+        json,
+        r#"{"value":5,"left":{"value":2,"left":{"value":1,"left":null,"right":null},"right":{"value":3,"left":null,"right":null}},"right":{"value":6,"left":null,"right":null}}"#
+    );
+    let back: ArenaTree<i32> = ArenaTree::from_json(&json).unwrap();
+    assert!(back.structurally_eq(&t));
+
+    let t = ArenaTree::from_vec(vec!["b\"\\\n".to_string(), "a\u{1}é😀".to_string()]);
+    let back: ArenaTree<String> = ArenaTree::from_json(&t.to_json()).unwrap();
+    assert!(back.iter().eq(t.iter()));
+    let back: ArenaTree<String> = ArenaTree::from_json(r#" { "value" : "😀é" } "#).unwrap();
+    assert!(back.iter().eq(["😀é"].iter()));
+
+    assert_eq!(ArenaTree::<i32>::default().to_json(), "null");
+    assert_eq!(ArenaTree::<i32>::from_json("null").unwrap().size(), 0);
+}
+
+#[test]
+fn json_degenerate_tree() {
+    let mut t = ArenaTree::<u32>::default();
+    let mut pos = None;
+    for val in 0..100_000 {
+        pos = Some((t.attach(pos, val), false));
+    }
+    let back: ArenaTree<u32> = ArenaTree::from_json(&t.to_json()).unwrap();
+    assert!(back.structurally_eq(&t));
+}
+
+#[test]
+fn json_errors() {
+    let from_json = ArenaTree::<i32>::from_json;
+    assert!(matches!(
+        from_json(r#"{"value":1"#),
+        Err(JsonError::Syntax(10))
+    ));
+    assert!(matches!(from_json("01"), Err(JsonError::Syntax(_))));
+    assert!(matches!(from_json("null null"), Err(JsonError::Syntax(5))));
+    assert!(matches!(from_json("[1]"), Err(JsonError::BadNode)));
+    assert!(matches!(
+        from_json(r#"{"left":null}"#),
+        Err(JsonError::BadNode)
+    ));
+    assert!(matches!(
+        from_json(r#"{"value":"1"}"#),
+        Err(JsonError::BadValue)
+    ));
+    let err = from_json(r#"{"value":2,"right":{"value":1}}"#).unwrap_err();
+    assert!(matches!(
+        err,
+        JsonError::Invalid(ValidationError::OutOfOrder(1))
+    ));
+    assert_eq!(err.to_string(), "node #1 violates the BST ordering");
+    assert!(matches!(
+        from_json(r#"{"value":2,"left":{"value":2}}"#),
+        Err(JsonError::Invalid(_))
+    ));
+}
+
+#[test]
+fn json_deep_documents() {
+    let depth = 200_000;
+    let src = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    drop(Json::parse(&src).unwrap());
+
+    // The bad value is found before the deep left subtree is read.
+    let chain = format!(
+        "{}null{}",
+        r#"{"value":1,"left":"#.repeat(depth),
+        "}".repeat(depth)
+    );
+    let src = format!(r#"{{"value":"x","left":{}}}"#, chain);
+    assert!(matches!(
+        ArenaTree::<i32>::from_json(&src),
+        Err(JsonError::BadValue)
+    ));
+}
@@ -0,0 +1,285 @@
+use crate::arena::{Link, NodeId};
+use crate::slots::Slots;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Debug)]
+struct KdNode<T, const K: usize> {
+    point: [T; K],
+    left: Link,
+    right: Link,
+}
+
+/// k-d tree indexing points of `K` coordinates, answering nearest-neighbor
+/// and rectangle queries by skipping the subtrees that can't match.
+///
+/// Nodes at depth `d` split their subtree on coordinate `d % K`: the left subtree
+/// holds smaller coordinates, the right one equal or greater. Coordinates must be
+/// totally ordered by `PartialOrd`, so floats must not be NaN.
+/// Nodes live in an arena like the nodes of `ArenaTree`, so node ids stay valid.
+#[derive(Debug)]
+pub struct KdTree<T, const K: usize> {
+    arena: Slots<KdNode<T, K>>,
+    root: Option<NodeId>,
+}
+
+impl<T, const K: usize> Default for KdTree<T, K> {
+    fn default() -> Self {
+        Self {
+            arena: Slots::default(),
+            root: None,
+        }
+    }
+}
+
+impl<T, const K: usize> KdTree<T, K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&[T; K]> {
+        self.arena.get(id).map(|node| &node.point)
+    }
+
+    /// Iterates over the points in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &[T; K]> {
+        self.arena.iter().map(|(_, node)| &node.point)
+    }
+}
+
+impl<T, const K: usize> KdTree<T, K>
+where
+    T: Copy + PartialOrd,
+{
+    /// Builds a balanced tree by splitting on the median of every subtree.
+    pub fn from_vec(mut points: Vec<[T; K]>) -> Self {
+        let mut t = Self::default();
+        points.sort_by(|a, b| a.partial_cmp(b).expect("NaN coordinate"));
+        points.dedup();
+        t.root = t.build(&mut points, 0);
+        t
+    }
+
+    fn build(&mut self, points: &mut [[T; K]], depth: usize) -> Option<NodeId> {
+        if points.is_empty() {
+            return None;
+        }
+        let axis = depth % K;
+        points.sort_by(|a, b| a[axis].partial_cmp(&b[axis]).expect("NaN coordinate"));
+        // The first of the median coordinates, so equal ones go right.
+        let mut mid = points.len() / 2;
+        while mid > 0 && points[mid - 1][axis] == points[mid][axis] {
+            mid -= 1;
+        }
+        let (left, rest) = points.split_at_mut(mid);
+        let (point, right) = rest.split_first_mut().expect("non-empty slice");
+        let left = self.build(left, depth + 1);
+        let right = self.build(right, depth + 1);
+        Some(self.arena.push(KdNode {
+            point: *point,
+            left: Link::new(left),
+            right: Link::new(right),
+        }))
+    }
+
+    /// Inserts `point`, returning the id of the new node,
+    /// or of the existing node if the point is already present.
+    pub fn insert(&mut self, point: [T; K]) -> NodeId {
+        let mut cur = match self.root {
+            Some(root) => root,
+            None => {
+                let id = self.push(point);
+                self.root = Some(id);
+                return id;
+            }
+        };
+        let mut depth = 0;
+        loop {
+            let node = &self.arena[cur];
+            if node.point == point {
+                return cur;
+            }
+            let left = point[depth % K] < node.point[depth % K];
+            let next = if left { node.left } else { node.right };
+            match next.get() {
+                Some(id) => cur = id,
+                None => {
+                    let id = self.push(point);
+                    let node = &mut self.arena[cur];
+                    if left {
+                        node.left = Link::new(Some(id));
+                    } else {
+                        node.right = Link::new(Some(id));
+                    }
+                    return id;
+                }
+            }
+            depth += 1;
+        }
+    }
+
+    fn push(&mut self, point: [T; K]) -> NodeId {
+        self.arena.push(KdNode {
+            point,
+            left: Link::NONE,
+            right: Link::NONE,
+        })
+    }
+
+    pub fn contains(&self, point: &[T; K]) -> bool {
+        let mut cur = self.root;
+        let mut depth = 0;
+        while let Some(id) = cur {
+            let node = &self.arena[id];
+            if node.point == *point {
+                return true;
+            }
+            cur = if point[depth % K] < node.point[depth % K] {
+                node.left.get()
+            } else {
+                node.right.get()
+            };
+            depth += 1;
+        }
+        false
+    }
+
+    /// Points within the box of corners `min` and `max`, both included,
+    /// in no particular order.
+    pub fn within(&self, min: &[T; K], max: &[T; K]) -> Vec<&[T; K]> {
+        let mut found = vec![];
+        // Walks with an explicit stack, `insert` doesn't keep the tree balanced.
+        let mut stack: Vec<_> = self.root.map(|id| (id, 0)).into_iter().collect();
+        while let Some((id, depth)) = stack.pop() {
+            let node = &self.arena[id];
+            if (0..K).all(|i| min[i] <= node.point[i] && node.point[i] <= max[i]) {
+                found.push(&node.point);
+            }
+            let axis = depth % K;
+            if max[axis] >= node.point[axis] {
+                stack.extend(node.right.get().map(|id| (id, depth + 1)));
+            }
+            if min[axis] < node.point[axis] {
+                stack.extend(node.left.get().map(|id| (id, depth + 1)));
+            }
+        }
+        found
+    }
+}
+
+impl<T, const K: usize> KdTree<T, K>
+where
+    T: Copy + PartialOrd + Default + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    /// Closest point to `query` by euclidean distance, any of them on ties.
+    pub fn nearest(&self, query: &[T; K]) -> Option<&[T; K]> {
+        let mut best: Option<(T, NodeId)> = None;
+        // Nodes to visit, with the squared distance to the splitting plane
+        // for the far sides, visited only if the plane is closer than the best point.
+        let mut stack: Vec<_> = self.root.map(|id| (id, 0, None)).into_iter().collect();
+        while let Some((id, depth, plane)) = stack.pop() {
+            if plane.is_some_and(|plane| best.is_some_and(|(best_dist, _)| plane >= best_dist)) {
+                continue;
+            }
+            let node = &self.arena[id];
+            let dist = (0..K).fold(T::default(), |dist, i| {
+                let gap = gap(query[i], node.point[i]);
+                dist + gap * gap
+            });
+            if best.is_none_or(|(best_dist, _)| dist < best_dist) {
+                best = Some((dist, id));
+            }
+            let axis = depth % K;
+            let (near, far) = if query[axis] < node.point[axis] {
+                (node.left, node.right)
+            } else {
+                (node.right, node.left)
+            };
+            // The far side is popped after the whole near side is searched.
+            let gap = gap(query[axis], node.point[axis]);
+            stack.extend(far.get().map(|id| (id, depth + 1, Some(gap * gap))));
+            stack.extend(near.get().map(|id| (id, depth + 1, None)));
+        }
+        best.map(|(_, id)| &self.arena[id].point)
+    }
+}
+
+/// Absolute difference, without going below zero for unsigned coordinates.
+fn gap<T>(a: T, b: T) -> T
+where
+    T: PartialOrd + Sub<Output = T>,
+{
+    if a < b {
+        b - a
+    } else {
+        a - b
+    }
+}
+
+#[test]
+fn kdtree_nearest() {
+    let points = [[2, 3], [5, 4], [9, 6], [4, 7], [8, 1], [7, 2]];
+    let t = KdTree::from_vec(points.to_vec());
+    assert_eq!(t.size(), 6);
+    assert_eq!(t.nearest(&[9, 2]), Some(&[8, 1]));
+    assert_eq!(t.nearest(&[3, 6]), Some(&[4, 7]));
+    assert_eq!(t.nearest(&[5, 4]), Some(&[5, 4]));
+    assert_eq!(KdTree::<i32, 2>::new().nearest(&[0, 0]), None);
+
+    // Brute force over a grid of queries, with unsigned coordinates.
+    let mut t = KdTree::new();
+    let points: Vec<[u32; 3]> = (0..200u32)
+        .map(|i| [i * 37 % 101, i * 53 % 97, i * 11 % 89])
+        .collect();
+    for point in points.iter() {
+        t.insert(*point);
+    }
+    let dist = |a: &[u32; 3], b: &[u32; 3]| -> u32 {
+        (0..3).map(|i| gap(a[i], b[i]) * gap(a[i], b[i])).sum()
+    };
+    for query in (0..50u32).map(|i| [i * 7 % 100, i * 13 % 100, i * 3 % 100]) {
+        let found = t.nearest(&query).unwrap();
+        let best = points.iter().map(|p| dist(p, &query)).min().unwrap();
+        assert_eq!(dist(found, &query), best);
+    }
+}
+
+#[test]
+fn kdtree_within() {
+    let mut t = KdTree::new();
+    for x in 0..10 {
+        for y in 0..10 {
+            t.insert([f64::from(x), f64::from(y)]);
+        }
+    }
+    assert_eq!(t.insert([3.0, 4.0]), t.insert([3.0, 4.0]));
+    assert!(t.contains(&[3.0, 4.0]));
+    assert!(!t.contains(&[3.5, 4.0]));
+    let mut found = t.within(&[2.5, 4.0], &[4.0, 5.5]);
+    found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(found, [&[3.0, 4.0], &[3.0, 5.0], &[4.0, 4.0], &[4.0, 5.0]]);
+    assert!(t.within(&[20.0, 0.0], &[30.0, 10.0]).is_empty());
+}
+
+#[test]
+fn kdtree_degenerate() {
+    // The chain sorted inserts make, linked directly as inserting takes O(n^2),
+    // deep enough to overflow a recursive walk.
+    let mut t = KdTree::new();
+    t.insert([0, 0]);
+    let mut last = t.root.unwrap();
+    for i in 1..100_000i64 {
+        let id = t.push([i, i]);
+        t.arena[last].right = Link::new(Some(id));
+        last = id;
+    }
+    assert_eq!(t.nearest(&[70_000, 70_001]), Some(&[70_000, 70_000]));
+    assert_eq!(t.within(&[10, 10], &[12, 20]).len(), 3);
+}
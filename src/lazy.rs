@@ -0,0 +1,195 @@
+use crate::arena::{ArenaTree, NodeId, Traversal};
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::ops::ControlFlow;
+
+/// Variant of `ArenaTree` where `delete` only marks the node as removed,
+/// leaving the tree shape untouched, and `sweep` unlinks all marked nodes at once.
+///
+/// Lookups and traversals skip the marked nodes, and inserting a marked value
+/// revives its node. Suits bursts of deletions followed by quiet periods to sweep in.
+/// The removal mark is kept as node metadata, which is why the tree isn't available
+/// through `Deref`: its traversals would still see the marked nodes.
+#[derive(Debug, Clone)]
+pub struct LazyTree<T, C = Natural> {
+    tree: ArenaTree<T, C, bool>,
+    removed: usize,
+}
+
+impl<T> Default for LazyTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> LazyTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+impl<T, C> LazyTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            tree: ArenaTree::with_comparator_and_meta(cmp),
+            removed: 0,
+        }
+    }
+
+    /// Number of values, not counting the removed ones.
+    pub fn size(&self) -> usize {
+        self.tree.size() - self.removed
+    }
+
+    /// Number of nodes marked as removed, waiting for `sweep`.
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    fn is_live(&self, id: NodeId) -> bool {
+        self.tree.meta(id) == Some(&false)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        if self.is_live(id) {
+            self.tree.get(id)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, C> LazyTree<T, C>
+where
+    C: Comparator<T>,
+{
+    pub fn traversal(&self, typ: &Traversal) -> Vec<&T> {
+        let mut path = Vec::with_capacity(self.size());
+        let _: ControlFlow<()> = self.tree.visit_ids(typ, |id| {
+            path.extend(self.get(id));
+            ControlFlow::Continue(())
+        });
+        path
+    }
+
+    /// In-order iterator over the values.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.traversal(&Traversal::LNR).into_iter()
+    }
+
+    /// Unlinks and reclaims the nodes marked as removed, returning how many they were.
+    pub fn sweep(&mut self) -> usize {
+        let mut marked = vec![];
+        let _: ControlFlow<()> = self.tree.visit_ids(&Traversal::LRN, |id| {
+            if !self.is_live(id) {
+                marked.push(id);
+            }
+            ControlFlow::Continue(())
+        });
+        // Unlinking leaves the ids of other nodes valid, compacting waits until the end.
+        for id in marked.iter() {
+            self.tree.unlink(*id);
+        }
+        self.tree.maybe_compact();
+        self.removed = 0;
+        marked.len()
+    }
+
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.tree.search(val).filter(|id| self.is_live(*id))
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.search(val).is_some()
+    }
+
+    /// Inserts `val`, returning the id of its node, a revived one if `val` was removed,
+    /// or the existing one if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        match self.tree.search(&val) {
+            Some(id) if !self.is_live(id) => {
+                *self.tree.get_mut(id).expect("linked node") = val;
+                self.tree.set_meta(id, false);
+                self.removed -= 1;
+                id
+            }
+            Some(id) => id,
+            None => self.tree.insert(val),
+        }
+    }
+
+    /// Marks the value as removed, returning whether it was present.
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.search(val) {
+            Some(id) => {
+                self.tree.set_meta(id, true);
+                self.removed += 1;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[test]
+fn lazy_delete_and_sweep() {
+    let mut t = LazyTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    let id = t.search(&3).unwrap();
+    assert!(t.delete(&3));
+    assert!(t.delete(&5));
+    assert!(!t.delete(&3));
+    assert_eq!((t.size(), t.removed()), (5, 2));
+    assert!(!t.contains(&3));
+    assert_eq!(t.get(id), None);
+    assert!(t.iter().copied().eq(vec![1, 4, 7, 8, 9]));
+    assert_eq!(t.traversal(&Traversal::NLR), [&1, &4, &8, &7, &9]);
+
+    // Revives the node in place.
+    assert_eq!(t.insert(3), id);
+    assert_eq!(t.removed(), 1);
+
+    assert_eq!(t.sweep(), 1);
+    assert_eq!((t.size(), t.removed()), (6, 0));
+    assert_eq!(t.tree.size(), 6);
+    assert!(t.iter().copied().eq(vec![1, 3, 4, 7, 8, 9]));
+    assert_eq!(t.search(&3), Some(id));
+}
+
+#[test]
+fn lazy_sweep_everything() {
+    let mut t = LazyTree::from_vec((0..100).collect());
+    for val in 0..100 {
+        assert!(t.delete(&val));
+    }
+    assert_eq!(t.size(), 0);
+    assert_eq!(t.sweep(), 100);
+    assert_eq!(t.tree.size(), 0);
+    assert_eq!(t.insert(1), 0);
+    assert!(t.iter().copied().eq(vec![1]));
+}
@@ -1 +1,45 @@
+// Unsafe code is only allowed where optional features need it, and audited there.
+#![cfg_attr(
+    not(any(feature = "mmap", feature = "unsafe-fast")),
+    forbid(unsafe_code)
+)]
+#![cfg_attr(any(feature = "mmap", feature = "unsafe-fast"), deny(unsafe_code))]
+
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
 pub mod arena;
+pub mod augment;
+pub mod avl;
+pub mod btree;
+pub mod builder;
+pub mod compare;
+pub mod counted;
+pub mod display;
+pub mod fenwick;
+pub mod interval;
+pub mod json;
+pub mod kdtree;
+pub mod lazy;
+pub mod map;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod multimap;
+pub mod persistent;
+pub mod pool;
+pub mod rbtree;
+pub mod scapegoat;
+pub mod segment;
+#[cfg(feature = "serde")]
+mod serde_impl;
+pub mod set;
+pub mod skiplist;
+mod slots;
+pub mod soa;
+pub mod splay;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod threaded;
+pub mod treap;
+pub mod trie;
+pub mod validate;
+pub mod wbt;
@@ -0,0 +1,205 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::Comparator;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Orders map entries by their keys only.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ByKey;
+
+impl<K, V> Comparator<(K, V)> for ByKey
+where
+    K: Ord,
+{
+    fn compare(&self, a: &(K, V), b: &(K, V)) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+}
+
+/// Ordered key-value map on top of `ArenaTree`.
+#[derive(Debug)]
+pub struct ArenaMap<K, V> {
+    pub(crate) tree: ArenaTree<(K, V), ByKey>,
+}
+
+impl<K, V> Default for ArenaMap<K, V> {
+    fn default() -> Self {
+        Self {
+            tree: ArenaTree::with_comparator(ByKey),
+        }
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for ArenaMap<K, V>
+where
+    K: Ord,
+{
+    /// Links the pairs in key order, balanced, see `ArenaTree::from_sorted_iter`.
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Self {
+            tree: ArenaTree::from_sorted_iter_with(ByKey, map),
+        }
+    }
+}
+
+impl<K, V> From<ArenaMap<K, V>> for BTreeMap<K, V>
+where
+    K: Ord,
+{
+    fn from(map: ArenaMap<K, V>) -> Self {
+        map.tree.into_sorted_vec().into_iter().collect()
+    }
+}
+
+impl<K, V> ArenaMap<K, V>
+where
+    K: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn search<Q>(&self, key: &Q) -> Option<NodeId>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.search_by(|(k, _)| key.cmp(k.borrow()))
+    }
+
+    /// Inserts the pair, returning the previous value of the key if any.
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        // Entries compare by key, so the new one locates its own slot in a single walk.
+        let entry = (key, val);
+        match self.tree.locate(&entry) {
+            Ok(id) => {
+                let old = self.tree.get_mut(id).unwrap();
+                Some(std::mem::replace(&mut old.1, entry.1))
+            }
+            Err(pos) => {
+                self.tree.attach(pos, entry);
+                None
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).is_some()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let id = self.search(key)?;
+        self.tree.get(id).map(|(_, v)| v)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let id = self.search(key)?;
+        self.tree.get_mut(id).map(|(_, v)| v)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let id = self.search(key)?;
+        Some(self.tree.remove_id(id).1)
+    }
+
+    /// Iterates over the pairs in key order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> {
+        self.tree.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+#[test]
+fn map_insert_get() {
+    let mut m = ArenaMap::new();
+    assert_eq!(m.insert(2, "two"), None);
+    assert_eq!(m.insert(1, "one"), None);
+    assert_eq!(m.insert(3, "three"), None);
+    assert_eq!(m.insert(2, "TWO"), Some("two"));
+    assert_eq!(m.len(), 3);
+
+    assert_eq!(m.get(&2), Some(&"TWO"));
+    assert_eq!(m.get(&4), None);
+    assert!(m.contains_key(&1));
+
+    *m.get_mut(&1).unwrap() = "ONE";
+    assert_eq!(m.get(&1), Some(&"ONE"));
+
+    println!("map: {:?}", m);
+}
+
+#[test]
+fn map_remove_and_iter() {
+    let mut m = ArenaMap::new();
+    for (k, v) in [(4, 'd'), (2, 'b'), (6, 'f'), (1, 'a'), (3, 'c'), (5, 'e')] {
+        m.insert(k, v);
+    }
+    assert_eq!(m.remove(&4), Some('d'));
+    assert_eq!(m.remove(&4), None);
+    assert_eq!(m.len(), 5);
+
+    assert_eq!(
+        m.iter().collect::<Vec<_>>(),
+        vec![(&1, &'a'), (&2, &'b'), (&3, &'c'), (&5, &'e'), (&6, &'f')]
+    );
+    assert_eq!(
+        m.keys().rev().copied().collect::<Vec<_>>(),
+        vec![6, 5, 3, 2, 1]
+    );
+    assert_eq!(m.values().collect::<String>(), "abcef");
+}
+
+#[test]
+fn map_borrow_lookup() {
+    let mut m = ArenaMap::new();
+    m.insert("one".to_string(), 1);
+    m.insert("two".to_string(), 2);
+
+    assert_eq!(m.get("one"), Some(&1));
+    assert!(m.contains_key("two"));
+    *m.get_mut("two").unwrap() += 20;
+    assert_eq!(m.remove("two"), Some(22));
+    assert!(!m.contains_key("two"));
+}
+
+#[test]
+fn map_btreemap_conversions() {
+    let map: BTreeMap<_, _> = (0..100).map(|k| (k * 37 % 100, k)).collect();
+    let m = ArenaMap::from(map.clone());
+    assert_eq!(m.len(), 100);
+    assert_eq!(m.get(&37), Some(&1));
+    assert!(m.tree.is_balanced());
+    assert_eq!(BTreeMap::from(m), map);
+}
@@ -0,0 +1,455 @@
+//! Binary search tree stored in a memory-mapped file.
+//!
+//! The file starts with a header, followed by fixed-size node records:
+//!
+//! ```text
+//! header: magic (8) | value size: u32 | reserved: u32 | len: u64 | root: u64
+//! record: value (T::SIZE) | left: u64 | right: u64
+//! ```
+//!
+//! Integers are little-endian and `u64::MAX` marks a missing link.
+//! Records are kept dense, a deletion moves the last record into the freed one.
+
+use memmap2::MmapMut;
+use std::cmp::Ordering;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"BSTMMAP1";
+const HEADER_LEN: usize = 32;
+const NIL: u64 = u64::MAX;
+const INITIAL_CAPACITY: u64 = 64;
+
+/// Values storable in a `MmapTree`, encoded into exactly `SIZE` bytes.
+pub trait Record: Ord + Sized {
+    const SIZE: usize;
+
+    fn encode(&self, buf: &mut [u8]);
+
+    fn decode(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_record {
+    ($($t: ty),*) => {
+        $(
+            impl Record for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn encode(&self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(buf: &[u8]) -> Self {
+                    <$t>::from_le_bytes(buf.try_into().expect("record size"))
+                }
+            }
+        )*
+    };
+}
+
+impl_record!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl<const N: usize> Record for [u8; N] {
+    const SIZE: usize = N;
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        buf.try_into().expect("record size")
+    }
+}
+
+#[derive(Debug)]
+pub enum MmapError {
+    Io(io::Error),
+    /// The file doesn't start with the expected magic bytes.
+    BadMagic,
+    /// The file stores values of another size.
+    ValueSize,
+    /// The file is shorter than its header claims.
+    Truncated,
+    /// A link points out of the records, or the links don't form a tree.
+    BadLink,
+    /// The in-order sequence of values is not strictly increasing.
+    OutOfOrder,
+}
+
+impl fmt::Display for MmapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MmapError::Io(err) => write!(f, "{}", err),
+            MmapError::BadMagic => write!(f, "not a tree file"),
+            MmapError::ValueSize => write!(f, "value size mismatch"),
+            MmapError::Truncated => write!(f, "file is truncated"),
+            MmapError::BadLink => write!(f, "links don't form a tree"),
+            MmapError::OutOfOrder => write!(f, "values violate the BST ordering"),
+        }
+    }
+}
+
+impl Error for MmapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MmapError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MmapError {
+    fn from(err: io::Error) -> Self {
+        MmapError::Io(err)
+    }
+}
+
+fn read_u64(buf: &[u8]) -> u64 {
+    u64::from_le_bytes(buf[..8].try_into().expect("8 bytes"))
+}
+
+fn write_u64(buf: &mut [u8], val: u64) {
+    buf[..8].copy_from_slice(&val.to_le_bytes());
+}
+
+fn link(val: u64) -> Option<u64> {
+    if val == NIL {
+        None
+    } else {
+        Some(val)
+    }
+}
+
+/// Maps the whole file.
+#[allow(unsafe_code)]
+fn map(file: &File) -> io::Result<MmapMut> {
+    // SAFETY: the mapping is only sound while no one else truncates or writes the file,
+    // which `MmapTree` documents as a requirement of its users.
+    unsafe { MmapMut::map_mut(file) }
+}
+
+/// Persistent binary search tree whose nodes live in a memory-mapped file,
+/// so it may outgrow the RAM and outlive the process.
+///
+/// Changes reach the file lazily; `flush` forces them out.
+/// The file must not be modified by anything else while it is open.
+#[derive(Debug)]
+pub struct MmapTree<T> {
+    file: File,
+    map: MmapMut,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Record> MmapTree<T> {
+    /// Creates an empty tree at `path`, replacing any existing file.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, MmapError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(HEADER_LEN as u64 + INITIAL_CAPACITY * Self::stride() as u64)?;
+        let mut map = map(&file)?;
+        map[..8].copy_from_slice(MAGIC);
+        map[8..12].copy_from_slice(&(T::SIZE as u32).to_le_bytes());
+        write_u64(&mut map[16..], 0);
+        write_u64(&mut map[24..], NIL);
+        Ok(Self {
+            file,
+            map,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Opens the tree stored at `path`, validating the header and the whole tree.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MmapError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        if file.metadata()?.len() < HEADER_LEN as u64 {
+            return Err(MmapError::Truncated);
+        }
+        let map = map(&file)?;
+        let t = Self {
+            file,
+            map,
+            _marker: PhantomData,
+        };
+        t.validate()?;
+        Ok(t)
+    }
+
+    /// Writes the pending changes to the file.
+    pub fn flush(&self) -> Result<(), MmapError> {
+        Ok(self.map.flush()?)
+    }
+
+    pub fn len(&self) -> u64 {
+        read_u64(&self.map[16..])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of records the file holds before it has to grow.
+    pub fn capacity(&self) -> u64 {
+        ((self.map.len() - HEADER_LEN) / Self::stride()) as u64
+    }
+
+    pub fn contains(&self, val: &T) -> bool {
+        self.find(val).1.is_some()
+    }
+
+    /// Inserts `val`, returning whether it was not present yet.
+    pub fn insert(&mut self, val: T) -> Result<bool, MmapError> {
+        let pos = match self.find(&val) {
+            (_, Some(_)) => return Ok(false),
+            (pos, None) => pos,
+        };
+        let id = self.len();
+        if id == self.capacity() {
+            self.grow()?;
+        }
+        let record = self.record_mut(id);
+        val.encode(&mut record[..T::SIZE]);
+        write_u64(&mut record[T::SIZE..], NIL);
+        write_u64(&mut record[T::SIZE + 8..], NIL);
+        self.set_link(pos, Some(id));
+        self.set_len(id + 1);
+        Ok(true)
+    }
+
+    /// Deletes `val`, returning whether it was present.
+    pub fn delete(&mut self, val: &T) -> bool {
+        let (mut pos, mut id) = match self.find(val) {
+            (pos, Some(id)) => (pos, id),
+            (_, None) => return false,
+        };
+        if let (Some(_), Some(right_id)) = (self.left(id), self.right(id)) {
+            let mut successor = (Some((id, false)), right_id);
+            while let Some(left_id) = self.left(successor.1) {
+                successor = (Some((successor.1, true)), left_id);
+            }
+            let (src, dest) = (self.offset(successor.1), self.offset(id));
+            self.map.copy_within(src..src + T::SIZE, dest);
+            pos = successor.0;
+            id = successor.1;
+        }
+        let child = self.left(id).or_else(|| self.right(id));
+        self.set_link(pos, child);
+
+        let last = self.len() - 1;
+        if last != id {
+            let (last_pos, _) = self.find(&self.val(last));
+            let (src, dest) = (self.offset(last), self.offset(id));
+            self.map.copy_within(src..src + Self::stride(), dest);
+            self.set_link(last_pos, Some(id));
+        }
+        self.set_len(last);
+        true
+    }
+
+    /// Iterates over the values in order.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        let mut stack = vec![];
+        let mut cur = self.root();
+        std::iter::from_fn(move || {
+            while let Some(id) = cur {
+                stack.push(id);
+                cur = self.left(id);
+            }
+            let id = stack.pop()?;
+            cur = self.right(id);
+            Some(self.val(id))
+        })
+    }
+
+    fn stride() -> usize {
+        T::SIZE + 16
+    }
+
+    fn offset(&self, id: u64) -> usize {
+        HEADER_LEN + id as usize * Self::stride()
+    }
+
+    fn record(&self, id: u64) -> &[u8] {
+        let start = self.offset(id);
+        &self.map[start..start + Self::stride()]
+    }
+
+    fn record_mut(&mut self, id: u64) -> &mut [u8] {
+        let start = self.offset(id);
+        &mut self.map[start..start + Self::stride()]
+    }
+
+    fn val(&self, id: u64) -> T {
+        T::decode(&self.record(id)[..T::SIZE])
+    }
+
+    fn root(&self) -> Option<u64> {
+        link(read_u64(&self.map[24..]))
+    }
+
+    fn left(&self, id: u64) -> Option<u64> {
+        link(read_u64(&self.record(id)[T::SIZE..]))
+    }
+
+    fn right(&self, id: u64) -> Option<u64> {
+        link(read_u64(&self.record(id)[T::SIZE + 8..]))
+    }
+
+    fn set_len(&mut self, len: u64) {
+        write_u64(&mut self.map[16..], len);
+    }
+
+    /// Points the link at `pos` to `id`, `None` meaning the root.
+    fn set_link(&mut self, pos: Option<(u64, bool)>, id: Option<u64>) {
+        let id = id.unwrap_or(NIL);
+        match pos {
+            None => write_u64(&mut self.map[24..], id),
+            Some((parent_id, true)) => write_u64(&mut self.record_mut(parent_id)[T::SIZE..], id),
+            Some((parent_id, false)) => {
+                write_u64(&mut self.record_mut(parent_id)[T::SIZE + 8..], id)
+            }
+        }
+    }
+
+    /// Finds the node equal to `val` along with the link leading to it,
+    /// or the link a new node would be put at.
+    fn find(&self, val: &T) -> (Option<(u64, bool)>, Option<u64>) {
+        let mut pos = None;
+        let mut cur = self.root();
+        while let Some(id) = cur {
+            let dir = match val.cmp(&self.val(id)) {
+                Ordering::Equal => break,
+                Ordering::Less => true,
+                Ordering::Greater => false,
+            };
+            pos = Some((id, dir));
+            cur = if dir { self.left(id) } else { self.right(id) };
+        }
+        (pos, cur)
+    }
+
+    /// Doubles the file and maps it again.
+    fn grow(&mut self) -> Result<(), MmapError> {
+        self.map.flush()?;
+        let capacity = self.capacity().max(INITIAL_CAPACITY) * 2;
+        self.file
+            .set_len(HEADER_LEN as u64 + capacity * Self::stride() as u64)?;
+        self.map = map(&self.file)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), MmapError> {
+        if &self.map[..8] != MAGIC {
+            return Err(MmapError::BadMagic);
+        }
+        if self.map[8..12] != (T::SIZE as u32).to_le_bytes() {
+            return Err(MmapError::ValueSize);
+        }
+        let len = self.len();
+        if len > self.capacity() {
+            return Err(MmapError::Truncated);
+        }
+        let mut seen = vec![false; len as usize];
+        let mut stack = vec![];
+        let mut cur = self.root();
+        let mut prev: Option<T> = None;
+        let mut count = 0;
+        loop {
+            while let Some(id) = cur {
+                if id >= len || seen[id as usize] {
+                    return Err(MmapError::BadLink);
+                }
+                seen[id as usize] = true;
+                stack.push(id);
+                cur = self.left(id);
+            }
+            let id = match stack.pop() {
+                Some(id) => id,
+                None => break,
+            };
+            let val = self.val(id);
+            if let Some(prev) = &prev {
+                if prev >= &val {
+                    return Err(MmapError::OutOfOrder);
+                }
+            }
+            prev = Some(val);
+            count += 1;
+            cur = self.right(id);
+        }
+        if count != len {
+            return Err(MmapError::BadLink);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("bst-{}-{}.tree", name, std::process::id()))
+}
+
+#[test]
+fn mmap_persists_across_open() {
+    let path = temp_path("persist");
+    {
+        let mut t = MmapTree::<u32>::create(&path).unwrap();
+        for val in (0..200).map(|x| (x * 37) % 200) {
+            assert!(t.insert(val).unwrap());
+        }
+        assert!(!t.insert(5).unwrap());
+        assert!(t.capacity() >= 200);
+        for val in (0..200).filter(|x| x % 3 == 0) {
+            assert!(t.delete(&val));
+        }
+        assert!(!t.delete(&3));
+        t.flush().unwrap();
+    }
+    let t = MmapTree::<u32>::open(&path).unwrap();
+    let expected: Vec<u32> = (0..200).filter(|x| x % 3 != 0).collect();
+    assert_eq!(t.len(), expected.len() as u64);
+    assert_eq!(t.iter().collect::<Vec<_>>(), expected);
+    assert!(t.contains(&4));
+    assert!(!t.contains(&6));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn mmap_open_validates() {
+    let path = temp_path("validate");
+    {
+        let mut t = MmapTree::<[u8; 2]>::create(&path).unwrap();
+        t.insert(*b"bb").unwrap();
+        t.insert(*b"aa").unwrap();
+        t.flush().unwrap();
+    }
+    assert!(matches!(
+        MmapTree::<u32>::open(&path),
+        Err(MmapError::ValueSize)
+    ));
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    let left = HEADER_LEN + 2 + 16;
+    bytes[left..left + 2].copy_from_slice(b"zz");
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(
+        MmapTree::<[u8; 2]>::open(&path),
+        Err(MmapError::OutOfOrder)
+    ));
+
+    bytes[..8].copy_from_slice(b"NOTATREE");
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(
+        MmapTree::<[u8; 2]>::open(&path),
+        Err(MmapError::BadMagic)
+    ));
+    std::fs::remove_file(&path).unwrap();
+}
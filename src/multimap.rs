@@ -0,0 +1,115 @@
+use crate::map::ArenaMap;
+use std::borrow::Borrow;
+
+/// Ordered map where equal keys accumulate their values in insertion order.
+#[derive(Debug)]
+pub struct ArenaMultiMap<K, V> {
+    map: ArenaMap<K, Vec<V>>,
+    len: usize,
+}
+
+impl<K, V> Default for ArenaMultiMap<K, V> {
+    fn default() -> Self {
+        Self {
+            map: ArenaMap::default(),
+            len: 0,
+        }
+    }
+}
+
+impl<K, V> ArenaMultiMap<K, V>
+where
+    K: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of values, across all keys.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn keys_len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn insert(&mut self, key: K, val: V) {
+        self.len += 1;
+        match self.map.get_mut(&key) {
+            Some(vals) => vals.push(val),
+            None => {
+                self.map.insert(key, vec![val]);
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Values of the key, empty if the key is absent.
+    pub fn get_all<Q>(&self, key: &Q) -> &[V]
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.map.get(key).map_or(&[], |vals| vals.as_slice())
+    }
+
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let vals = self.map.remove(key).unwrap_or_default();
+        self.len -= vals.len();
+        vals
+    }
+
+    /// Iterates over the keys with their values in key order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &[V])> {
+        self.map.iter().map(|(k, vals)| (k, vals.as_slice()))
+    }
+}
+
+#[test]
+fn multimap_insert_get_all() {
+    let mut m = ArenaMultiMap::new();
+    m.insert("dev", "alice");
+    m.insert("ops", "bob");
+    m.insert("dev", "carol");
+    assert_eq!(m.len(), 3);
+    assert_eq!(m.keys_len(), 2);
+
+    assert_eq!(m.get_all("dev"), &["alice", "carol"]);
+    assert_eq!(m.get_all("ops"), &["bob"]);
+    assert!(m.get_all("qa").is_empty());
+
+    assert_eq!(
+        m.iter().collect::<Vec<_>>(),
+        vec![(&"dev", &["alice", "carol"][..]), (&"ops", &["bob"][..])]
+    );
+
+    println!("map: {:?}", m);
+}
+
+#[test]
+fn multimap_remove_all() {
+    let mut m = ArenaMultiMap::new();
+    for (k, v) in [(1, 'a'), (2, 'b'), (1, 'c')] {
+        m.insert(k, v);
+    }
+    assert_eq!(m.remove_all(&1), vec!['a', 'c']);
+    assert!(m.remove_all(&1).is_empty());
+    assert!(!m.contains_key(&1));
+    assert_eq!(m.len(), 1);
+}
@@ -0,0 +1,367 @@
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+type Subtree<T> = Option<Rc<PNode<T>>>;
+
+#[derive(Debug)]
+struct PNode<T> {
+    val: T,
+    left: Subtree<T>,
+    right: Subtree<T>,
+    height: usize,
+}
+
+fn height<T>(tree: &Subtree<T>) -> usize {
+    tree.as_ref().map_or(0, |node| node.height)
+}
+
+impl<T> PNode<T> {
+    fn new(val: T, left: Subtree<T>, right: Subtree<T>) -> Subtree<T> {
+        let height = height(&left).max(height(&right)) + 1;
+        Some(Rc::new(Self {
+            val,
+            left,
+            right,
+            height,
+        }))
+    }
+}
+
+impl<T> PNode<T>
+where
+    T: Clone,
+{
+    /// New node over subtrees whose heights differ by at most 2, rotated back
+    /// into AVL balance. Rotated nodes are copied, their subtrees shared.
+    fn balance(val: T, left: Subtree<T>, right: Subtree<T>) -> Subtree<T> {
+        let (hl, hr) = (height(&left), height(&right));
+        if hl > hr + 1 {
+            let l = left.as_ref().expect("taller subtree");
+            if height(&l.left) >= height(&l.right) {
+                let right = Self::new(val, l.right.clone(), right);
+                Self::new(l.val.clone(), l.left.clone(), right)
+            } else {
+                let lr = l.right.as_ref().expect("taller inner subtree");
+                let left = Self::new(l.val.clone(), l.left.clone(), lr.left.clone());
+                let right = Self::new(val, lr.right.clone(), right);
+                Self::new(lr.val.clone(), left, right)
+            }
+        } else if hr > hl + 1 {
+            let r = right.as_ref().expect("taller subtree");
+            if height(&r.right) >= height(&r.left) {
+                let left = Self::new(val, left, r.left.clone());
+                Self::new(r.val.clone(), left, r.right.clone())
+            } else {
+                let rl = r.left.as_ref().expect("taller inner subtree");
+                let left = Self::new(val, left, rl.left.clone());
+                let right = Self::new(r.val.clone(), rl.right.clone(), r.right.clone());
+                Self::new(rl.val.clone(), left, right)
+            }
+        } else {
+            Self::new(val, left, right)
+        }
+    }
+}
+
+/// Immutable binary search tree, where `insert` and `delete` return a new version
+/// and leave the tree they are called on untouched.
+///
+/// Only the nodes on the path to the change are copied, along with the few
+/// a rebalancing rotation moves, and the new version shares every other node
+/// with the previous one, so keeping many versions around is cheap.
+/// Copying a node clones its value. Cloning a version is O(1).
+///
+/// Versions are kept AVL-balanced, so `insert`, `delete` and lookups take
+/// O(log n) whatever the insertion order, and recurse no deeper than that.
+/// Unlike the other trees, nodes are reference-counted rather than kept in an arena,
+/// as any number of versions may share them and each is freed with the last one.
+#[derive(Debug)]
+pub struct PersistentTree<T, C = Natural> {
+    root: Subtree<T>,
+    size: usize,
+    cmp: C,
+}
+
+impl<T> Default for PersistentTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T, C> Clone for PersistentTree<T, C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            size: self.size,
+            cmp: self.cmp.clone(),
+        }
+    }
+}
+
+impl<T> PersistentTree<T>
+where
+    T: Ord + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        v.into_iter().fold(Self::default(), |t, val| t.insert(val))
+    }
+}
+
+/// In-order iterator over the values of a `PersistentTree`.
+pub struct Iter<'a, T> {
+    stack: Vec<&'a PNode<T>>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut cur: &'a Subtree<T>) {
+        while let Some(node) = cur {
+            self.stack.push(node);
+            cur = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        Some(&node.val)
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a PersistentTree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C> PersistentTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            root: None,
+            size: 0,
+            cmp,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter { stack: vec![] };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    /// Whether both versions are the very same tree, without comparing values.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T, C> PersistentTree<T, C>
+where
+    T: Clone,
+    C: Comparator<T> + Clone,
+{
+    pub fn get<Q>(&self, val: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut cur = &self.root;
+        while let Some(node) = cur {
+            cur = match self.cmp.compare(val, node.val.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Equal => return Some(&node.val),
+                Ordering::Greater => &node.right,
+            };
+        }
+        None
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.get(val).is_some()
+    }
+
+    fn with_root(&self, root: Subtree<T>, size: usize) -> Self {
+        Self {
+            root,
+            size,
+            cmp: self.cmp.clone(),
+        }
+    }
+
+    /// New version with `val` inserted, the same tree if an equal value is present.
+    pub fn insert(&self, val: T) -> Self {
+        match self.insert_at(&self.root, val) {
+            Some(root) => self.with_root(root, self.size + 1),
+            None => self.clone(),
+        }
+    }
+
+    /// Copies the path down to where `val` goes, `None` if an equal value is present.
+    fn insert_at(&self, tree: &Subtree<T>, val: T) -> Option<Subtree<T>> {
+        let node = match tree {
+            Some(node) => node,
+            None => return Some(PNode::new(val, None, None)),
+        };
+        Some(match self.cmp.compare(&val, &node.val) {
+            Ordering::Less => {
+                let left = self.insert_at(&node.left, val)?;
+                PNode::balance(node.val.clone(), left, node.right.clone())
+            }
+            Ordering::Equal => return None,
+            Ordering::Greater => {
+                let right = self.insert_at(&node.right, val)?;
+                PNode::balance(node.val.clone(), node.left.clone(), right)
+            }
+        })
+    }
+
+    /// New version without `val`, the same tree if it isn't present.
+    pub fn delete<Q>(&self, val: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.delete_at(&self.root, val) {
+            Some(root) => self.with_root(root, self.size - 1),
+            None => self.clone(),
+        }
+    }
+
+    /// Copies the path down to `val`, `None` if it isn't present.
+    fn delete_at<Q>(&self, tree: &Subtree<T>, val: &Q) -> Option<Subtree<T>>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let node = tree.as_ref()?;
+        Some(match self.cmp.compare(val, node.val.borrow()) {
+            Ordering::Less => {
+                let left = self.delete_at(&node.left, val)?;
+                PNode::balance(node.val.clone(), left, node.right.clone())
+            }
+            Ordering::Greater => {
+                let right = self.delete_at(&node.right, val)?;
+                PNode::balance(node.val.clone(), node.left.clone(), right)
+            }
+            Ordering::Equal => match (&node.left, &node.right) {
+                (None, _) => node.right.clone(),
+                (_, None) => node.left.clone(),
+                (Some(_), Some(right)) => {
+                    // Replaced by its successor, the smallest value of the right subtree.
+                    let (successor, right) = Self::pop_min(right);
+                    PNode::balance(successor, node.left.clone(), right)
+                }
+            },
+        })
+    }
+
+    /// Copies the path down to the smallest value, returning it with the subtree left.
+    fn pop_min(node: &Rc<PNode<T>>) -> (T, Subtree<T>) {
+        match &node.left {
+            None => (node.val.clone(), node.right.clone()),
+            Some(left) => {
+                let (min, left) = Self::pop_min(left);
+                (
+                    min,
+                    PNode::balance(node.val.clone(), left, node.right.clone()),
+                )
+            }
+        }
+    }
+}
+
+#[test]
+fn persistent_versions() {
+    let v1 = PersistentTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    let v2 = v1.insert(6);
+    let v3 = v2.delete(&3);
+    assert!(v1.iter().copied().eq(vec![1, 3, 4, 5, 7, 8, 9]));
+    assert!(v2.iter().copied().eq(vec![1, 3, 4, 5, 6, 7, 8, 9]));
+    assert!(v3.iter().copied().eq(vec![1, 4, 5, 6, 7, 8, 9]));
+    assert_eq!((v1.size(), v2.size(), v3.size()), (7, 8, 7));
+    assert!(v1.contains(&3) && !v1.contains(&6));
+    assert!(!v3.contains(&3) && v3.get(&6) == Some(&6));
+
+    assert!(v2.insert(6).ptr_eq(&v2));
+    assert!(v3.delete(&3).ptr_eq(&v3));
+    assert!(!v2.ptr_eq(&v1));
+}
+
+#[test]
+fn persistent_structural_sharing() {
+    let v1 = PersistentTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    let v2 = v1.insert(6);
+    let (r1, r2) = (v1.root.as_ref().unwrap(), v2.root.as_ref().unwrap());
+    // The path 5 > 8 > 7 is copied, the subtrees of 3 and 9 are shared.
+    assert!(Rc::ptr_eq(
+        r1.left.as_ref().unwrap(),
+        r2.left.as_ref().unwrap()
+    ));
+    let (n1, n2) = (r1.right.as_ref().unwrap(), r2.right.as_ref().unwrap());
+    assert!(!Rc::ptr_eq(n1, n2));
+    assert!(Rc::ptr_eq(
+        n1.right.as_ref().unwrap(),
+        n2.right.as_ref().unwrap()
+    ));
+
+    let v3 = v2.delete(&5);
+    assert_eq!(v3.root.as_ref().unwrap().val, 6);
+    assert!(Rc::ptr_eq(
+        r2.left.as_ref().unwrap(),
+        v3.root.as_ref().unwrap().left.as_ref().unwrap()
+    ));
+    drop(v1);
+    assert!(v2.iter().copied().eq(vec![1, 3, 4, 5, 6, 7, 8, 9]));
+}
+
+#[test]
+fn persistent_balanced() {
+    let mut t = PersistentTree::new();
+    for val in 0..100_000 {
+        t = t.insert(val);
+    }
+    let v1 = t.clone();
+    for val in (0..100_000).step_by(2) {
+        t = t.delete(&val);
+    }
+    // AVL trees are at most 1.44 log2(n) high.
+    assert!(height(&v1.root) <= 24);
+    assert!(height(&t.root) <= 23);
+    assert!(v1.iter().copied().eq(0..100_000));
+    assert!(t.iter().copied().eq((1..100_000).step_by(2)));
+}
@@ -0,0 +1,420 @@
+use crate::arena::{Link, NodeId};
+use crate::compare::{Comparator, Natural};
+use crate::slots::Slots;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+#[derive(Debug)]
+struct PoolNode<T> {
+    val: T,
+    parent: Link,
+    left: Link,
+    right: Link,
+}
+
+/// Arena shared by many `PoolTree`s.
+///
+/// Trees don't own their nodes, they take the pool on every operation instead.
+/// Slots freed by one tree are reused by the others,
+/// and subtrees move between trees by relinking, without copying values.
+#[derive(Debug)]
+pub struct NodePool<T> {
+    arena: Slots<PoolNode<T>>,
+}
+
+impl<T> Default for NodePool<T> {
+    fn default() -> Self {
+        Self {
+            arena: Slots::default(),
+        }
+    }
+}
+
+impl<T> NodePool<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            arena: Slots::with_capacity(capacity),
+        }
+    }
+
+    /// Number of nodes allocated, across all trees.
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.arena.get(id).map(|node| &node.val)
+    }
+
+    fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.arena[id].parent.get()
+    }
+
+    fn left(&self, id: NodeId) -> Option<NodeId> {
+        self.arena[id].left.get()
+    }
+
+    fn right(&self, id: NodeId) -> Option<NodeId> {
+        self.arena[id].right.get()
+    }
+
+    fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        self.arena[id].parent = Link::new(parent);
+    }
+
+    fn set_left(&mut self, id: NodeId, left: Option<NodeId>) {
+        self.arena[id].left = Link::new(left);
+    }
+
+    fn set_right(&mut self, id: NodeId, right: Option<NodeId>) {
+        self.arena[id].right = Link::new(right);
+    }
+
+    fn subtree_size(&self, id: NodeId) -> usize {
+        let mut size = 0;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            size += 1;
+            stack.extend(self.left(id));
+            stack.extend(self.right(id));
+        }
+        size
+    }
+}
+
+/// Binary search tree allocating its nodes from a `NodePool`.
+///
+/// Every operation must be given the pool the tree was built in.
+/// Dropping a tree leaves its nodes allocated; `clear` returns them to the pool.
+#[derive(Debug)]
+pub struct PoolTree<T, C = Natural> {
+    root: Option<NodeId>,
+    len: usize,
+    cmp: C,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for PoolTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> PoolTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, C> PoolTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            root: None,
+            len: 0,
+            cmp,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn root(&self) -> Option<NodeId> {
+        self.root
+    }
+
+    /// Iterates over the values in order.
+    pub fn iter<'a>(&self, pool: &'a NodePool<T>) -> impl Iterator<Item = &'a T> {
+        let mut stack = vec![];
+        let mut cur = self.root;
+        std::iter::from_fn(move || {
+            while let Some(id) = cur {
+                stack.push(id);
+                cur = pool.left(id);
+            }
+            let id = stack.pop()?;
+            cur = pool.right(id);
+            pool.get(id)
+        })
+    }
+
+    /// Frees all nodes of the tree back to the pool.
+    pub fn clear(&mut self, pool: &mut NodePool<T>) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(id) = stack.pop() {
+            stack.extend(pool.left(id));
+            stack.extend(pool.right(id));
+            pool.arena.remove(id);
+        }
+        self.len = 0;
+    }
+
+    /// Points the link of `parent` that leads to `old` at `new` instead.
+    fn replace_child(
+        &mut self,
+        pool: &mut NodePool<T>,
+        parent: Option<NodeId>,
+        old: NodeId,
+        new: Option<NodeId>,
+    ) {
+        match parent {
+            None => self.root = new,
+            Some(parent_id) => {
+                if pool.left(parent_id) == Some(old) {
+                    pool.set_left(parent_id, new);
+                } else {
+                    pool.set_right(parent_id, new);
+                }
+            }
+        }
+    }
+
+    /// Unlinks the node and frees its slot.
+    fn remove_id(&mut self, pool: &mut NodePool<T>, id: NodeId) -> T {
+        let parent = pool.parent(id);
+        match (pool.left(id), pool.right(id)) {
+            (Some(left_id), Some(right_id)) => {
+                let mut successor = right_id;
+                while let Some(left_id) = pool.left(successor) {
+                    successor = left_id;
+                }
+                let successor_right = pool.right(successor);
+                let successor_parent = pool.parent(successor);
+                if let Some(id) = successor_right {
+                    pool.set_parent(id, successor_parent);
+                }
+                self.replace_child(pool, successor_parent, successor, successor_right);
+
+                let right_id = pool.right(id);
+                pool.set_left(successor, Some(left_id));
+                pool.set_right(successor, right_id);
+                pool.set_parent(left_id, Some(successor));
+                if let Some(right_id) = right_id {
+                    pool.set_parent(right_id, Some(successor));
+                }
+                pool.set_parent(successor, parent);
+                self.replace_child(pool, parent, id, Some(successor));
+            }
+            (left_id, right_id) => {
+                let child = left_id.or(right_id);
+                if let Some(child_id) = child {
+                    pool.set_parent(child_id, parent);
+                }
+                self.replace_child(pool, parent, id, child);
+            }
+        }
+        self.len -= 1;
+        pool.arena.remove(id).expect("vacant slot").val
+    }
+}
+
+impl<T, C> PoolTree<T, C>
+where
+    C: Comparator<T>,
+{
+    /// Finds the node equal to `val`, or the position a new node would be linked at.
+    fn locate<Q>(&self, pool: &NodePool<T>, val: &Q) -> Result<NodeId, Option<(NodeId, bool)>>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut cur = match self.root {
+            Some(id) => id,
+            None => return Err(None),
+        };
+        loop {
+            let (next, dir) = match self.cmp.compare(val, pool.arena[cur].val.borrow()) {
+                Ordering::Equal => return Ok(cur),
+                Ordering::Less => (pool.left(cur), true),
+                Ordering::Greater => (pool.right(cur), false),
+            };
+            match next {
+                Some(id) => cur = id,
+                None => return Err(Some((cur, dir))),
+            }
+        }
+    }
+
+    /// Links the subtree rooted at `id` at `pos`, `None` meaning the root.
+    fn link(&mut self, pool: &mut NodePool<T>, pos: Option<(NodeId, bool)>, id: NodeId) {
+        pool.set_parent(id, pos.map(|(parent_id, _)| parent_id));
+        match pos {
+            None => self.root = Some(id),
+            Some((parent_id, true)) => pool.set_left(parent_id, Some(id)),
+            Some((parent_id, false)) => pool.set_right(parent_id, Some(id)),
+        }
+    }
+
+    pub fn search<Q>(&self, pool: &NodePool<T>, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.locate(pool, val).ok()
+    }
+
+    pub fn contains<Q>(&self, pool: &NodePool<T>, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.search(pool, val).is_some()
+    }
+
+    /// Inserts `val`, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, pool: &mut NodePool<T>, val: T) -> NodeId {
+        let pos = match self.locate(pool, &val) {
+            Ok(id) => return id,
+            Err(pos) => pos,
+        };
+        let id = pool.arena.push(PoolNode {
+            val,
+            parent: Link::NONE,
+            left: Link::NONE,
+            right: Link::NONE,
+        });
+        self.link(pool, pos, id);
+        self.len += 1;
+        id
+    }
+
+    pub fn delete<Q>(&mut self, pool: &mut NodePool<T>, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.search(pool, val) {
+            Some(id) => {
+                self.remove_id(pool, id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cuts the subtree rooted at `id` out into a tree of its own.
+    /// Its nodes stay where they are in the pool.
+    pub fn detach(&mut self, pool: &mut NodePool<T>, id: NodeId) -> Self
+    where
+        C: Clone,
+    {
+        let parent = pool.parent(id);
+        self.replace_child(pool, parent, id, None);
+        pool.set_parent(id, None);
+        let len = pool.subtree_size(id);
+        self.len -= len;
+        Self {
+            root: Some(id),
+            len,
+            cmp: self.cmp.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Links all of `other` below a single node of this tree, without copying values.
+    /// Only possible when no value of this tree lies between the smallest and
+    /// the largest value of `other`; otherwise `other` is given back.
+    pub fn graft(&mut self, pool: &mut NodePool<T>, other: Self) -> Result<(), Self> {
+        let other_root = match other.root {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let (mut min, mut max) = (other_root, other_root);
+        while let Some(id) = pool.left(min) {
+            min = id;
+        }
+        while let Some(id) = pool.right(max) {
+            max = id;
+        }
+        let pos = match (
+            self.locate(pool, &pool.arena[min].val),
+            self.locate(pool, &pool.arena[max].val),
+        ) {
+            (Err(a), Err(b)) if a == b => a,
+            _ => return Err(other),
+        };
+        self.link(pool, pos, other_root);
+        self.len += other.len;
+        Ok(())
+    }
+}
+
+#[test]
+fn pool_shared_by_trees() {
+    let mut pool = NodePool::new();
+    let mut a = PoolTree::new();
+    let mut b = PoolTree::new();
+    for val in [4, 2, 6].iter() {
+        a.insert(&mut pool, *val);
+    }
+    for val in [5, 1].iter() {
+        b.insert(&mut pool, *val);
+    }
+    assert_eq!(pool.len(), 5);
+    assert!(a.contains(&pool, &6));
+    assert!(!b.contains(&pool, &6));
+
+    let id = a.search(&pool, &4).unwrap();
+    assert!(a.delete(&mut pool, &4));
+    assert_eq!(b.insert(&mut pool, 3), id);
+    assert_eq!(a.iter(&pool).copied().collect::<Vec<_>>(), [2, 6]);
+    assert_eq!(b.iter(&pool).copied().collect::<Vec<_>>(), [1, 3, 5]);
+
+    a.clear(&mut pool);
+    assert!(a.is_empty());
+    assert_eq!(pool.len(), 3);
+}
+
+#[test]
+fn pool_detach_and_graft() {
+    let mut pool = NodePool::new();
+    let mut a = PoolTree::new();
+    let mut b = PoolTree::new();
+    for val in [50, 20, 80, 10, 30, 25].iter() {
+        a.insert(&mut pool, *val);
+    }
+    for val in [40, 22].iter() {
+        b.insert(&mut pool, *val);
+    }
+
+    let id = a.search(&pool, &20).unwrap();
+    let sub = a.detach(&mut pool, id);
+    assert_eq!(sub.size(), 4);
+    assert_eq!(a.size(), 2);
+    let sub = b.graft(&mut pool, sub).unwrap_err();
+    assert!(b.delete(&mut pool, &22));
+    b.graft(&mut pool, sub).unwrap();
+    assert_eq!(b.size(), 5);
+    assert_eq!(pool.get(id), Some(&20));
+    assert_eq!(
+        b.iter(&pool).copied().collect::<Vec<_>>(),
+        [10, 20, 25, 30, 40]
+    );
+    assert_eq!(pool.len(), 7);
+}
@@ -0,0 +1,223 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::ops::Deref;
+
+/// Scapegoat balance factor: a child may hold at most this share of its parent's subtree.
+const ALPHA: f64 = 2.0 / 3.0;
+
+/// Scapegoat variant of `ArenaTree`.
+///
+/// Nodes keep the size of their subtree as metadata and nothing else;
+/// when an insertion lands too deep, the highest unbalanced ancestor on its path
+/// is rebuilt into a perfectly balanced subtree in place, reusing the same slots,
+/// so node ids survive rebuilds too. See the balanced variants of `ArenaTree` for its API.
+#[derive(Debug, Clone)]
+pub struct ScapegoatArenaTree<T, C = Natural> {
+    tree: ArenaTree<T, C, usize>,
+    /// Largest size since the last full rebuild.
+    max_size: usize,
+}
+
+impl<T> Default for ScapegoatArenaTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> ScapegoatArenaTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+impl<T, C> Deref for ScapegoatArenaTree<T, C> {
+    type Target = ArenaTree<T, C, usize>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, C> ScapegoatArenaTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            tree: ArenaTree::with_comparator_and_meta(cmp),
+            max_size: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.max_size = 0;
+    }
+
+    fn size_of(&self, id: Option<NodeId>) -> usize {
+        id.and_then(|id| self.tree.meta(id)).copied().unwrap_or(0)
+    }
+
+    fn update(&mut self, id: NodeId) {
+        let size = 1 + self.size_of(self.tree.left_of(id)) + self.size_of(self.tree.right_of(id));
+        self.tree.set_meta(id, size);
+    }
+
+    /// Recomputes the sizes of the whole subtree at `id`.
+    fn update_subtree(&mut self, id: Option<NodeId>) -> usize {
+        match id {
+            None => 0,
+            Some(id) => {
+                let size = 1
+                    + self.update_subtree(self.tree.left_of(id))
+                    + self.update_subtree(self.tree.right_of(id));
+                self.tree.set_meta(id, size);
+                size
+            }
+        }
+    }
+
+    fn rebuild(&mut self, id: NodeId) {
+        let root = self.tree.rebuild_subtree(id);
+        self.update_subtree(Some(root));
+    }
+
+    /// Deepest depth allowed for `size` nodes.
+    fn max_depth(size: usize) -> usize {
+        ((size as f64).ln() / (1.0 / ALPHA).ln()).floor() as usize
+    }
+}
+
+impl<T, C> ScapegoatArenaTree<T, C>
+where
+    C: Comparator<T>,
+{
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.tree.search(val)
+    }
+
+    /// Inserts `val`, rebuilding a subtree if it lands too deep.
+    /// Returns the id of the new node, or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let size = self.tree.size();
+        let id = self.tree.insert(val);
+        if self.tree.size() == size {
+            return id;
+        }
+        self.max_size = self.max_size.max(self.tree.size());
+
+        let mut depth = 0;
+        let mut cur = Some(id);
+        while let Some(cur_id) = cur {
+            self.update(cur_id);
+            depth += 1;
+            cur = self.tree.parent_of(cur_id);
+        }
+        if depth - 1 > Self::max_depth(self.tree.size()) {
+            let mut child = id;
+            while let Some(parent_id) = self.tree.parent_of(child) {
+                if self.size_of(Some(child)) as f64 > ALPHA * self.size_of(Some(parent_id)) as f64 {
+                    self.rebuild(parent_id);
+                    break;
+                }
+                child = parent_id;
+            }
+        }
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Removes the value equal to `val`, returning it, and rebuilds the whole tree
+    /// once it shrank well below its largest size.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.tree.search(val)?;
+        let (val, mut cur) = self.tree.unlink(id);
+        while let Some(cur_id) = cur {
+            self.update(cur_id);
+            cur = self.tree.parent_of(cur_id);
+        }
+        if (self.tree.size() as f64) < ALPHA * self.max_size as f64 {
+            if let Some(root) = self.tree.root() {
+                self.rebuild(root);
+            }
+            self.max_size = self.tree.size();
+        }
+        Some(val)
+    }
+}
+
+#[cfg(test)]
+fn scapegoat_check<T, C>(t: &ScapegoatArenaTree<T, C>, id: Option<NodeId>) -> usize {
+    match id {
+        None => 0,
+        Some(id) => {
+            let size = 1 + scapegoat_check(t, t.left_of(id)) + scapegoat_check(t, t.right_of(id));
+            assert_eq!(t.meta(id), Some(&size));
+            size
+        }
+    }
+}
+
+#[test]
+fn scapegoat_sorted_insertions() {
+    let t = ScapegoatArenaTree::from_vec((0..1000).collect());
+    scapegoat_check(&t, t.root());
+    assert!(t.height() <= ScapegoatArenaTree::<i32>::max_depth(1000) + 1);
+    assert!(t.iter().copied().eq(0..1000));
+}
+
+#[test]
+fn scapegoat_delete_rebuilds_in_place() {
+    let mut t = ScapegoatArenaTree::from_vec((0..100).collect());
+    let id = t.search(&99).unwrap();
+    for val in 0..60 {
+        assert!(t.delete(&val));
+        scapegoat_check(&t, t.root());
+    }
+    assert!(!t.delete(&0));
+    assert_eq!(t.get(id), Some(&99));
+    assert!(t.height() <= 6);
+    assert!(t.iter().copied().eq(60..100));
+}
+
+#[test]
+fn scapegoat_take_and_clear() {
+    let mut t = ScapegoatArenaTree::from_vec((0..200).collect());
+    for val in (0..200).step_by(3) {
+        assert_eq!(t.take(&val), Some(val));
+        scapegoat_check(&t, t.root());
+    }
+    assert_eq!(t.take(&0), None);
+    assert_eq!(t.size(), 133);
+    t.clear();
+    assert_eq!((t.size(), t.root()), (0, None));
+    t.insert(1);
+    scapegoat_check(&t, t.root());
+}
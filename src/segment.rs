@@ -0,0 +1,132 @@
+use std::ops::{Bound, RangeBounds};
+
+/// Segment tree over a fixed number of values, answering `op` folded over any range
+/// and updating single values, both in O(log n).
+///
+/// `op` must be associative with `identity` as its identity element;
+/// it doesn't have to be commutative, operands are always combined in order.
+/// The tree lives in one `Vec`: leaves in its upper half, parents of `i` at `i / 2`.
+#[derive(Debug, Clone)]
+pub struct SegmentTree<T, Op> {
+    nodes: Vec<T>,
+    len: usize,
+    identity: T,
+    op: Op,
+}
+
+impl<T, Op> SegmentTree<T, Op>
+where
+    T: Clone,
+    Op: Fn(&T, &T) -> T,
+{
+    pub fn from_vec(vals: Vec<T>, identity: T, op: Op) -> Self {
+        let len = vals.len();
+        let mut nodes = vec![identity.clone(); len];
+        nodes.extend(vals);
+        for i in (1..len).rev() {
+            nodes[i] = op(&nodes[2 * i], &nodes[2 * i + 1]);
+        }
+        Self {
+            nodes,
+            len,
+            identity,
+            op,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        if idx < self.len {
+            Some(&self.nodes[self.len + idx])
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the value at `idx`, panicking if it is out of bounds.
+    pub fn set(&mut self, idx: usize, val: T) {
+        assert!(idx < self.len, "index out of bounds");
+        let mut i = self.len + idx;
+        self.nodes[i] = val;
+        while i > 1 {
+            i /= 2;
+            self.nodes[i] = (self.op)(&self.nodes[2 * i], &self.nodes[2 * i + 1]);
+        }
+    }
+
+    /// Folds `op` over the values in `range`, `identity` for an empty range.
+    /// Panics if the range is out of bounds.
+    pub fn query<R>(&self, range: R) -> T
+    where
+        R: RangeBounds<usize>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(start <= end && end <= self.len, "range out of bounds");
+
+        let (mut left, mut right) = (self.identity.clone(), self.identity.clone());
+        let (mut lo, mut hi) = (start + self.len, end + self.len);
+        while lo < hi {
+            if lo % 2 == 1 {
+                left = (self.op)(&left, &self.nodes[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                right = (self.op)(&self.nodes[hi], &right);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        (self.op)(&left, &right)
+    }
+}
+
+#[test]
+fn segment_sum_and_min() {
+    let mut t = SegmentTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9], 0, |a: &i32, b: &i32| a + b);
+    assert_eq!(t.query(..), 37);
+    assert_eq!(t.query(1..4), 12);
+    assert_eq!(t.query(2..=2), 8);
+    assert_eq!(t.query(3..3), 0);
+    t.set(3, 10);
+    assert_eq!(t.query(..4), 26);
+    assert_eq!(t.get(3), Some(&10));
+    assert_eq!(t.get(7), None);
+
+    let t = SegmentTree::from_vec(vec![5, 3, 8, 1, 4], i32::MAX, |a: &i32, b: &i32| *a.min(b));
+    assert_eq!(t.query(..2), 3);
+    assert_eq!(t.query(2..), 1);
+}
+
+#[test]
+fn segment_non_commutative() {
+    let words = ["a", "b", "c", "d", "e", "f"];
+    let mut t = SegmentTree::from_vec(
+        words.iter().map(|w| w.to_string()).collect(),
+        String::new(),
+        |a: &String, b: &String| format!("{}{}", a, b),
+    );
+    for start in 0..=words.len() {
+        for end in start..=words.len() {
+            assert_eq!(t.query(start..end), words[start..end].concat());
+        }
+    }
+    t.set(0, "z".to_string());
+    assert_eq!(t.query(..3), "zbc");
+}
@@ -0,0 +1,186 @@
+//! Structure-preserving serde support, enabled by the `serde` feature.
+//!
+//! A tree is stored as its root index plus a flat list of nodes in pre-order,
+//! each naming the indices of its children.
+//! Deserializing checks the indices form a single tree, then the tree with `ArenaTree::validate`.
+
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::Comparator;
+use crate::map::ArenaMap;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use serde::{Deserialize as DeriveDeserialize, Serialize as DeriveSerialize};
+
+#[derive(DeriveSerialize)]
+struct TreeRef<'a, T> {
+    root: Option<usize>,
+    nodes: Vec<NodeRef<'a, T>>,
+}
+
+#[derive(DeriveSerialize)]
+struct NodeRef<'a, T> {
+    val: &'a T,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+#[derive(DeriveDeserialize)]
+struct TreeRepr<T> {
+    root: Option<usize>,
+    nodes: Vec<NodeRepr<T>>,
+}
+
+#[derive(DeriveDeserialize)]
+struct NodeRepr<T> {
+    val: T,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl<T, C> Serialize for ArenaTree<T, C>
+where
+    T: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes: Vec<NodeRef<'_, T>> = Vec::with_capacity(self.size());
+        // (node id, index of the parent and whether this is its left child)
+        let mut stack: Vec<(NodeId, Option<(usize, bool)>)> =
+            self.root().map(|id| (id, None)).into_iter().collect();
+        while let Some((id, pos)) = stack.pop() {
+            let idx = nodes.len();
+            nodes.push(NodeRef {
+                val: self.get(id).unwrap(),
+                left: None,
+                right: None,
+            });
+            match pos {
+                Some((parent, true)) => nodes[parent].left = Some(idx),
+                Some((parent, false)) => nodes[parent].right = Some(idx),
+                None => {}
+            }
+            if let Some(right_id) = self.right_of(id) {
+                stack.push((right_id, Some((idx, false))));
+            }
+            if let Some(left_id) = self.left_of(id) {
+                stack.push((left_id, Some((idx, true))));
+            }
+        }
+        TreeRef {
+            root: if nodes.is_empty() { None } else { Some(0) },
+            nodes,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T, C> Deserialize<'de> for ArenaTree<T, C>
+where
+    T: Deserialize<'de>,
+    C: Comparator<T> + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let TreeRepr { root, nodes } = TreeRepr::deserialize(deserializer)?;
+        let mut t = ArenaTree::with_comparator(C::default());
+        let root = match root {
+            None if nodes.is_empty() => return Ok(t),
+            None => return Err(de::Error::custom("nodes without a root")),
+            Some(root) => root,
+        };
+
+        let mut slots: Vec<_> = nodes.into_iter().map(Some).collect();
+        let mut stack = vec![(root, None)];
+        let mut linked = 0;
+        while let Some((idx, pos)) = stack.pop() {
+            let node = slots
+                .get_mut(idx)
+                .ok_or_else(|| de::Error::custom(format!("node index {} out of range", idx)))?
+                .take()
+                .ok_or_else(|| de::Error::custom(format!("node {} is linked twice", idx)))?;
+            let id = t.attach(pos, node.val);
+            linked += 1;
+            if let Some(right) = node.right {
+                stack.push((right, Some((id, false))));
+            }
+            if let Some(left) = node.left {
+                stack.push((left, Some((id, true))));
+            }
+        }
+        if linked != slots.len() {
+            return Err(de::Error::custom("unreachable nodes"));
+        }
+
+        t.validate().map_err(de::Error::custom)?;
+        Ok(t)
+    }
+}
+
+impl<K, V> Serialize for ArenaMap<K, V>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tree.serialize(serializer)
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for ArenaMap<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            tree: ArenaTree::deserialize(deserializer)?,
+        })
+    }
+}
+
+#[test]
+fn serde_roundtrip() {
+    use crate::arena::Traversal;
+
+    let mut t = ArenaTree::from_vec(vec![4, 2, 6, 1, 3, 5]);
+    assert!(t.delete(&4));
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(
+        json,
+        r#"{"root":0,"nodes":[{"val":5,"left":1,"right":4},{"val":2,"left":2,"right":3},{"val":1,"left":null,"right":null},{"val":3,"left":null,"right":null},{"val":6,"left":null,"right":null}]}"#
+    );
+
+    let back: ArenaTree<i32> = serde_json::from_str(&json).unwrap();
+    assert!(back.structurally_eq(&t));
+    assert_eq!(back.traversal(&Traversal::BFS), vec![&5, &2, &6, &1, &3]);
+
+    let empty: ArenaTree<i32> = serde_json::from_str(r#"{"root":null,"nodes":[]}"#).unwrap();
+    assert_eq!(empty.size(), 0);
+
+    let mut m = ArenaMap::new();
+    m.insert("b".to_string(), 2);
+    m.insert("a".to_string(), 1);
+    let back: ArenaMap<String, i32> =
+        serde_json::from_str(&serde_json::to_string(&m).unwrap()).unwrap();
+    assert_eq!(back.get("a"), Some(&1));
+    assert_eq!(back.len(), 2);
+}
+
+#[test]
+fn serde_validation() {
+    let cases = [
+        r#"{"root":null,"nodes":[{"val":1,"left":null,"right":null}]}"#,
+        r#"{"root":3,"nodes":[{"val":1,"left":null,"right":null}]}"#,
+        r#"{"root":0,"nodes":[{"val":2,"left":0,"right":null}]}"#,
+        r#"{"root":0,"nodes":[{"val":2,"left":1,"right":1},{"val":1,"left":null,"right":null}]}"#,
+        r#"{"root":0,"nodes":[{"val":2,"left":null,"right":null},{"val":1,"left":null,"right":null}]}"#,
+        r#"{"root":0,"nodes":[{"val":2,"left":null,"right":1},{"val":1,"left":null,"right":null}]}"#,
+    ];
+    for json in cases.iter() {
+        println!("json: {}", json);
+        assert!(serde_json::from_str::<ArenaTree<i32>>(json).is_err());
+    }
+    let json = r#"{"root":0,"nodes":[{"val":2,"left":1,"right":null},{"val":3,"left":null,"right":null}]}"#;
+    let err = serde_json::from_str::<ArenaTree<i32>>(json).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("node #1 violates the BST ordering"));
+}
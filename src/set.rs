@@ -0,0 +1,174 @@
+use crate::arena::ArenaTree;
+use crate::btree::BTree;
+use crate::compare::Comparator;
+use crate::skiplist::SkipList;
+use crate::treap::PriorityRng;
+use std::ops::RangeBounds;
+
+/// Ordered set API shared by `ArenaTree`, `BTree` and `SkipList`,
+/// so code written against it runs on either of them, e.g. to benchmark a workload.
+pub trait SortedSet<T> {
+    type Range<'a>: Iterator<Item = &'a T>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts `val`, returning whether it was not present yet.
+    fn insert(&mut self, val: T) -> bool;
+
+    /// The value equal to `val`.
+    fn search(&self, val: &T) -> Option<&T>;
+
+    fn contains(&self, val: &T) -> bool {
+        self.search(val).is_some()
+    }
+
+    /// Removes the value equal to `val`, returning whether it was present.
+    fn delete(&mut self, val: &T) -> bool;
+
+    /// Values within `range`, in order.
+    fn range<'a, R>(&'a self, range: R) -> Self::Range<'a>
+    where
+        R: RangeBounds<T>;
+}
+
+impl<T, C, M> SortedSet<T> for ArenaTree<T, C, M>
+where
+    C: Comparator<T>,
+    M: Default,
+{
+    type Range<'a>
+        = crate::arena::Iter<'a, T, C, M>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        self.size()
+    }
+
+    fn insert(&mut self, val: T) -> bool {
+        let size = self.size();
+        ArenaTree::insert(self, val);
+        self.size() != size
+    }
+
+    fn search(&self, val: &T) -> Option<&T> {
+        let id = ArenaTree::search(self, val)?;
+        self.get(id)
+    }
+
+    fn delete(&mut self, val: &T) -> bool {
+        ArenaTree::delete(self, val)
+    }
+
+    fn range<'a, R>(&'a self, range: R) -> Self::Range<'a>
+    where
+        R: RangeBounds<T>,
+    {
+        ArenaTree::range(self, range)
+    }
+}
+
+impl<T, C> SortedSet<T> for BTree<T, C>
+where
+    C: Comparator<T>,
+{
+    type Range<'a>
+        = crate::btree::Iter<'a, T, C>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        BTree::len(self)
+    }
+
+    fn insert(&mut self, val: T) -> bool {
+        BTree::insert(self, val)
+    }
+
+    fn search(&self, val: &T) -> Option<&T> {
+        self.get(val)
+    }
+
+    fn delete(&mut self, val: &T) -> bool {
+        BTree::delete(self, val)
+    }
+
+    fn range<'a, R>(&'a self, range: R) -> Self::Range<'a>
+    where
+        R: RangeBounds<T>,
+    {
+        BTree::range(self, range)
+    }
+}
+
+impl<T, C, R> SortedSet<T> for SkipList<T, C, R>
+where
+    C: Comparator<T>,
+    R: PriorityRng,
+{
+    type Range<'a>
+        = crate::skiplist::Iter<'a, T, C, R>
+    where
+        Self: 'a,
+        T: 'a;
+
+    fn len(&self) -> usize {
+        self.size()
+    }
+
+    fn insert(&mut self, val: T) -> bool {
+        let size = self.size();
+        SkipList::insert(self, val);
+        self.size() != size
+    }
+
+    fn search(&self, val: &T) -> Option<&T> {
+        self.get(SkipList::search(self, val)?)
+    }
+
+    fn delete(&mut self, val: &T) -> bool {
+        SkipList::delete(self, val)
+    }
+
+    fn range<'a, B>(&'a self, range: B) -> Self::Range<'a>
+    where
+        B: RangeBounds<T>,
+    {
+        SkipList::range(self, range)
+    }
+}
+
+#[cfg(test)]
+fn set_workload<S: SortedSet<i32>>(mut s: S) {
+    for val in (0..100).map(|val| val * 7 % 100) {
+        assert!(s.insert(val));
+    }
+    assert!(!s.insert(7));
+    for val in (0..100).filter(|val| val % 3 == 0) {
+        assert!(s.delete(&val));
+    }
+    assert!(!s.delete(&3));
+    assert_eq!(s.len(), 66);
+    assert_eq!(s.search(&4), Some(&4));
+    assert!(!s.contains(&6));
+    assert!(s
+        .range(10..20)
+        .copied()
+        .eq(vec![10, 11, 13, 14, 16, 17, 19]));
+}
+
+#[test]
+fn set_shared_api() {
+    set_workload(ArenaTree::default());
+    set_workload(BTree::with_fanout(4));
+    set_workload(SkipList::with_seed(1));
+}
@@ -0,0 +1,347 @@
+use crate::arena::{Link, NodeId};
+use crate::compare::{Comparator, Natural};
+use crate::slots::Slots;
+use crate::treap::{PriorityRng, XorShift64};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+/// Most levels a node is linked at.
+const MAX_LEVEL: usize = 32;
+
+#[derive(Debug)]
+struct SkipNode<T> {
+    val: T,
+    /// Next node at every level the node is linked at, from the bottom up.
+    next: Vec<Link>,
+}
+
+/// Skip list with the `insert`, `search`, `delete` and `range` API of `ArenaTree`,
+/// to compare both on a workload without touching the call sites.
+///
+/// Every node is linked at level 0, and at each level above with probability 1/2,
+/// so searches take O(log n) steps on expectation. Nodes live in an arena,
+/// so node ids stay valid until their node is deleted.
+/// The levels are drawn from `R`, seeded from the standard hasher by default.
+#[derive(Debug)]
+pub struct SkipList<T, C = Natural, R = XorShift64> {
+    arena: Slots<SkipNode<T>>,
+    /// First node at every level in use.
+    head: Vec<Link>,
+    cmp: C,
+    rng: R,
+}
+
+impl<T> Default for SkipList<T> {
+    fn default() -> Self {
+        Self::with_comparator_and_rng(Natural, XorShift64::default())
+    }
+}
+
+impl<T> SkipList<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a list whose levels only depend on the seed and the operations.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_comparator_and_rng(Natural, XorShift64::with_seed(seed))
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+/// In-order iterator over the values of a `SkipList`.
+pub struct Iter<'a, T, C = Natural, R = XorShift64> {
+    list: &'a SkipList<T, C, R>,
+    next: Option<NodeId>,
+    /// First node past the end of the iteration.
+    stop: Option<NodeId>,
+}
+
+impl<'a, T, C, R> Iterator for Iter<'a, T, C, R> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next.filter(|id| Some(*id) != self.stop)?;
+        let node = &self.list.arena[id];
+        self.next = node.next[0].get();
+        Some(&node.val)
+    }
+}
+
+impl<'a, T, C, R> IntoIterator for &'a SkipList<T, C, R> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C, R>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C> SkipList<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_comparator_and_rng(cmp, XorShift64::default())
+    }
+}
+
+impl<T, C, R> SkipList<T, C, R> {
+    pub fn with_comparator_and_rng(cmp: C, rng: R) -> Self {
+        Self {
+            arena: Slots::default(),
+            head: vec![],
+            cmp,
+            rng,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    /// Number of levels in use.
+    pub fn levels(&self) -> usize {
+        self.head.len()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.arena.get(id).map(|node| &node.val)
+    }
+
+    pub fn clear(&mut self) {
+        self.arena.clear();
+        self.head.clear();
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, C, R> {
+        Iter {
+            list: self,
+            next: self.next_of(None, 0),
+            stop: None,
+        }
+    }
+
+    /// Next node after `prev` at `level`, `None` standing for the head.
+    fn next_of(&self, prev: Option<NodeId>, level: usize) -> Option<NodeId> {
+        match prev {
+            None => self.head.get(level).and_then(|link| link.get()),
+            Some(id) => self.arena[id].next[level].get(),
+        }
+    }
+
+    fn set_next(&mut self, prev: Option<NodeId>, level: usize, next: Option<NodeId>) {
+        match prev {
+            None => self.head[level] = Link::new(next),
+            Some(id) => self.arena[id].next[level] = Link::new(next),
+        }
+    }
+
+    /// Last node at every level for which `before` holds, `before` holding
+    /// up to some value.
+    fn predecessors<F>(&self, before: F) -> Vec<Option<NodeId>>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut prevs = vec![None; self.head.len()];
+        let mut prev = None;
+        for level in (0..self.head.len()).rev() {
+            while let Some(next) = self.next_of(prev, level) {
+                if !before(&self.arena[next].val) {
+                    break;
+                }
+                prev = Some(next);
+            }
+            prevs[level] = prev;
+        }
+        prevs
+    }
+
+    /// First node for which `before` doesn't hold.
+    fn first_after<F>(&self, before: F) -> Option<NodeId>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut prev = None;
+        for level in (0..self.head.len()).rev() {
+            while let Some(next) = self.next_of(prev, level) {
+                if !before(&self.arena[next].val) {
+                    break;
+                }
+                prev = Some(next);
+            }
+        }
+        self.next_of(prev, 0)
+    }
+}
+
+impl<T, C, R> SkipList<T, C, R>
+where
+    C: Comparator<T>,
+{
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.first_after(|v| self.cmp.compare(v.borrow(), val) == Ordering::Less)?;
+        if self.cmp.compare(self.arena[id].val.borrow(), val) == Ordering::Equal {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.search(val).is_some()
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let prevs = self.predecessors(|v| self.cmp.compare(v.borrow(), val) == Ordering::Less);
+        let id = match self.next_of(prevs.first().copied().flatten(), 0) {
+            Some(id) if self.cmp.compare(self.arena[id].val.borrow(), val) == Ordering::Equal => id,
+            _ => return false,
+        };
+        let node = self.arena.remove(id).expect("linked node");
+        for (level, next) in node.next.into_iter().enumerate() {
+            self.set_next(prevs[level], level, next.get());
+        }
+        while self.head.last().is_some_and(|link| link.get().is_none()) {
+            self.head.pop();
+        }
+        true
+    }
+
+    /// Lazy in-order iterator over the values within `range`.
+    pub fn range<Q, B>(&self, range: B) -> Iter<'_, T, C, R>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+        B: RangeBounds<Q>,
+    {
+        let next = self.first_after(|v| match range.start_bound() {
+            Bound::Included(start) => self.cmp.compare(v.borrow(), start) == Ordering::Less,
+            Bound::Excluded(start) => self.cmp.compare(v.borrow(), start) != Ordering::Greater,
+            Bound::Unbounded => false,
+        });
+        let stop = match range.end_bound() {
+            Bound::Included(end) => {
+                self.first_after(|v| self.cmp.compare(v.borrow(), end) != Ordering::Greater)
+            }
+            Bound::Excluded(end) => {
+                self.first_after(|v| self.cmp.compare(v.borrow(), end) == Ordering::Less)
+            }
+            Bound::Unbounded => None,
+        };
+        let empty = match (next, stop) {
+            (Some(next_id), Some(stop_id)) => {
+                self.cmp
+                    .compare(&self.arena[next_id].val, &self.arena[stop_id].val)
+                    != Ordering::Less
+            }
+            _ => false,
+        };
+        Iter {
+            list: self,
+            next: if empty { None } else { next },
+            stop,
+        }
+    }
+}
+
+impl<T, C, R> SkipList<T, C, R>
+where
+    C: Comparator<T>,
+    R: PriorityRng,
+{
+    /// Inserts `val`, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let mut prevs = self.predecessors(|v| self.cmp.compare(v, &val) == Ordering::Less);
+        if let Some(id) = self.next_of(prevs.first().copied().flatten(), 0) {
+            if self.cmp.compare(&self.arena[id].val, &val) == Ordering::Equal {
+                return id;
+            }
+        }
+        // Level `l` is reached with probability 1/2^l.
+        let levels = (self.rng.next_priority().trailing_ones() as usize + 1).min(MAX_LEVEL);
+        while self.head.len() < levels {
+            self.head.push(Link::NONE);
+            prevs.push(None);
+        }
+        let next = (0..levels)
+            .map(|level| Link::new(self.next_of(prevs[level], level)))
+            .collect();
+        let id = self.arena.push(SkipNode { val, next });
+        for (level, prev) in prevs.into_iter().take(levels).enumerate() {
+            self.set_next(prev, level, Some(id));
+        }
+        id
+    }
+}
+
+#[test]
+fn skiplist_insert_search_delete() {
+    let mut t = SkipList::with_seed(7);
+    let ids: Vec<_> = (0..200).map(|val| t.insert(val * 7 % 200)).collect();
+    assert_eq!(t.insert(14), ids[2]);
+    assert_eq!(t.size(), 200);
+    assert!(t.levels() >= 4 && t.levels() <= MAX_LEVEL);
+    assert!(t.iter().copied().eq(0..200));
+    assert_eq!(t.search(&21).and_then(|id| t.get(id)), Some(&21));
+
+    for val in (0..200).filter(|val| val % 2 == 1) {
+        assert!(t.delete(&val));
+    }
+    assert!(!t.delete(&1));
+    assert!(!t.contains(&3));
+    assert_eq!(t.search(&200), None);
+    assert!(t.iter().copied().eq((0..200).step_by(2)));
+
+    for val in (0..200).step_by(2) {
+        assert!(t.delete(&val));
+    }
+    assert!(t.is_empty());
+    assert_eq!(t.levels(), 0);
+}
+
+#[test]
+fn skiplist_range() {
+    let t = SkipList::from_vec(vec![5, 1, 2, 4, 3, 8, 6, 9]);
+    assert!(t.range(3..8).copied().eq(vec![3, 4, 5, 6]));
+    assert!(t.range(..=4).copied().eq(vec![1, 2, 3, 4]));
+    assert!(t
+        .range((Bound::Excluded(6), Bound::Unbounded))
+        .copied()
+        .eq(vec![8, 9]));
+    assert_eq!(t.range(7..8).next(), None);
+    assert_eq!(
+        t.range((Bound::Included(6), Bound::Excluded(3))).next(),
+        None
+    );
+    assert_eq!(t.range(10..).next(), None);
+}
@@ -0,0 +1,409 @@
+use crate::arena::NodeId;
+use std::convert::TryFrom;
+use std::mem;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn slot(id: NodeId) -> usize {
+    id as usize
+}
+
+/// Number of slots in each chunk of the arena.
+pub(crate) const CHUNK_LEN: usize = 64;
+
+/// Copies a chunk, where `T: Clone` isn't known.
+type CloneChunk<T> = fn(&Vec<T>) -> Vec<T>;
+
+/// Vector grown by fixed-size chunks instead of reallocation,
+/// so values never move once pushed.
+///
+/// Chunks are reference counted, so `share` hands out a copy of the vector
+/// without copying the values. A chunk still shared when written to is copied first.
+#[derive(Debug)]
+struct Chunks<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    len: usize,
+    /// Copies a shared chunk, set by `share` where `T: Clone` is known.
+    clone_chunk: Option<CloneChunk<T>>,
+}
+
+/// The chunk for writing, copied first if it is shared.
+fn unshare<T>(chunk: &mut Arc<Vec<T>>, clone_chunk: Option<CloneChunk<T>>) -> &mut Vec<T> {
+    if Arc::get_mut(chunk).is_none() {
+        let clone_chunk = clone_chunk.expect("chunks are only shared by `share`");
+        *chunk = Arc::new(clone_chunk(chunk));
+    }
+    Arc::get_mut(chunk).expect("unshared chunk")
+}
+
+impl<T> Chunks<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        let mut chunks = Self {
+            chunks: vec![],
+            len: 0,
+            clone_chunk: None,
+        };
+        chunks.reserve(capacity);
+        chunks
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn capacity(&self) -> usize {
+        self.chunks.len() * CHUNK_LEN
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        let needed = (self.len + additional).div_ceil(CHUNK_LEN);
+        while self.chunks.len() < needed {
+            self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_LEN)));
+        }
+    }
+
+    fn push(&mut self, val: T) {
+        self.reserve(1);
+        unshare(&mut self.chunks[self.len / CHUNK_LEN], self.clone_chunk).push(val);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.len = self.len.checked_sub(1)?;
+        unshare(&mut self.chunks[self.len / CHUNK_LEN], self.clone_chunk).pop()
+    }
+
+    fn get(&self, idx: usize) -> Option<&T> {
+        self.chunks.get(idx / CHUNK_LEN)?.get(idx % CHUNK_LEN)
+    }
+
+    /// # Safety
+    ///
+    /// `idx` must be less than the length.
+    #[cfg(feature = "unsafe-fast")]
+    #[allow(unsafe_code)]
+    unsafe fn get_unchecked(&self, idx: usize) -> &T {
+        // SAFETY: every chunk before the last one is full, so the slot exists
+        // whenever `idx` is in bounds, as the caller promises.
+        unsafe {
+            self.chunks
+                .get_unchecked(idx / CHUNK_LEN)
+                .get_unchecked(idx % CHUNK_LEN)
+        }
+    }
+
+    fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let chunk = self.chunks.get_mut(idx / CHUNK_LEN)?;
+        unshare(chunk, self.clone_chunk).get_mut(idx % CHUNK_LEN)
+    }
+
+    fn last(&self) -> Option<&T> {
+        self.get(self.len.checked_sub(1)?)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        let clone_chunk = self.clone_chunk;
+        self.chunks
+            .iter_mut()
+            .flat_map(move |chunk| unshare(chunk, clone_chunk).iter_mut())
+    }
+
+    /// Keeps the values matching `f`, moving them into fresh chunks.
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let old = mem::take(&mut self.chunks);
+        self.len = 0;
+        for mut chunk in old.into_iter() {
+            for val in mem::take(unshare(&mut chunk, self.clone_chunk)) {
+                if f(&val) {
+                    self.push(val);
+                }
+            }
+        }
+    }
+
+    /// Bytes allocated for the chunks and the chunk table, shared chunks included.
+    fn memory_usage(&self) -> usize {
+        self.capacity() * mem::size_of::<T>()
+            + self.chunks.capacity() * mem::size_of::<Arc<Vec<T>>>()
+    }
+
+    /// Releases the chunks past the last value.
+    fn shrink_to_fit(&mut self) {
+        self.chunks.truncate(self.len.div_ceil(CHUNK_LEN));
+        self.chunks.shrink_to_fit();
+    }
+
+    /// Drops all values, keeping the unshared chunks for reuse.
+    fn clear(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            match Arc::get_mut(chunk) {
+                Some(chunk) => chunk.clear(),
+                None => *chunk = Arc::new(Vec::with_capacity(CHUNK_LEN)),
+            }
+        }
+        self.len = 0;
+    }
+
+    /// Copy of the vector sharing all chunks with it, in O(n / CHUNK_LEN).
+    fn share(&mut self) -> Self
+    where
+        T: Clone,
+    {
+        self.clone_chunk = Some(Vec::clone);
+        Self {
+            chunks: self.chunks.clone(),
+            len: self.len,
+            clone_chunk: self.clone_chunk,
+        }
+    }
+}
+
+impl<T> Index<usize> for Chunks<T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        self.get(idx).expect("index out of bounds")
+    }
+}
+
+impl<T> IndexMut<usize> for Chunks<T> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        self.get_mut(idx).expect("index out of bounds")
+    }
+}
+
+/// Backing storage of the arena trees.
+/// Removing a value leaves a vacant slot behind, so ids of other values stay valid.
+/// Vacant slots are kept in a free list and reused by later pushes.
+///
+/// Every slot has a generation, bumped whenever its value is removed or moved away,
+/// so a `(id, generation)` pair taken earlier can tell it went stale.
+/// Generations outlive the slots themselves, so ids are never handed out again
+/// with a generation that was already seen.
+///
+/// Slots are allocated in chunks of `CHUNK_LEN`, so growing the arena
+/// never moves the values already stored, and avoids large reallocations.
+#[derive(Debug)]
+pub(crate) struct Slots<T> {
+    slots: Chunks<Option<T>>,
+    generations: Vec<u32>,
+    free: Vec<NodeId>,
+    live: usize,
+}
+
+impl<T> Default for Slots<T> {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+impl<T> Slots<T> {
+    /// Number of occupied slots.
+    pub(crate) fn len(&self) -> usize {
+        self.live
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.live == 0
+    }
+
+    /// Id the next pushed value will be stored at.
+    pub(crate) fn next_id(&self) -> NodeId {
+        match self.free.last() {
+            Some(&id) => id,
+            None => NodeId::try_from(self.slots.len()).expect("arena index overflow"),
+        }
+    }
+
+    /// Number of slots, occupied or vacant.
+    pub(crate) fn slots_len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn push(&mut self, val: T) -> NodeId {
+        let id = self.next_id();
+        match self.free.pop() {
+            Some(id) => self.slots[slot(id)] = Some(val),
+            None => self.slots.push(Some(val)),
+        }
+        if self.generations.len() < self.slots.len() {
+            self.generations.push(0);
+        }
+        self.live += 1;
+        id
+    }
+
+    fn bump(&mut self, idx: usize) {
+        self.generations[idx] = self.generations[idx].wrapping_add(1);
+    }
+
+    /// Generation of the slot, if it is occupied.
+    pub(crate) fn generation(&self, id: NodeId) -> Option<u32> {
+        self.get(id)?;
+        Some(self.generations[slot(id)])
+    }
+
+    pub(crate) fn get(&self, id: NodeId) -> Option<&T> {
+        self.slots.get(slot(id))?.as_ref()
+    }
+
+    /// # Safety
+    ///
+    /// The slot of `id` must be occupied.
+    #[cfg(feature = "unsafe-fast")]
+    #[allow(unsafe_code)]
+    pub(crate) unsafe fn get_unchecked(&self, id: NodeId) -> &T {
+        // SAFETY: an occupied slot is in bounds and holds a value, as the caller promises.
+        unsafe {
+            self.slots
+                .get_unchecked(slot(id))
+                .as_ref()
+                .unwrap_unchecked()
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.slots.get_mut(slot(id))?.as_mut()
+    }
+
+    pub(crate) fn remove(&mut self, id: NodeId) -> Option<T> {
+        let val = self.slots.get_mut(slot(id))?.take()?;
+        self.bump(slot(id));
+        self.free.push(id);
+        self.live -= 1;
+        Some(val)
+    }
+
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Chunks::with_capacity(capacity),
+            generations: Vec::with_capacity(capacity),
+            free: vec![],
+            live: 0,
+        }
+    }
+
+    /// Iterates over the occupied slots in id order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(id, slot)| Some((id as NodeId, slot.as_ref()?)))
+    }
+
+    /// New ids the occupied slots would get if vacant ones were squeezed out,
+    /// indexed by the current ids.
+    pub(crate) fn dense_ids(&self) -> Vec<Option<NodeId>> {
+        let mut next = 0;
+        self.slots
+            .iter()
+            .map(|slot| {
+                slot.as_ref().map(|_| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect()
+    }
+
+    /// Number of values storable without allocating another chunk.
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Bytes allocated for the slots, their generations and the free list.
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.slots.memory_usage()
+            + self.generations.capacity() * mem::size_of::<u32>()
+            + self.free.capacity() * mem::size_of::<NodeId>()
+    }
+
+    /// Reserves room for `additional` more values, counting vacant slots as room.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.slots
+            .reserve(additional.saturating_sub(self.free.len()));
+    }
+
+    /// Drops trailing vacant slots and releases unused chunks.
+    /// Vacant slots between occupied ones are kept; see `compact`.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        while let Some(None) = self.slots.last() {
+            self.slots.pop();
+        }
+        let len = self.slots.len();
+        self.free.retain(|&id| slot(id) < len);
+        self.slots.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(Option::as_mut)
+    }
+
+    /// Squeezes out the vacant slots and shrinks the storage,
+    /// returning the new ids indexed by the old ones.
+    pub(crate) fn compact(&mut self) -> Vec<Option<NodeId>> {
+        let remap = self.dense_ids();
+        // Both the slot a value leaves and the one it lands in change hands.
+        for (old, new) in remap.iter().enumerate() {
+            match new {
+                Some(new) if slot(*new) != old => {
+                    self.bump(old);
+                    self.bump(slot(*new));
+                }
+                _ => {}
+            }
+        }
+        self.slots.retain(Option::is_some);
+        self.free.clear();
+        self.free.shrink_to_fit();
+        remap
+    }
+
+    /// Copy of the arena sharing the slots with it until either is written to,
+    /// the generations and the free list being copied right away.
+    pub(crate) fn share(&mut self) -> Self
+    where
+        T: Clone,
+    {
+        Self {
+            slots: self.slots.share(),
+            generations: self.generations.clone(),
+            free: self.free.clone(),
+            live: self.live,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for idx in 0..self.slots.len() {
+            if self.slots[idx].is_some() {
+                self.bump(idx);
+            }
+        }
+        self.slots.clear();
+        self.free.clear();
+        self.live = 0;
+    }
+}
+
+impl<T> Index<NodeId> for Slots<T> {
+    type Output = T;
+
+    fn index(&self, id: NodeId) -> &T {
+        self.get(id).expect("vacant slot")
+    }
+}
+
+impl<T> IndexMut<NodeId> for Slots<T> {
+    fn index_mut(&mut self, id: NodeId) -> &mut T {
+        self.get_mut(id).expect("vacant slot")
+    }
+}
@@ -0,0 +1,298 @@
+use crate::arena::{Link, NodeId};
+use crate::compare::{Comparator, Natural};
+use crate::slots::slot;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Binary search tree laid out as a structure of arrays.
+///
+/// Values and links live in separate vectors indexed by node id,
+/// so searches walking the links don't drag the values through the cache.
+/// The arrays are kept dense: deleting a node moves the last node into its slot,
+/// so node ids are only stable until the next `delete`.
+#[derive(Debug, Clone)]
+pub struct SoaTree<T, C = Natural> {
+    root: Option<NodeId>,
+    vals: Vec<T>,
+    parents: Vec<Link>,
+    lefts: Vec<Link>,
+    rights: Vec<Link>,
+    cmp: C,
+}
+
+impl<T> Default for SoaTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> SoaTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+/// In-order iterator over the values of a `SoaTree`.
+pub struct Iter<'a, T, C = Natural> {
+    tree: &'a SoaTree<T, C>,
+    stack: Vec<NodeId>,
+}
+
+impl<'a, T, C> Iter<'a, T, C> {
+    fn push_left(&mut self, mut cur: Option<NodeId>) {
+        while let Some(id) = cur {
+            self.stack.push(id);
+            cur = self.tree.lefts[slot(id)].get();
+        }
+    }
+}
+
+impl<'a, T, C> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        self.push_left(self.tree.rights[slot(id)].get());
+        Some(&self.tree.vals[slot(id)])
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a SoaTree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C> SoaTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            root: None,
+            vals: Vec::new(),
+            parents: Vec::new(),
+            lefts: Vec::new(),
+            rights: Vec::new(),
+            cmp,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.vals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vals.is_empty()
+    }
+
+    pub fn root(&self) -> Option<NodeId> {
+        self.root
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.vals.get(slot(id))
+    }
+
+    pub fn parent_of(&self, id: NodeId) -> Option<NodeId> {
+        self.parents.get(slot(id))?.get()
+    }
+
+    pub fn left_of(&self, id: NodeId) -> Option<NodeId> {
+        self.lefts.get(slot(id))?.get()
+    }
+
+    pub fn right_of(&self, id: NodeId) -> Option<NodeId> {
+        self.rights.get(slot(id))?.get()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, C> {
+        let mut iter = Iter {
+            tree: self,
+            stack: Vec::new(),
+        };
+        iter.push_left(self.root);
+        iter
+    }
+
+    /// Points the link of `parent` that leads to `old` at `new` instead.
+    fn replace_child(&mut self, parent: Option<NodeId>, old: NodeId, new: Option<NodeId>) {
+        match parent {
+            None => self.root = new,
+            Some(parent_id) => {
+                let p = slot(parent_id);
+                if self.lefts[p].get() == Some(old) {
+                    self.lefts[p] = Link::new(new);
+                } else {
+                    self.rights[p] = Link::new(new);
+                }
+            }
+        }
+    }
+
+    /// Unlinks a node with at most one child, then fills its slot with the last node.
+    fn unlink(&mut self, id: NodeId) -> T {
+        let i = slot(id);
+        let child = self.lefts[i].get().or_else(|| self.rights[i].get());
+        let parent = self.parents[i].get();
+        if let Some(child_id) = child {
+            self.parents[slot(child_id)] = Link::new(parent);
+        }
+        self.replace_child(parent, id, child);
+
+        let last = NodeId::try_from(self.vals.len() - 1).expect("arena index overflow");
+        let val = self.vals.swap_remove(i);
+        self.parents.swap_remove(i);
+        self.lefts.swap_remove(i);
+        self.rights.swap_remove(i);
+        if last != id {
+            self.replace_child(self.parents[i].get(), last, Some(id));
+            for link in [self.lefts[i], self.rights[i]].iter() {
+                if let Some(child_id) = link.get() {
+                    self.parents[slot(child_id)] = Link::new(Some(id));
+                }
+            }
+        }
+        val
+    }
+}
+
+impl<T, C> SoaTree<T, C>
+where
+    C: Comparator<T>,
+{
+    /// Finds the node equal to `val`, or the position a new node would be linked at.
+    fn locate<Q>(&self, val: &Q) -> Result<NodeId, Option<(NodeId, bool)>>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut cur = match self.root {
+            Some(id) => id,
+            None => return Err(None),
+        };
+        loop {
+            let i = slot(cur);
+            let (next, dir) = match self.cmp.compare(val, self.vals[i].borrow()) {
+                Ordering::Equal => return Ok(cur),
+                Ordering::Less => (self.lefts[i].get(), true),
+                Ordering::Greater => (self.rights[i].get(), false),
+            };
+            match next {
+                Some(id) => cur = id,
+                None => return Err(Some((cur, dir))),
+            }
+        }
+    }
+
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.locate(val).ok()
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.search(val).is_some()
+    }
+
+    /// Inserts `val`, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let pos = match self.locate(&val) {
+            Ok(id) => return id,
+            Err(pos) => pos,
+        };
+        let id = NodeId::try_from(self.vals.len()).expect("arena index overflow");
+        self.vals.push(val);
+        self.parents
+            .push(Link::new(pos.map(|(parent_id, _)| parent_id)));
+        self.lefts.push(Link::NONE);
+        self.rights.push(Link::NONE);
+        match pos {
+            None => self.root = Some(id),
+            Some((parent_id, true)) => self.lefts[slot(parent_id)] = Link::new(Some(id)),
+            Some((parent_id, false)) => self.rights[slot(parent_id)] = Link::new(Some(id)),
+        }
+        id
+    }
+
+    /// Deletes the node equal to `val`, returning whether it was present.
+    /// The last node moves into the freed slot, changing its id.
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut id = match self.search(val) {
+            Some(id) => id,
+            None => return false,
+        };
+        if let (Some(_), Some(right_id)) = (self.lefts[slot(id)].get(), self.rights[slot(id)].get())
+        {
+            let mut successor = right_id;
+            while let Some(left_id) = self.lefts[slot(successor)].get() {
+                successor = left_id;
+            }
+            self.vals.swap(slot(id), slot(successor));
+            id = successor;
+        }
+        self.unlink(id);
+        true
+    }
+}
+
+#[test]
+fn soa_insert_and_search() {
+    let mut t = SoaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    assert_eq!(t.size(), 7);
+    assert_eq!(t.insert(4), 4);
+    assert_eq!(t.size(), 7);
+    assert!(t.contains(&7));
+    assert!(!t.contains(&6));
+    assert_eq!(t.search(&8).and_then(|id| t.get(id)), Some(&8));
+    assert_eq!(t.iter().copied().collect::<Vec<_>>(), [1, 3, 4, 5, 7, 8, 9]);
+}
+
+#[test]
+fn soa_delete_keeps_arrays_dense() {
+    let mut t = SoaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9, 6]);
+    for val in [5, 1, 8, 42, 6].iter() {
+        assert_eq!(t.delete(val), *val != 42);
+        assert_eq!(t.size(), t.parents.len());
+        for id in 0..t.size() as NodeId {
+            for child in [t.left_of(id), t.right_of(id)].iter().flatten() {
+                assert_eq!(t.parent_of(*child), Some(id));
+            }
+        }
+        let root = t.root().unwrap();
+        assert_eq!(t.parent_of(root), None);
+    }
+    assert_eq!(t.iter().copied().collect::<Vec<_>>(), [3, 4, 7, 9]);
+    for val in [3, 4, 7, 9].iter() {
+        assert!(t.delete(val));
+    }
+    assert!(t.is_empty());
+    assert_eq!(t.root(), None);
+}
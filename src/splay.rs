@@ -0,0 +1,213 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::ops::Deref;
+
+/// Splay tree variant of `ArenaTree`.
+///
+/// Every access rotates the node it reached up to the root, so recently used values
+/// are found within a few steps, and any sequence of operations is amortized O(log n).
+/// See the balanced variants of `ArenaTree` for its API; reads through `Deref` don't splay.
+#[derive(Debug, Clone)]
+pub struct SplayArenaTree<T, C = Natural> {
+    tree: ArenaTree<T, C>,
+}
+
+impl<T> Default for SplayArenaTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> SplayArenaTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+impl<T, C> Deref for SplayArenaTree<T, C> {
+    type Target = ArenaTree<T, C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, C> SplayArenaTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            tree: ArenaTree::with_comparator(cmp),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    /// Lifts `id` above its parent.
+    fn rotate_up(&mut self, id: NodeId) {
+        let parent_id = self.tree.parent_of(id).expect("rotating the root");
+        if self.tree.left_of(parent_id) == Some(id) {
+            self.tree.rotate_right(parent_id);
+        } else {
+            self.tree.rotate_left(parent_id);
+        }
+    }
+
+    /// Rotates `id` up to the root.
+    fn splay(&mut self, id: NodeId) {
+        while let Some(parent_id) = self.tree.parent_of(id) {
+            match self.tree.parent_of(parent_id) {
+                None => self.rotate_up(id),
+                Some(grand_id) => {
+                    let zig_zig = (self.tree.left_of(grand_id) == Some(parent_id))
+                        == (self.tree.left_of(parent_id) == Some(id));
+                    if zig_zig {
+                        self.rotate_up(parent_id);
+                    } else {
+                        self.rotate_up(id);
+                    }
+                    self.rotate_up(id);
+                }
+            }
+        }
+    }
+}
+
+impl<T, C> SplayArenaTree<T, C>
+where
+    C: Comparator<T>,
+{
+    /// Finds the node equal to `val` and splays it to the root.
+    /// On a miss, the last node visited is splayed instead.
+    pub fn search<Q>(&mut self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.tree.search(val) {
+            Some(id) => {
+                self.splay(id);
+                Some(id)
+            }
+            None => {
+                if let Some((parent_id, _)) = self.tree.search_parent(val) {
+                    self.splay(parent_id);
+                }
+                None
+            }
+        }
+    }
+
+    pub fn contains<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.search(val).is_some()
+    }
+
+    pub fn get_by_val<Q>(&mut self, val: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.search(val)?;
+        self.tree.get(id)
+    }
+
+    /// Inserts `val` and splays it to the root, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let id = self.tree.insert(val);
+        self.splay(id);
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Removes the node equal to `val`, then splays the parent of the removed position,
+    /// returning the value.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        match self.tree.search(val) {
+            Some(id) => {
+                let parent_id = self.tree.parent_of(id);
+                let (val, _) = self.tree.unlink(id);
+                if let Some(parent_id) = parent_id {
+                    self.splay(parent_id);
+                }
+                Some(val)
+            }
+            None => {
+                self.search(val);
+                None
+            }
+        }
+    }
+}
+
+#[test]
+fn splay_access_moves_to_root() {
+    let mut t = SplayArenaTree::from_vec((0..100).collect());
+    assert_eq!(t.get(t.root().unwrap()), Some(&99));
+    let id = t.search(&42).unwrap();
+    assert_eq!(t.root(), Some(id));
+    assert_eq!(t.get_by_val(&7), Some(&7));
+    assert_eq!(t.get(t.root().unwrap()), Some(&7));
+    // Splaying roughly halves the depth of the nodes on the access path.
+    assert!(t.height() < 100);
+
+    assert!(!t.contains(&1000));
+    assert_eq!(t.get(t.root().unwrap()), Some(&99));
+    assert!(t.iter().copied().eq(0..100));
+}
+
+#[test]
+fn splay_delete() {
+    let mut t = SplayArenaTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    assert!(t.delete(&3));
+    assert!(!t.delete(&3));
+    assert!(t.delete(&5));
+    assert_eq!(t.size(), 5);
+    assert!(t.iter().copied().eq([1, 4, 7, 8, 9].iter().copied()));
+    assert_eq!(t.parent_of(t.root().unwrap()), None);
+}
+
+#[test]
+fn splay_take_and_clear() {
+    let mut t = SplayArenaTree::from_vec((0..100).collect());
+    assert_eq!(t.take(&42), Some(42));
+    assert_eq!(t.take(&42), None);
+    assert!(t.iter().copied().eq((0..42).chain(43..100)));
+    assert_eq!(t.size(), 99);
+    t.clear();
+    assert_eq!((t.size(), t.root()), (0, None));
+    t.insert(1);
+    assert_eq!(t.get(t.root().unwrap()), Some(&1));
+}
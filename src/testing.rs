@@ -0,0 +1,199 @@
+//! Differential testing support, enabled by the `testing` feature.
+//!
+//! `OracleTree` runs every operation on both an `ArenaTree` and a `BTreeSet`
+//! and panics as soon as they disagree, so integration tests of code embedding
+//! a tree can check it against the standard library along the way.
+
+use crate::arena::{ArenaTree, NodeId};
+use std::borrow::Borrow;
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::ops::RangeBounds;
+
+/// `ArenaTree` mirrored into a `BTreeSet`, asserting both agree after each operation.
+#[derive(Debug, Clone)]
+pub struct OracleTree<T> {
+    tree: ArenaTree<T>,
+    oracle: BTreeSet<T>,
+}
+
+impl<T> Default for OracleTree<T> {
+    fn default() -> Self {
+        Self {
+            tree: ArenaTree::default(),
+            oracle: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T> OracleTree<T>
+where
+    T: Ord + Clone + Debug,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+
+    pub fn tree(&self) -> &ArenaTree<T> {
+        &self.tree
+    }
+
+    pub fn oracle(&self) -> &BTreeSet<T> {
+        &self.oracle
+    }
+
+    pub fn into_inner(self) -> (ArenaTree<T>, BTreeSet<T>) {
+        (self.tree, self.oracle)
+    }
+
+    pub fn size(&self) -> usize {
+        self.check_len();
+        self.tree.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let size = self.tree.size();
+        let id = self.tree.insert(val.clone());
+        let inserted = self.oracle.insert(val.clone());
+        assert_eq!(
+            self.tree.size() > size,
+            inserted,
+            "insert({:?}) disagrees",
+            val
+        );
+        assert_eq!(
+            self.tree.get(id),
+            Some(&val),
+            "insert({:?}) returned #{}",
+            val,
+            id
+        );
+        self.check_len();
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + Debug + ?Sized,
+    {
+        let deleted = self.tree.delete(val);
+        assert_eq!(
+            deleted,
+            self.oracle.remove(val),
+            "delete({:?}) disagrees",
+            val
+        );
+        self.check_len();
+        deleted
+    }
+
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + Debug + ?Sized,
+    {
+        let taken = self.tree.take(val);
+        assert_eq!(taken, self.oracle.take(val), "take({:?}) disagrees", val);
+        self.check_len();
+        taken
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + Debug + ?Sized,
+    {
+        let found = self.tree.contains(val);
+        assert_eq!(
+            found,
+            self.oracle.contains(val),
+            "contains({:?}) disagrees",
+            val
+        );
+        found
+    }
+
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        Q: Ord + Debug + ?Sized,
+    {
+        let id = self.tree.search(val);
+        let found = id.and_then(|id| self.tree.get(id));
+        assert_eq!(found, self.oracle.get(val), "search({:?}) disagrees", val);
+        id
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Vec<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q> + Clone + Debug,
+    {
+        let vals: Vec<_> = self.tree.range(range.clone()).collect();
+        assert!(
+            vals.iter().copied().eq(self.oracle.range(range.clone())),
+            "range({:?}) disagrees",
+            range
+        );
+        vals
+    }
+
+    /// Asserts the whole tree agrees with the oracle and passes `ArenaTree::validate`.
+    /// Takes O(n), unlike the checks after each operation.
+    pub fn check(&self) {
+        assert!(
+            self.tree.iter().eq(self.oracle.iter()),
+            "values disagree: {:?} != {:?}",
+            self.tree.iter().collect::<Vec<_>>(),
+            self.oracle
+        );
+        if let Err(err) = self.tree.validate() {
+            panic!("invalid tree: {}", err);
+        }
+    }
+
+    fn check_len(&self) {
+        assert_eq!(self.tree.size(), self.oracle.len(), "size disagrees");
+    }
+}
+
+#[test]
+fn testing_oracle_tree() {
+    let mut t = OracleTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    assert!(t.contains(&4) && !t.contains(&6));
+    assert_eq!(t.insert(4), t.search(&4).unwrap());
+    assert!(t.delete(&3));
+    assert!(!t.delete(&3));
+    assert_eq!(t.take(&9), Some(9));
+    t.insert(9);
+    assert_eq!(t.range(4..8), [&4, &5, &7]);
+    assert_eq!(t.size(), 6);
+    t.check();
+    for val in 0..50 {
+        t.insert(val * 37 % 50);
+        t.delete(&(val * 11 % 50));
+    }
+    t.check();
+}
+
+#[test]
+#[should_panic(expected = "disagrees")]
+fn testing_oracle_tree_disagreement() {
+    let mut t = OracleTree::from_vec(vec![2, 1, 3]);
+    t.oracle.insert(4);
+    t.contains(&4);
+}
@@ -0,0 +1,324 @@
+use crate::arena::{Link, NodeId};
+use crate::compare::{Comparator, Natural};
+use crate::slots::Slots;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+
+#[derive(Debug)]
+struct ThreadedNode<T> {
+    val: T,
+    left: Link,
+    right: Link,
+    /// Whether `right` is a thread to the in-order successor rather than a child.
+    threaded: bool,
+}
+
+/// Right-threaded binary search tree: the right link of a node without a right child
+/// points to its in-order successor instead, so `successor` takes O(1) amortized
+/// and in-order iteration needs neither a stack nor parent links.
+///
+/// Nodes live in an arena like the nodes of `ArenaTree`,
+/// and deletions relink nodes, so node ids stay valid.
+#[derive(Debug)]
+pub struct ThreadedTree<T, C = Natural> {
+    arena: Slots<ThreadedNode<T>>,
+    root: Option<NodeId>,
+    cmp: C,
+}
+
+impl<T> Default for ThreadedTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> ThreadedTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+/// Stackless in-order iterator over the values of a `ThreadedTree`.
+pub struct Iter<'a, T, C = Natural> {
+    tree: &'a ThreadedTree<T, C>,
+    next: Option<NodeId>,
+}
+
+impl<'a, T, C> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.tree.successor(id);
+        Some(&self.tree.arena[id].val)
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a ThreadedTree<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, C> ThreadedTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self {
+            arena: Slots::default(),
+            root: None,
+            cmp,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.is_empty()
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.arena.get(id).map(|node| &node.val)
+    }
+
+    /// Id of the smallest value.
+    pub fn first(&self) -> Option<NodeId> {
+        self.root.map(|id| self.most_left(id))
+    }
+
+    /// Id of the next value in order, following the thread if there is no right child.
+    pub fn successor(&self, id: NodeId) -> Option<NodeId> {
+        let node = &self.arena[id];
+        if node.threaded {
+            node.right.get()
+        } else {
+            node.right.get().map(|right_id| self.most_left(right_id))
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, C> {
+        Iter {
+            tree: self,
+            next: self.first(),
+        }
+    }
+
+    fn most_left(&self, mut id: NodeId) -> NodeId {
+        while let Some(left_id) = self.arena[id].left.get() {
+            id = left_id;
+        }
+        id
+    }
+
+    fn most_right(&self, mut id: NodeId) -> NodeId {
+        while !self.arena[id].threaded {
+            id = self.arena[id].right.get().expect("unthreaded right child");
+        }
+        id
+    }
+
+    /// Links `new` in place of the `is_left` child of `parent`, the root if `None`.
+    fn replace_child(&mut self, parent: Option<NodeId>, is_left: bool, new: Option<NodeId>) {
+        match parent {
+            None => self.root = new,
+            Some(parent_id) if is_left => self.arena[parent_id].left = Link::new(new),
+            Some(parent_id) => self.arena[parent_id].right = Link::new(new),
+        }
+    }
+}
+
+impl<T, C> ThreadedTree<T, C>
+where
+    C: Comparator<T>,
+{
+    /// Id of the node equal to `val`, with its parent and whether it is the left child.
+    fn locate<Q>(&self, val: &Q) -> Option<(NodeId, Option<NodeId>, bool)>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let (mut parent, mut is_left) = (None, false);
+        let mut cur = self.root?;
+        loop {
+            let node = &self.arena[cur];
+            let (next, left) = match self.cmp.compare(val, node.val.borrow()) {
+                Ordering::Less => (node.left.get(), true),
+                Ordering::Equal => return Some((cur, parent, is_left)),
+                Ordering::Greater if node.threaded => return None,
+                Ordering::Greater => (node.right.get(), false),
+            };
+            is_left = left;
+            parent = Some(cur);
+            cur = next?;
+        }
+    }
+
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.locate(val).map(|(id, _, _)| id)
+    }
+
+    pub fn contains<Q>(&self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.locate(val).is_some()
+    }
+
+    /// Inserts `val`, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let mut cur = match self.root {
+            Some(root) => root,
+            None => {
+                let id = self.push(val, None);
+                self.root = Some(id);
+                return id;
+            }
+        };
+        loop {
+            let node = &self.arena[cur];
+            match self.cmp.compare(&val, &node.val) {
+                Ordering::Less => match node.left.get() {
+                    Some(left_id) => cur = left_id,
+                    None => {
+                        let id = self.push(val, Some(cur));
+                        self.arena[cur].left = Link::new(Some(id));
+                        return id;
+                    }
+                },
+                Ordering::Equal => return cur,
+                Ordering::Greater if node.threaded => {
+                    let id = self.push(val, node.right.get());
+                    let node = &mut self.arena[cur];
+                    node.right = Link::new(Some(id));
+                    node.threaded = false;
+                    return id;
+                }
+                Ordering::Greater => cur = node.right.get().expect("unthreaded right child"),
+            }
+        }
+    }
+
+    /// Pushes a childless node threaded to `successor`.
+    fn push(&mut self, val: T, successor: Option<NodeId>) -> NodeId {
+        self.arena.push(ThreadedNode {
+            val,
+            left: Link::NONE,
+            right: Link::new(successor),
+            threaded: true,
+        })
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let (id, parent, is_left) = match self.locate(val) {
+            Some(found) => found,
+            None => return false,
+        };
+        let node = self.arena.remove(id).expect("linked node");
+        // Only the largest value of the left subtree is threaded to the node.
+        let pred = node.left.get().map(|left_id| self.most_right(left_id));
+        match (node.left.get(), node.threaded) {
+            (None, true) => match parent {
+                // The parent is the predecessor, it takes over the thread.
+                Some(parent_id) if !is_left => {
+                    let parent = &mut self.arena[parent_id];
+                    parent.right = node.right;
+                    parent.threaded = true;
+                }
+                _ => self.replace_child(parent, is_left, None),
+            },
+            (None, false) => self.replace_child(parent, is_left, node.right.get()),
+            (Some(left_id), true) => {
+                self.arena[pred.expect("left subtree")].right = node.right;
+                self.replace_child(parent, is_left, Some(left_id));
+            }
+            (Some(left_id), false) => {
+                let right_id = node.right.get().expect("unthreaded right child");
+                let (mut succ_parent, mut succ) = (id, right_id);
+                while let Some(left_id) = self.arena[succ].left.get() {
+                    succ_parent = succ;
+                    succ = left_id;
+                }
+                if succ_parent != id {
+                    let succ_node = &self.arena[succ];
+                    let succ_right = if succ_node.threaded {
+                        None
+                    } else {
+                        succ_node.right.get()
+                    };
+                    self.arena[succ_parent].left = Link::new(succ_right);
+                    let succ_node = &mut self.arena[succ];
+                    succ_node.right = Link::new(Some(right_id));
+                    succ_node.threaded = false;
+                }
+                self.arena[succ].left = Link::new(Some(left_id));
+                if let Some(pred) = pred {
+                    self.arena[pred].right = Link::new(Some(succ));
+                }
+                self.replace_child(parent, is_left, Some(succ));
+            }
+        }
+        true
+    }
+}
+
+#[test]
+fn threaded_successor() {
+    let t = ThreadedTree::from_vec(vec![5, 3, 8, 1, 4, 7, 9]);
+    assert!(t.iter().copied().eq(vec![1, 3, 4, 5, 7, 8, 9]));
+    let id = t.search(&4).unwrap();
+    assert_eq!(t.successor(id).and_then(|id| t.get(id)), Some(&5));
+    let id = t.search(&5).unwrap();
+    assert_eq!(t.successor(id).and_then(|id| t.get(id)), Some(&7));
+    assert_eq!(t.successor(t.search(&9).unwrap()), None);
+    assert_eq!(t.first().and_then(|id| t.get(id)), Some(&1));
+    assert_eq!(ThreadedTree::<i32>::new().iter().next(), None);
+}
+
+#[test]
+fn threaded_delete() {
+    let vals: Vec<_> = (0..200).map(|val| val * 37 % 200).collect();
+    let mut t = ThreadedTree::from_vec(vals.clone());
+    let id = t.search(&150).unwrap();
+    let mut left: std::collections::BTreeSet<_> = vals.iter().copied().collect();
+    for val in vals.iter().filter(|val| *val % 3 != 0) {
+        assert!(t.delete(val));
+        left.remove(val);
+        assert!(t.iter().eq(left.iter()));
+    }
+    assert!(!t.delete(&1));
+    assert_eq!(t.size(), left.len());
+    assert_eq!(t.search(&150), Some(id));
+    for val in left.iter() {
+        assert!(t.delete(val));
+    }
+    assert!(t.is_empty());
+    assert_eq!(t.first(), None);
+}
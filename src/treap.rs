@@ -0,0 +1,237 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Deref;
+
+/// Source of the random priorities of a `Treap`.
+pub trait PriorityRng {
+    fn next_priority(&mut self) -> u64;
+}
+
+impl<F> PriorityRng for F
+where
+    F: FnMut() -> u64,
+{
+    fn next_priority(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// Small xorshift64* generator, the default priority source.
+#[derive(Debug, Clone)]
+pub struct XorShift64(u64);
+
+impl XorShift64 {
+    pub fn with_seed(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift.
+        Self(seed.max(1))
+    }
+}
+
+/// Seeds from the randomness of the standard hasher.
+impl Default for XorShift64 {
+    fn default() -> Self {
+        Self::with_seed(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl PriorityRng for XorShift64 {
+    fn next_priority(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+/// Treap variant of `ArenaTree`: a search tree by value and a max-heap by a random
+/// priority kept as node metadata, so its shape is that of a randomly built tree
+/// and the expected height is O(log n) whatever the insertion order.
+/// See the balanced variants of `ArenaTree` for its API.
+#[derive(Debug, Clone)]
+pub struct Treap<T, C = Natural, R = XorShift64> {
+    tree: ArenaTree<T, C, u64>,
+    rng: R,
+}
+
+impl<T> Default for Treap<T> {
+    fn default() -> Self {
+        Self::with_comparator_and_rng(Natural, XorShift64::default())
+    }
+}
+
+impl<T> Treap<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a treap whose shape only depends on the seed and the operations.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_comparator_and_rng(Natural, XorShift64::with_seed(seed))
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+impl<T, C, R> Deref for Treap<T, C, R> {
+    type Target = ArenaTree<T, C, u64>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, C, R> Treap<T, C, R> {
+    pub fn with_comparator_and_rng(cmp: C, rng: R) -> Self {
+        Self {
+            tree: ArenaTree::with_comparator_and_meta(cmp),
+            rng,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    fn priority(&self, id: Option<NodeId>) -> Option<u64> {
+        self.tree.meta(id?).copied()
+    }
+}
+
+impl<T, C, R> Treap<T, C, R>
+where
+    C: Comparator<T>,
+    R: PriorityRng,
+{
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.tree.search(val)
+    }
+
+    /// Inserts `val` with a fresh priority, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let size = self.tree.size();
+        let id = self.tree.insert(val);
+        if self.tree.size() == size {
+            return id;
+        }
+        let priority = self.rng.next_priority();
+        self.tree.set_meta(id, priority);
+        while let Some(parent_id) = self.tree.parent_of(id) {
+            if self.priority(Some(parent_id)) >= Some(priority) {
+                break;
+            }
+            if self.tree.left_of(parent_id) == Some(id) {
+                self.tree.rotate_right(parent_id);
+            } else {
+                self.tree.rotate_left(parent_id);
+            }
+        }
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Rotates the node equal to `val` down until it has at most one child,
+    /// then unlinks it, returning its value.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.tree.search(val)?;
+        while let (Some(left_id), Some(right_id)) = (self.tree.left_of(id), self.tree.right_of(id))
+        {
+            if self.priority(Some(left_id)) > self.priority(Some(right_id)) {
+                self.tree.rotate_right(id);
+            } else {
+                self.tree.rotate_left(id);
+            }
+        }
+        let (val, _) = self.tree.unlink(id);
+        Some(val)
+    }
+}
+
+#[cfg(test)]
+fn treap_check<T, C, R>(t: &Treap<T, C, R>, id: NodeId) {
+    for child in [t.left_of(id), t.right_of(id)].iter().flatten() {
+        assert!(t.priority(Some(id)) >= t.priority(Some(*child)));
+        treap_check(t, *child);
+    }
+}
+
+#[test]
+fn treap_seeded_shape_is_deterministic() {
+    let mut a = Treap::with_seed(42);
+    let mut b = Treap::with_seed(42);
+    for val in 0..1000 {
+        a.insert(val);
+        b.insert(val);
+    }
+    assert!(a.structurally_eq(&b.tree));
+    treap_check(&a, a.root().unwrap());
+    assert!(a.height() < 50);
+    assert!(a.iter().copied().eq(0..1000));
+}
+
+#[test]
+fn treap_delete_and_custom_rng() {
+    let mut counter = 0;
+    let mut t = Treap::with_comparator_and_rng(Natural, move || {
+        counter += 1;
+        counter
+    });
+    // Later values get higher priorities, so each insert rotates up to the root.
+    for val in [5, 3, 8, 1, 4].iter() {
+        t.insert(*val);
+    }
+    assert_eq!(t.get(t.root().unwrap()), Some(&4));
+
+    assert!(t.delete(&4));
+    assert!(!t.delete(&4));
+    treap_check(&t, t.root().unwrap());
+    assert!(t.iter().copied().eq([1, 3, 5, 8].iter().copied()));
+}
+
+#[test]
+fn treap_take_and_clear() {
+    let mut t = Treap::with_seed(7);
+    for val in 0..200 {
+        t.insert(val);
+    }
+    for val in (0..200).step_by(3) {
+        assert_eq!(t.take(&val), Some(val));
+        treap_check(&t, t.root().unwrap());
+    }
+    assert_eq!(t.take(&0), None);
+    assert_eq!(t.size(), 133);
+    t.clear();
+    assert_eq!((t.size(), t.root()), (0, None));
+    t.insert(1);
+    assert!(t.contains(&1));
+}
@@ -0,0 +1,244 @@
+use crate::arena::NodeId;
+use crate::slots::Slots;
+
+#[derive(Debug)]
+struct TrieNode<V> {
+    val: Option<V>,
+    /// Children sorted by the byte leading to them.
+    children: Vec<(u8, NodeId)>,
+}
+
+impl<V> TrieNode<V> {
+    fn new() -> Self {
+        Self {
+            val: None,
+            children: vec![],
+        }
+    }
+
+    fn child(&self, byte: u8) -> Result<NodeId, usize> {
+        self.children
+            .binary_search_by_key(&byte, |(b, _)| *b)
+            .map(|idx| self.children[idx].1)
+    }
+}
+
+/// Map keyed by byte strings, with one node per byte of the keys,
+/// so every key sharing a prefix is found under the node of that prefix.
+///
+/// Nodes live in an arena like the nodes of `ArenaTree`.
+/// Removing a key also drops the nodes only leading to it.
+#[derive(Debug)]
+pub struct Trie<V> {
+    arena: Slots<TrieNode<V>>,
+    root: NodeId,
+    len: usize,
+}
+
+impl<V> Default for Trie<V> {
+    fn default() -> Self {
+        let mut arena = Slots::default();
+        let root = arena.push(TrieNode::new());
+        Self {
+            arena,
+            root,
+            len: 0,
+        }
+    }
+}
+
+/// Iterator over the entries under a prefix, ordered by key.
+pub struct PrefixIter<'a, V> {
+    trie: &'a Trie<V>,
+    /// Nodes from the prefix down, with the index of the next child of each.
+    stack: Vec<(NodeId, usize)>,
+    /// Node whose value is to be visited before its children.
+    enter: Option<NodeId>,
+    key: Vec<u8>,
+}
+
+impl<'a, V> Iterator for PrefixIter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let trie = self.trie;
+        loop {
+            if let Some(id) = self.enter.take() {
+                self.stack.push((id, 0));
+                if let Some(val) = &trie.arena[id].val {
+                    return Some((self.key.clone(), val));
+                }
+            }
+            let (id, idx) = self.stack.last_mut()?;
+            match trie.arena[*id].children.get(*idx) {
+                Some(&(byte, child)) => {
+                    *idx += 1;
+                    self.key.push(byte);
+                    self.enter = Some(child);
+                }
+                None => {
+                    self.stack.pop();
+                    if !self.stack.is_empty() {
+                        self.key.pop();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<V> Trie<V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Node of `key`, if some key starts with it.
+    fn find(&self, key: &[u8]) -> Option<NodeId> {
+        key.iter()
+            .try_fold(self.root, |id, byte| self.arena[id].child(*byte).ok())
+    }
+
+    /// Inserts the pair, returning the previous value of the key if any.
+    pub fn insert<K>(&mut self, key: K, val: V) -> Option<V>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut id = self.root;
+        for byte in key.as_ref() {
+            id = match self.arena[id].child(*byte) {
+                Ok(child) => child,
+                Err(idx) => {
+                    let child = self.arena.push(TrieNode::new());
+                    self.arena[id].children.insert(idx, (*byte, child));
+                    child
+                }
+            };
+        }
+        let prev = self.arena[id].val.replace(val);
+        if prev.is_none() {
+            self.len += 1;
+        }
+        prev
+    }
+
+    pub fn get<K>(&self, key: K) -> Option<&V>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.arena[self.find(key.as_ref())?].val.as_ref()
+    }
+
+    pub fn get_mut<K>(&mut self, key: K) -> Option<&mut V>
+    where
+        K: AsRef<[u8]>,
+    {
+        let id = self.find(key.as_ref())?;
+        self.arena[id].val.as_mut()
+    }
+
+    pub fn contains_key<K>(&self, key: K) -> bool
+    where
+        K: AsRef<[u8]>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<K>(&mut self, key: K) -> Option<V>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let mut path = Vec::with_capacity(key.len() + 1);
+        path.push(self.root);
+        for byte in key {
+            let id = *path.last().unwrap();
+            path.push(self.arena[id].child(*byte).ok()?);
+        }
+        let val = self.arena[*path.last().unwrap()].val.take()?;
+        self.len -= 1;
+        // Drops the nodes left without values below them, from the bottom up.
+        for (depth, byte) in key.iter().enumerate().rev() {
+            let id = path[depth + 1];
+            let node = &self.arena[id];
+            if node.val.is_some() || !node.children.is_empty() {
+                break;
+            }
+            self.arena.remove(id);
+            let parent = &mut self.arena[path[depth]];
+            let idx = parent
+                .children
+                .binary_search_by_key(byte, |(b, _)| *b)
+                .expect("linked child");
+            parent.children.remove(idx);
+        }
+        Some(val)
+    }
+
+    /// Entries whose keys start with `prefix`, ordered by key.
+    pub fn prefix_iter<K>(&self, prefix: K) -> PrefixIter<'_, V>
+    where
+        K: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        PrefixIter {
+            trie: self,
+            stack: vec![],
+            enter: self.find(prefix),
+            key: prefix.to_vec(),
+        }
+    }
+
+    /// Entries ordered by key.
+    pub fn iter(&self) -> PrefixIter<'_, V> {
+        self.prefix_iter([])
+    }
+}
+
+#[test]
+fn trie_insert_get_remove() {
+    let mut t = Trie::new();
+    for (i, word) in ["tea", "ten", "to", "inn", "in", "tea"].iter().enumerate() {
+        t.insert(word, i);
+    }
+    assert_eq!(t.len(), 5);
+    assert_eq!(t.get("tea"), Some(&5));
+    assert_eq!(t.get("te"), None);
+    assert_eq!(t.get(b"in"), Some(&4));
+    *t.get_mut("to").unwrap() += 10;
+    assert_eq!(t.get("to"), Some(&12));
+
+    assert_eq!(t.remove("te"), None);
+    assert_eq!(t.remove("inn"), Some(3));
+    assert_eq!(t.remove("inn"), None);
+    assert!(t.contains_key("in"));
+    assert_eq!(t.remove("in"), Some(4));
+    assert_eq!(t.len(), 3);
+    // Only the root and the nodes of "tea", "ten" and "to" are left.
+    assert_eq!(t.arena.len(), 6);
+}
+
+#[test]
+fn trie_prefix_iter() {
+    let mut t = Trie::new();
+    for word in ["tea", "ten", "to", "inn", "in", "", "team"].iter() {
+        t.insert(word, word.len());
+    }
+    let keys = |prefix: &str| -> Vec<String> {
+        t.prefix_iter(prefix)
+            .map(|(key, _)| String::from_utf8(key).unwrap())
+            .collect()
+    };
+    assert_eq!(keys("te"), ["tea", "team", "ten"]);
+    assert_eq!(keys("tea"), ["tea", "team"]);
+    assert_eq!(keys("x"), Vec::<String>::new());
+    assert_eq!(keys(""), ["", "in", "inn", "tea", "team", "ten", "to"]);
+    assert_eq!(t.iter().map(|(_, len)| len).sum::<usize>(), 17);
+}
@@ -0,0 +1,148 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::Comparator;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+/// Broken invariant found by `ArenaTree::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The root has a parent.
+    RootHasParent(NodeId),
+    /// The node links to a vacant slot.
+    DanglingLink(NodeId),
+    /// The parent link of the node isn't the node linking to it.
+    ParentMismatch(NodeId),
+    /// The node is linked to more than once, so the links aren't a tree.
+    Cycle(NodeId),
+    /// Some live nodes can't be reached from the root.
+    Unreachable { reachable: usize, live: usize },
+    /// The value of the node is on the wrong side of one of its ancestors.
+    OutOfOrder(NodeId),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::RootHasParent(id) => write!(f, "root #{} has a parent", id),
+            ValidationError::DanglingLink(id) => write!(f, "node #{} links to a vacant slot", id),
+            ValidationError::ParentMismatch(id) => {
+                write!(f, "parent link of node #{} doesn't match", id)
+            }
+            ValidationError::Cycle(id) => write!(f, "node #{} is linked more than once", id),
+            ValidationError::Unreachable { reachable, live } => {
+                write!(f, "only {} of {} nodes are reachable", reachable, live)
+            }
+            ValidationError::OutOfOrder(id) => {
+                write!(f, "node #{} violates the BST ordering", id)
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl<T, C, M> ArenaTree<T, C, M>
+where
+    C: Comparator<T>,
+{
+    /// Checks the links and the ordering of the whole tree, returning the first
+    /// broken invariant found. Takes O(n) time and memory, without recursion.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let root = match self.root() {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+        if self.get(root).is_none() {
+            return Err(ValidationError::DanglingLink(root));
+        }
+        if self.parent_of(root).is_some() {
+            return Err(ValidationError::RootHasParent(root));
+        }
+        let mut seen = HashSet::with_capacity(self.size());
+        // Nodes along with the ids of the closest ancestors bounding their values.
+        let mut stack = vec![(root, None, None)];
+        while let Some((id, lower, upper)) = stack.pop() {
+            if !seen.insert(id) {
+                return Err(ValidationError::Cycle(id));
+            }
+            let val = self.get(id).expect("checked by the parent");
+            let in_order = |bound: Option<NodeId>, ord: Ordering| {
+                bound.is_none_or(|bound| {
+                    let bound = self.get(bound).expect("visited ancestor");
+                    self.comparator().compare(val, bound) == ord
+                })
+            };
+            if !in_order(lower, Ordering::Greater) || !in_order(upper, Ordering::Less) {
+                return Err(ValidationError::OutOfOrder(id));
+            }
+            let children = [
+                (self.left_of(id), lower, Some(id)),
+                (self.right_of(id), Some(id), upper),
+            ];
+            for (child, lower, upper) in children.iter().copied() {
+                let child = match child {
+                    Some(child) => child,
+                    None => continue,
+                };
+                if self.get(child).is_none() {
+                    return Err(ValidationError::DanglingLink(id));
+                }
+                if self.parent_of(child) != Some(id) {
+                    return Err(ValidationError::ParentMismatch(child));
+                }
+                stack.push((child, lower, upper));
+            }
+        }
+        if seen.len() != self.size() {
+            return Err(ValidationError::Unreachable {
+                reachable: seen.len(),
+                live: self.size(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn validate_sound_trees() {
+    let mut t = ArenaTree::from_vec((0..200).map(|val| val * 37 % 200).collect());
+    assert_eq!(t.validate(), Ok(()));
+    for val in (0..200).step_by(3) {
+        t.delete(&val);
+        assert_eq!(t.validate(), Ok(()));
+    }
+    assert_eq!(ArenaTree::<i32>::default().validate(), Ok(()));
+}
+
+#[test]
+fn validate_broken_trees() {
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8]);
+    let root = t.root().unwrap();
+    let three = t.left_of(root).unwrap();
+    // 6 below 3 is greater than 5.
+    let six = t.attach(Some((three, false)), 6);
+    assert_eq!(t.validate(), Err(ValidationError::OutOfOrder(six)));
+
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8]);
+    let root = t.root().unwrap();
+    let three = t.left_of(root).unwrap();
+    t.link(three, true, Some(root));
+    assert_eq!(t.validate(), Err(ValidationError::RootHasParent(root)));
+
+    let mut t = ArenaTree::from_vec(vec![5, 3, 8]);
+    let root = t.root().unwrap();
+    t.link(root, false, None);
+    assert_eq!(
+        t.validate(),
+        Err(ValidationError::Unreachable {
+            reachable: 2,
+            live: 3
+        })
+    );
+    assert_eq!(
+        t.validate().unwrap_err().to_string(),
+        "only 2 of 3 nodes are reachable"
+    );
+}
@@ -0,0 +1,282 @@
+use crate::arena::{ArenaTree, NodeId};
+use crate::compare::{Comparator, Natural};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::ops::Deref;
+
+/// Weight-balanced BB[α] variant of `ArenaTree`.
+///
+/// Nodes keep the size of their subtree as metadata. Weighting a subtree as its size plus one,
+/// each child weighs at least `alpha` of its parent, so the height stays within
+/// `log(n) / log(1 / (1 - alpha))`. The sizes also answer `select` and `rank` in O(log n).
+/// See the balanced variants of `ArenaTree` for its API.
+#[derive(Debug, Clone)]
+pub struct WbtArenaTree<T, C = Natural> {
+    tree: ArenaTree<T, C, usize>,
+    alpha: f64,
+}
+
+impl<T> Default for WbtArenaTree<T> {
+    fn default() -> Self {
+        Self::with_comparator(Natural)
+    }
+}
+
+impl<T> WbtArenaTree<T>
+where
+    T: Ord,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Panics unless `2/11 < alpha <= 1 - 1/√2`, the range where
+    /// a single or double rotation always restores the balance.
+    pub fn with_alpha(alpha: f64) -> Self {
+        Self::with_comparator_and_alpha(Natural, alpha)
+    }
+
+    pub fn from_vec(v: Vec<T>) -> Self {
+        let mut t = Self::default();
+        for val in v.into_iter() {
+            t.insert(val);
+        }
+        t
+    }
+}
+
+impl<T, C> Deref for WbtArenaTree<T, C> {
+    type Target = ArenaTree<T, C, usize>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T, C> WbtArenaTree<T, C> {
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_comparator_and_alpha(cmp, 0.25)
+    }
+
+    pub fn with_comparator_and_alpha(cmp: C, alpha: f64) -> Self {
+        assert!(
+            alpha > 2.0 / 11.0 && alpha <= 1.0 - std::f64::consts::FRAC_1_SQRT_2,
+            "alpha out of (2/11, 1 - 1/√2]"
+        );
+        Self {
+            tree: ArenaTree::with_comparator_and_meta(cmp),
+            alpha,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    fn size_of(&self, id: Option<NodeId>) -> usize {
+        id.and_then(|id| self.tree.meta(id)).copied().unwrap_or(0)
+    }
+
+    fn weight_of(&self, id: Option<NodeId>) -> f64 {
+        (self.size_of(id) + 1) as f64
+    }
+
+    fn update(&mut self, id: NodeId) {
+        let size = 1 + self.size_of(self.tree.left_of(id)) + self.size_of(self.tree.right_of(id));
+        self.tree.set_meta(id, size);
+    }
+
+    /// Share of the weight of `id` held by its left subtree.
+    fn left_share(&self, id: NodeId) -> f64 {
+        self.weight_of(self.tree.left_of(id)) / self.weight_of(Some(id))
+    }
+
+    fn rotate_left(&mut self, id: NodeId) -> NodeId {
+        let top = self.tree.rotate_left(id);
+        self.update(id);
+        self.update(top);
+        top
+    }
+
+    fn rotate_right(&mut self, id: NodeId) -> NodeId {
+        let top = self.tree.rotate_right(id);
+        self.update(id);
+        self.update(top);
+        top
+    }
+
+    /// Fixes the sizes from `start` up to the root, rotating where unbalanced.
+    fn rebalance(&mut self, start: Option<NodeId>) {
+        let threshold = (1.0 - 2.0 * self.alpha) / (1.0 - self.alpha);
+        let mut cur = start;
+        while let Some(mut id) = cur {
+            self.update(id);
+            let share = self.left_share(id);
+            if share < self.alpha {
+                let right_id = self.tree.right_of(id).expect("right heavy");
+                if self.left_share(right_id) > threshold {
+                    self.rotate_right(right_id);
+                }
+                id = self.rotate_left(id);
+            } else if share > 1.0 - self.alpha {
+                let left_id = self.tree.left_of(id).expect("left heavy");
+                if 1.0 - self.left_share(left_id) > threshold {
+                    self.rotate_left(left_id);
+                }
+                id = self.rotate_right(id);
+            }
+            cur = self.tree.parent_of(id);
+        }
+    }
+
+    /// The `k`-th smallest value, counting from 0.
+    pub fn select(&self, mut k: usize) -> Option<&T> {
+        let mut cur = self.tree.root();
+        while let Some(id) = cur {
+            let left = self.size_of(self.tree.left_of(id));
+            cur = match k.cmp(&left) {
+                Ordering::Less => self.tree.left_of(id),
+                Ordering::Equal => return self.tree.get(id),
+                Ordering::Greater => {
+                    k -= left + 1;
+                    self.tree.right_of(id)
+                }
+            };
+        }
+        None
+    }
+}
+
+impl<T, C> WbtArenaTree<T, C>
+where
+    C: Comparator<T>,
+{
+    pub fn search<Q>(&self, val: &Q) -> Option<NodeId>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.tree.search(val)
+    }
+
+    /// Number of values smaller than `val`.
+    pub fn rank<Q>(&self, val: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let mut rank = 0;
+        let mut cur = self.tree.root();
+        while let Some(id) = cur {
+            let node_val = self.tree.get(id).expect("linked node");
+            cur = match self.tree.comparator().compare(val, node_val.borrow()) {
+                Ordering::Less => self.tree.left_of(id),
+                Ordering::Equal => return rank + self.size_of(self.tree.left_of(id)),
+                Ordering::Greater => {
+                    rank += self.size_of(self.tree.left_of(id)) + 1;
+                    self.tree.right_of(id)
+                }
+            };
+        }
+        rank
+    }
+
+    /// Inserts `val` and rebalances, returning the id of the new node,
+    /// or of the existing node if an equal value is already present.
+    pub fn insert(&mut self, val: T) -> NodeId {
+        let size = self.tree.size();
+        let id = self.tree.insert(val);
+        if self.tree.size() != size {
+            self.rebalance(Some(id));
+        }
+        id
+    }
+
+    pub fn delete<Q>(&mut self, val: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        self.take(val).is_some()
+    }
+
+    /// Removes the value equal to `val` and rebalances, returning it.
+    pub fn take<Q>(&mut self, val: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+        Q: ?Sized,
+    {
+        let id = self.tree.search(val)?;
+        let (val, start) = self.tree.unlink(id);
+        self.rebalance(start);
+        Some(val)
+    }
+}
+
+#[cfg(test)]
+fn wbt_check<T, C>(t: &WbtArenaTree<T, C>, id: Option<NodeId>) -> usize {
+    match id {
+        None => 0,
+        Some(id) => {
+            let size = 1 + wbt_check(t, t.left_of(id)) + wbt_check(t, t.right_of(id));
+            assert_eq!(t.meta(id), Some(&size));
+            let share = t.left_share(id);
+            assert!(share >= t.alpha() && share <= 1.0 - t.alpha());
+            size
+        }
+    }
+}
+
+#[test]
+fn wbt_balance_and_order_statistics() {
+    let mut t = WbtArenaTree::with_alpha(0.29);
+    for val in 0..1000 {
+        t.insert(val);
+    }
+    wbt_check(&t, t.root());
+    assert!(t.height() <= 21);
+    assert_eq!(t.select(0), Some(&0));
+    assert_eq!(t.select(500), Some(&500));
+    assert_eq!(t.select(1000), None);
+    assert_eq!(t.rank(&500), 500);
+    assert_eq!(t.rank(&5000), 1000);
+}
+
+#[test]
+fn wbt_delete_rebalances() {
+    let mut t = WbtArenaTree::from_vec((0..200).collect());
+    for val in (0..200).filter(|val| val % 4 != 0) {
+        assert!(t.delete(&val));
+        wbt_check(&t, t.root());
+    }
+    assert!(!t.delete(&1));
+    assert_eq!(t.size(), 50);
+    assert_eq!(t.select(10), Some(&40));
+    assert_eq!(t.rank(&41), 11);
+}
+
+#[test]
+fn wbt_take_and_clear() {
+    let mut t = WbtArenaTree::new();
+    for val in 0..200 {
+        t.insert(val);
+    }
+    for val in (0..200).step_by(3) {
+        assert_eq!(t.take(&val), Some(val));
+        wbt_check(&t, t.root());
+    }
+    assert_eq!(t.take(&0), None);
+    assert_eq!(t.select(0), Some(&1));
+    t.clear();
+    assert_eq!((t.size(), t.root()), (0, None));
+    t.insert(1);
+    wbt_check(&t, t.root());
+}